@@ -1,18 +1,21 @@
 //! Agent configuration and preset loading
 //!
-//! These types and functions are reserved public API for future use.
-
-#![allow(dead_code)] // Reserved API: agent preset loading will be used when config file support is added
+//! Built-in presets mirror the Node.js `DEFAULT_MODELS_CONFIG`; entries in
+//! `~/.codeagent/agents.yaml` replace the built-in preset with the same name.
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use crate::backend::backend_chain;
+use crate::errors::ConfigError;
 
 /// Agent configuration
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AgentConfig {
-    /// Agent name
+    /// Agent name (defaults to the key in agents.yaml)
+    #[serde(default)]
     pub name: String,
     /// Model to use
     #[serde(default)]
@@ -29,9 +32,14 @@ pub struct AgentConfig {
     /// Prompt prefix
     #[serde(default, rename = "promptPrefix")]
     pub prompt_prefix: Option<String>,
+    /// Backends to try, in order, when the main one is unavailable or fails
+    /// early (`name` or `name:model`)
+    #[serde(default)]
+    pub fallback: Vec<String>,
 }
 
 /// Models configuration
+#[allow(dead_code)] // Reserved: models.yaml is not consumed yet
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ModelsConfig {
     /// Available models by backend
@@ -40,6 +48,7 @@ pub struct ModelsConfig {
 }
 
 /// Model information
+#[allow(dead_code)] // Reserved: models.yaml is not consumed yet
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelInfo {
     /// Model name/ID
@@ -53,34 +62,78 @@ pub struct ModelInfo {
 }
 
 /// Get config directory path
-fn get_config_dir() -> PathBuf {
+pub fn get_config_dir() -> PathBuf {
     let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
     home.join(".codeagent")
 }
 
-/// Load agent configuration by name
-pub async fn get_agent_config(name: &str) -> Result<AgentConfig> {
-    let config_dir = get_config_dir();
-    let agents_file = config_dir.join("agents.yaml");
+/// Built-in agent presets (matches Node.js `DEFAULT_MODELS_CONFIG`)
+pub fn default_agents() -> BTreeMap<String, AgentConfig> {
+    let presets: [(&str, &str, Option<&str>); 6] = [
+        ("oracle", "claude", Some("claude-opus-4-5-20251101")),
+        ("librarian", "claude", Some("claude-sonnet-4-5-20250929")),
+        ("explore", "opencode", Some("opencode/grok-code")),
+        ("develop", "codex", None),
+        ("frontend-ui-ux-engineer", "gemini", None),
+        ("document-writer", "gemini", None),
+    ];
+
+    presets
+        .into_iter()
+        .map(|(name, backend, model)| {
+            let config = AgentConfig {
+                name: name.to_string(),
+                model: model.map(str::to_string),
+                backend: Some(backend.to_string()),
+                ..Default::default()
+            };
+            (name.to_string(), config)
+        })
+        .collect()
+}
+
+/// Load agent presets, overlaying the given agents.yaml on the built-in defaults
+pub async fn load_agents(agents_file: &Path) -> Result<BTreeMap<String, AgentConfig>> {
+    let mut agents = default_agents();
 
     if !agents_file.exists() {
-        return Err(anyhow::anyhow!("Agent config not found: {}", name));
+        return Ok(agents);
     }
 
-    let content = tokio::fs::read_to_string(&agents_file)
+    let content = tokio::fs::read_to_string(agents_file)
         .await
         .with_context(|| format!("Failed to read agents config: {}", agents_file.display()))?;
 
-    let agents: HashMap<String, AgentConfig> =
+    let user_agents: HashMap<String, AgentConfig> =
         serde_yaml::from_str(&content).with_context(|| "Failed to parse agents.yaml")?;
 
-    agents
-        .get(name)
-        .cloned()
-        .ok_or_else(|| anyhow::anyhow!("Agent not found: {}", name))
+    for (name, mut config) in user_agents {
+        if config.name.is_empty() {
+            config.name = name.clone();
+        }
+        agents.insert(name, config);
+    }
+
+    Ok(agents)
+}
+
+/// Load agent configuration by name
+pub async fn get_agent_config(name: &str) -> Result<AgentConfig> {
+    let agents_file = get_config_dir().join("agents.yaml");
+    let agents = load_agents(&agents_file).await?;
+    lookup_agent(&agents, name)
+}
+
+/// Look up an agent preset, failing with the list of known agents
+fn lookup_agent(agents: &BTreeMap<String, AgentConfig>, name: &str) -> Result<AgentConfig> {
+    agents.get(name).cloned().ok_or_else(|| {
+        let available = agents.keys().cloned().collect::<Vec<_>>().join(", ");
+        ConfigError::UnknownAgent(name.to_string(), available).into()
+    })
 }
 
 /// Load models configuration
+#[allow(dead_code)] // Reserved: models.yaml is not consumed yet
 pub async fn load_models_config() -> Result<ModelsConfig> {
     let config_dir = get_config_dir();
     let models_file = config_dir.join("models.yaml");
@@ -100,12 +153,27 @@ pub async fn load_models_config() -> Result<ModelsConfig> {
 }
 
 /// Merge agent config into runtime config
+///
+/// The preset's model only applies to the preset's backend: when `--backend`,
+/// a session record or a parallel spec picks another one, that backend keeps
+/// its default model.
 pub fn merge_agent_config(
     model: Option<String>,
     backend: Option<String>,
     agent_config: &AgentConfig,
 ) -> (Option<String>, Option<String>) {
-    let model = model.or_else(|| agent_config.model.clone());
+    let preset_backend = agent_config.backend.as_deref().map(str::to_lowercase);
+    let same_backend = match backend {
+        None => true,
+        Some(ref backend) => {
+            backend_chain(Some(backend), &[]).first().map(|c| &c.name) == preset_backend.as_ref()
+        }
+    };
+    let model = match model {
+        Some(model) => Some(model),
+        None if same_backend => agent_config.model.clone(),
+        None => None,
+    };
     let backend = backend.or_else(|| agent_config.backend.clone());
     (model, backend)
 }
@@ -130,6 +198,67 @@ env:
         assert!(config.skip_permissions);
     }
 
+    #[test]
+    fn test_default_agents() {
+        let agents = default_agents();
+        assert_eq!(agents.len(), 6);
+        assert_eq!(agents["oracle"].backend, Some("claude".to_string()));
+        assert_eq!(
+            agents["oracle"].model,
+            Some("claude-opus-4-5-20251101".to_string())
+        );
+        assert_eq!(agents["develop"].backend, Some("codex".to_string()));
+        assert_eq!(agents["develop"].model, None);
+    }
+
+    #[tokio::test]
+    async fn test_load_agents_overrides_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("agents.yaml");
+        std::fs::write(
+            &file,
+            r#"
+oracle:
+  backend: codex
+  model: gpt-5
+custom:
+  backend: gemini
+  fallback: [claude, "codex:gpt-5"]
+  promptPrefix: "Be brief."
+  env:
+    FOO: bar
+"#,
+        )
+        .unwrap();
+
+        let agents = load_agents(&file).await.unwrap();
+        assert_eq!(agents["oracle"].backend, Some("codex".to_string()));
+        assert_eq!(agents["oracle"].name, "oracle");
+        assert_eq!(
+            agents["custom"].prompt_prefix,
+            Some("Be brief.".to_string())
+        );
+        assert_eq!(agents["custom"].env["FOO"], "bar");
+        assert_eq!(agents["custom"].fallback, vec!["claude", "codex:gpt-5"]);
+        assert!(agents.contains_key("librarian"));
+    }
+
+    #[tokio::test]
+    async fn test_load_agents_missing_file() {
+        let agents = load_agents(Path::new("/nonexistent/agents.yaml"))
+            .await
+            .unwrap();
+        assert_eq!(agents.len(), default_agents().len());
+    }
+
+    #[test]
+    fn test_unknown_agent() {
+        let err = lookup_agent(&default_agents(), "nope").unwrap_err();
+        let config_err = err.downcast_ref::<ConfigError>().unwrap();
+        assert!(matches!(config_err, ConfigError::UnknownAgent(_, _)));
+        assert!(err.to_string().contains("oracle"));
+    }
+
     #[test]
     fn test_merge_agent_config() {
         let agent = AgentConfig {
@@ -148,5 +277,9 @@ env:
         let (model, backend) = merge_agent_config(None, None, &agent);
         assert_eq!(model, Some("default-model".to_string()));
         assert_eq!(backend, Some("claude".to_string()));
+
+        // The preset's model still applies when the same backend is named
+        let (model, _) = merge_agent_config(None, Some("Claude".to_string()), &agent);
+        assert_eq!(model, Some("default-model".to_string()));
     }
 }
@@ -1,37 +1,94 @@
 //! Backend implementations for different AI CLI tools
 
 use anyhow::Result;
+use serde::Serialize;
+use serde_json::Value;
 use std::sync::Arc;
 
+use crate::backend_config::{
+    BUILTIN_BACKENDS, CustomBackendConfig, EventDialect, backends_file, load_custom_backends,
+};
 use crate::config::Config;
 use crate::errors::BackendError;
+use crate::parser::{AgentEvent, BackendType, detect_backend_type};
+
+/// How a backend handles tool permission prompts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PermissionMode {
+    /// Prompts by default; `--skip-permissions` turns prompts off
+    Configurable,
+    /// Always runs without prompts
+    AlwaysSkipped,
+    /// No permission flag; `--skip-permissions` has no effect
+    Unsupported,
+}
+
+/// Options a backend can honour
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    /// Accepts `--model`
+    pub model: bool,
+    /// Can resume a session
+    pub resume: bool,
+    /// Accepts `--reasoning-effort`
+    pub reasoning_effort: bool,
+    /// Permission prompt handling
+    pub permissions: PermissionMode,
+    /// Takes the working directory as a flag (otherwise only the process cwd is set)
+    pub workdir_flag: bool,
+    /// Can read the prompt from stdin
+    pub stdin_prompt: bool,
+    /// Can list its models
+    pub model_listing: bool,
+}
 
 /// Backend trait defining the interface for AI CLI backends
 pub trait Backend: Send + Sync {
     /// Get backend name
-    fn name(&self) -> &'static str;
+    fn name(&self) -> &str;
 
     /// Get command name to execute
-    fn command(&self) -> &'static str;
+    fn command(&self) -> &str;
 
     /// Build command arguments
     fn build_args(&self, config: &Config, target: &str) -> Vec<String>;
 
+    /// Options this backend supports
+    fn capabilities(&self) -> Capabilities;
+
     /// Check if backend is available (command exists)
     fn is_available(&self) -> bool {
         which::which(self.command()).is_ok()
     }
+
+    /// Whether the prompt goes on stdin; `None` decides per task (long prompts
+    /// and shell metacharacters use stdin)
+    fn prompt_on_stdin(&self) -> Option<bool> {
+        (!self.capabilities().stdin_prompt).then_some(false)
+    }
+
+    /// How to install the backend when it is missing
+    fn install_hint(&self) -> String {
+        install_hint(self.name())
+    }
+
+    /// Translate one raw JSON event into normalized events
+    fn translate_event(&self, value: &Value) -> Vec<AgentEvent> {
+        translate_detected_event(value)
+    }
 }
 
 /// Codex backend implementation
 pub struct CodexBackend;
 
 impl Backend for CodexBackend {
-    fn name(&self) -> &'static str {
+    fn name(&self) -> &str {
         "codex"
     }
 
-    fn command(&self) -> &'static str {
+    fn command(&self) -> &str {
         "codex"
     }
 
@@ -65,17 +122,33 @@ impl Backend for CodexBackend {
         args.push(target.to_string());
         args
     }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            model: true,
+            resume: true,
+            reasoning_effort: true,
+            permissions: PermissionMode::Configurable,
+            workdir_flag: true,
+            stdin_prompt: true,
+            model_listing: false,
+        }
+    }
+
+    fn translate_event(&self, value: &Value) -> Vec<AgentEvent> {
+        translate_codex_event(value)
+    }
 }
 
 /// Claude backend implementation
 pub struct ClaudeBackend;
 
 impl Backend for ClaudeBackend {
-    fn name(&self) -> &'static str {
+    fn name(&self) -> &str {
         "claude"
     }
 
-    fn command(&self) -> &'static str {
+    fn command(&self) -> &str {
         "claude"
     }
 
@@ -106,17 +179,33 @@ impl Backend for ClaudeBackend {
         args.push(target.to_string());
         args
     }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            model: true,
+            resume: true,
+            reasoning_effort: false,
+            permissions: PermissionMode::Configurable,
+            workdir_flag: false,
+            stdin_prompt: true,
+            model_listing: false,
+        }
+    }
+
+    fn translate_event(&self, value: &Value) -> Vec<AgentEvent> {
+        translate_claude_event(value)
+    }
 }
 
 /// Gemini backend implementation
 pub struct GeminiBackend;
 
 impl Backend for GeminiBackend {
-    fn name(&self) -> &'static str {
+    fn name(&self) -> &str {
         "gemini"
     }
 
-    fn command(&self) -> &'static str {
+    fn command(&self) -> &str {
         "gemini"
     }
 
@@ -140,17 +229,33 @@ impl Backend for GeminiBackend {
         args.push(target.to_string());
         args
     }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            model: true,
+            resume: true,
+            reasoning_effort: false,
+            permissions: PermissionMode::AlwaysSkipped,
+            workdir_flag: false,
+            stdin_prompt: true,
+            model_listing: false,
+        }
+    }
+
+    fn translate_event(&self, value: &Value) -> Vec<AgentEvent> {
+        translate_gemini_event(value)
+    }
 }
 
 /// Opencode backend implementation
 pub struct OpencodeBackend;
 
 impl Backend for OpencodeBackend {
-    fn name(&self) -> &'static str {
+    fn name(&self) -> &str {
         "opencode"
     }
 
-    fn command(&self) -> &'static str {
+    fn command(&self) -> &str {
         "opencode"
     }
 
@@ -174,6 +279,544 @@ impl Backend for OpencodeBackend {
         args.push(target.to_string());
         args
     }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            model: true,
+            resume: true,
+            reasoning_effort: false,
+            permissions: PermissionMode::Unsupported,
+            workdir_flag: false,
+            stdin_prompt: false,
+            model_listing: true,
+        }
+    }
+
+    fn translate_event(&self, value: &Value) -> Vec<AgentEvent> {
+        translate_opencode_event(value)
+    }
+}
+
+/// Replays a recorded event stream (see `mock.rs`) chosen with `--mock-file`
+pub struct MockBackend {
+    command: String,
+}
+
+impl MockBackend {
+    /// Create the mock backend, which runs this binary's `mock-replay` command
+    pub fn new() -> Self {
+        let command = std::env::current_exe()
+            .map(|path| path.display().to_string())
+            .unwrap_or_else(|_| "codeagent".to_string());
+        Self { command }
+    }
+}
+
+impl Backend for MockBackend {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    fn command(&self) -> &str {
+        &self.command
+    }
+
+    fn build_args(&self, config: &Config, target: &str) -> Vec<String> {
+        let mut args = vec!["mock-replay".to_string()];
+        args.extend(config.mock.replay_args());
+
+        if let Some(ref session_id) = config.session_id {
+            args.push("--session".to_string());
+            args.push(session_id.clone());
+        }
+
+        args.push("--".to_string());
+        args.push(target.to_string());
+        args
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            model: true,
+            resume: true,
+            reasoning_effort: false,
+            // Replays never prompt, so either choice is honoured
+            permissions: PermissionMode::Configurable,
+            workdir_flag: false,
+            stdin_prompt: true,
+            model_listing: false,
+        }
+    }
+}
+
+/// Backend declared in `~/.codeagent/backends.yaml`
+pub struct CustomBackend {
+    config: CustomBackendConfig,
+}
+
+impl CustomBackend {
+    /// Create a backend from its backends.yaml entry
+    pub fn new(config: CustomBackendConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Backend for CustomBackend {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn command(&self) -> &str {
+        &self.config.command
+    }
+
+    fn build_args(&self, config: &Config, target: &str) -> Vec<String> {
+        let workdir = config.work_dir.display().to_string();
+        let render = |templates: &[String]| -> Vec<String> {
+            templates
+                .iter()
+                .map(|arg| {
+                    arg.replace("{model}", config.model.as_deref().unwrap_or_default())
+                        .replace(
+                            "{session}",
+                            config.session_id.as_deref().unwrap_or_default(),
+                        )
+                        .replace("{workdir}", &workdir)
+                        .replace("{prompt}", target)
+                })
+                .collect()
+        };
+
+        let mut args = render(&self.config.args);
+        args.extend(render(&self.config.workdir));
+        if config.session_id.is_some() {
+            args.extend(render(&self.config.session));
+        }
+        if config.model.is_some() {
+            args.extend(render(&self.config.model));
+        }
+        if config.skip_permissions {
+            args.extend(render(&self.config.permissions));
+        }
+        if !self.config.stdin {
+            args.extend(render(&self.config.prompt));
+        }
+        args
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            model: !self.config.model.is_empty(),
+            resume: !self.config.session.is_empty(),
+            reasoning_effort: false,
+            permissions: if self.config.permissions.is_empty() {
+                PermissionMode::Unsupported
+            } else {
+                PermissionMode::Configurable
+            },
+            workdir_flag: !self.config.workdir.is_empty(),
+            stdin_prompt: self.config.stdin,
+            model_listing: false,
+        }
+    }
+
+    fn prompt_on_stdin(&self) -> Option<bool> {
+        Some(self.config.stdin)
+    }
+
+    fn install_hint(&self) -> String {
+        format!(
+            "`{}` on PATH, or fix `command` for '{}' in {}",
+            self.config.command,
+            self.config.name,
+            backends_file().display()
+        )
+    }
+
+    fn translate_event(&self, value: &Value) -> Vec<AgentEvent> {
+        match self.config.dialect {
+            EventDialect::Auto => translate_detected_event(value),
+            EventDialect::Claude => translate_claude_event(value),
+            EventDialect::Codex => translate_codex_event(value),
+            EventDialect::Gemini => translate_gemini_event(value),
+            EventDialect::Opencode => translate_opencode_event(value),
+        }
+    }
+}
+
+/// Get a string field, treating empty strings as missing
+fn str_field(value: &Value, key: &str) -> Option<String> {
+    value
+        .get(key)
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+/// Get a numeric field (missing counts as zero)
+fn u64_field(value: &Value, pointer: &str) -> u64 {
+    value.pointer(pointer).and_then(|v| v.as_u64()).unwrap_or(0)
+}
+
+/// Render tool output that may be a string or an array of text blocks
+fn output_text(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(blocks)) => blocks
+            .iter()
+            .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Some(Value::Null) | None => String::new(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// Translate an event whose dialect is detected from its structure
+pub fn translate_detected_event(value: &Value) -> Vec<AgentEvent> {
+    translate_event_as(detect_backend_type(value), value)
+}
+
+/// Translate an event in the given dialect
+pub fn translate_event_as(backend_type: BackendType, value: &Value) -> Vec<AgentEvent> {
+    match backend_type {
+        BackendType::Codex => translate_codex_event(value),
+        BackendType::Claude => translate_claude_event(value),
+        BackendType::Gemini => translate_gemini_event(value),
+        BackendType::Opencode => translate_opencode_event(value),
+        BackendType::Unknown => str_field(value, "session_id")
+            .or_else(|| str_field(value, "sessionId"))
+            .map(|session_id| vec![AgentEvent::SessionStarted { session_id }])
+            .unwrap_or_default(),
+    }
+}
+
+/// Translate `codex exec --json` events
+fn translate_codex_event(value: &Value) -> Vec<AgentEvent> {
+    let event_type = value.get("type").and_then(|t| t.as_str()).unwrap_or("");
+
+    match event_type {
+        "thread.started" => str_field(value, "thread_id")
+            .map(|session_id| vec![AgentEvent::SessionStarted { session_id }])
+            .unwrap_or_default(),
+        "item.started" | "item.updated" | "item.completed" => {
+            let Some(item) = value.get("item") else {
+                return Vec::new();
+            };
+            translate_codex_item(item, event_type == "item.completed")
+        }
+        "turn.completed" => vec![
+            AgentEvent::Usage {
+                input_tokens: u64_field(value, "/usage/input_tokens"),
+                output_tokens: u64_field(value, "/usage/output_tokens"),
+            },
+            AgentEvent::Completed {
+                success: true,
+                result: None,
+            },
+        ],
+        "turn.failed" => {
+            let message = value
+                .pointer("/error/message")
+                .and_then(|m| m.as_str())
+                .unwrap_or("turn failed")
+                .to_string();
+            vec![
+                AgentEvent::Error { message },
+                AgentEvent::Completed {
+                    success: false,
+                    result: None,
+                },
+            ]
+        }
+        "error" => str_field(value, "message")
+            .map(|message| vec![AgentEvent::Error { message }])
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// Translate a Codex thread item
+fn translate_codex_item(item: &Value, completed: bool) -> Vec<AgentEvent> {
+    let id = str_field(item, "id");
+    let item_type = item.get("type").and_then(|t| t.as_str()).unwrap_or("");
+
+    match (item_type, completed) {
+        ("agent_message" | "assistant_message", true) => str_field(item, "text")
+            .or_else(|| str_field(item, "content"))
+            .map(|text| vec![AgentEvent::AssistantText { text, delta: false }])
+            .unwrap_or_default(),
+        ("reasoning", true) => str_field(item, "text")
+            .map(|text| vec![AgentEvent::Thinking { text }])
+            .unwrap_or_default(),
+        ("command_execution", false) => vec![AgentEvent::ToolCall {
+            id,
+            name: "shell".to_string(),
+            input: serde_json::json!({ "command": item.get("command") }),
+        }],
+        ("command_execution", true) => vec![AgentEvent::ToolResult {
+            id,
+            name: Some("shell".to_string()),
+            output: output_text(item.get("aggregated_output")),
+            is_error: item
+                .get("exit_code")
+                .and_then(|c| c.as_i64())
+                .is_some_and(|c| c != 0),
+        }],
+        ("file_change", true) => vec![AgentEvent::ToolCall {
+            id,
+            name: "file_change".to_string(),
+            input: serde_json::json!({ "changes": item.get("changes") }),
+        }],
+        ("mcp_tool_call", false) => {
+            let server = item.get("server").and_then(|s| s.as_str()).unwrap_or("mcp");
+            let tool = item.get("tool").and_then(|t| t.as_str()).unwrap_or("tool");
+            vec![AgentEvent::ToolCall {
+                id,
+                name: format!("{}.{}", server, tool),
+                input: item.get("arguments").cloned().unwrap_or(Value::Null),
+            }]
+        }
+        ("mcp_tool_call", true) => vec![AgentEvent::ToolResult {
+            id,
+            name: str_field(item, "tool"),
+            output: output_text(item.pointer("/result/content").or(item.get("error"))),
+            is_error: item.get("status").and_then(|s| s.as_str()) == Some("failed"),
+        }],
+        ("web_search", false) => vec![AgentEvent::ToolCall {
+            id,
+            name: "web_search".to_string(),
+            input: serde_json::json!({ "query": item.get("query") }),
+        }],
+        ("error", true) => str_field(item, "message")
+            .map(|message| vec![AgentEvent::Error { message }])
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// Translate `claude -p --output-format stream-json` events
+fn translate_claude_event(value: &Value) -> Vec<AgentEvent> {
+    let event_type = value.get("type").and_then(|t| t.as_str()).unwrap_or("");
+
+    match event_type {
+        "system" => match (
+            value.get("subtype").and_then(|s| s.as_str()),
+            str_field(value, "session_id"),
+        ) {
+            (Some("init"), Some(session_id)) => vec![AgentEvent::SessionStarted { session_id }],
+            _ => Vec::new(),
+        },
+        "assistant" | "user" => {
+            let Some(blocks) = value.pointer("/message/content").and_then(|c| c.as_array()) else {
+                return Vec::new();
+            };
+            blocks.iter().filter_map(translate_claude_block).collect()
+        }
+        "result" => {
+            let is_error = value
+                .get("is_error")
+                .and_then(|e| e.as_bool())
+                .unwrap_or(false)
+                || value
+                    .get("subtype")
+                    .and_then(|s| s.as_str())
+                    .is_some_and(|s| s != "success");
+            let result = str_field(value, "result");
+            let mut events = Vec::new();
+            if value.get("usage").is_some() {
+                events.push(AgentEvent::Usage {
+                    input_tokens: u64_field(value, "/usage/input_tokens"),
+                    output_tokens: u64_field(value, "/usage/output_tokens"),
+                });
+            }
+            if is_error {
+                events.push(AgentEvent::Error {
+                    message: result
+                        .clone()
+                        .unwrap_or_else(|| "execution failed".to_string()),
+                });
+            }
+            events.push(AgentEvent::Completed {
+                success: !is_error,
+                result,
+            });
+            events
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Translate one Claude message content block
+fn translate_claude_block(block: &Value) -> Option<AgentEvent> {
+    match block.get("type").and_then(|t| t.as_str())? {
+        "text" => {
+            str_field(block, "text").map(|text| AgentEvent::AssistantText { text, delta: false })
+        }
+        "thinking" => str_field(block, "thinking").map(|text| AgentEvent::Thinking { text }),
+        "tool_use" => Some(AgentEvent::ToolCall {
+            id: str_field(block, "id"),
+            name: str_field(block, "name").unwrap_or_default(),
+            input: block.get("input").cloned().unwrap_or(Value::Null),
+        }),
+        "tool_result" => Some(AgentEvent::ToolResult {
+            id: str_field(block, "tool_use_id"),
+            name: None,
+            output: output_text(block.get("content")),
+            is_error: block
+                .get("is_error")
+                .and_then(|e| e.as_bool())
+                .unwrap_or(false),
+        }),
+        _ => None,
+    }
+}
+
+/// Translate `gemini -o stream-json` events (and raw API candidates)
+fn translate_gemini_event(value: &Value) -> Vec<AgentEvent> {
+    if let Some(candidates) = value.get("candidates").and_then(|c| c.as_array()) {
+        let text: String = candidates
+            .iter()
+            .filter_map(|c| c.pointer("/content/parts").and_then(|p| p.as_array()))
+            .flatten()
+            .filter_map(|part| part.get("text").and_then(|t| t.as_str()))
+            .collect();
+        return if text.is_empty() {
+            Vec::new()
+        } else {
+            vec![AgentEvent::AssistantText { text, delta: true }]
+        };
+    }
+
+    match value.get("type").and_then(|t| t.as_str()).unwrap_or("") {
+        "init" => str_field(value, "session_id")
+            .map(|session_id| vec![AgentEvent::SessionStarted { session_id }])
+            .unwrap_or_default(),
+        "message" if value.get("role").and_then(|r| r.as_str()) == Some("assistant") => {
+            str_field(value, "content")
+                .map(|text| {
+                    let delta = value
+                        .get("delta")
+                        .and_then(|d| d.as_bool())
+                        .unwrap_or(false);
+                    vec![AgentEvent::AssistantText { text, delta }]
+                })
+                .unwrap_or_default()
+        }
+        "tool_use" => vec![AgentEvent::ToolCall {
+            id: str_field(value, "tool_id"),
+            name: str_field(value, "tool_name").unwrap_or_default(),
+            input: value.get("parameters").cloned().unwrap_or(Value::Null),
+        }],
+        "tool_result" => {
+            let is_error = value.get("status").and_then(|s| s.as_str()) == Some("error");
+            let output = if is_error {
+                output_text(value.pointer("/error/message"))
+            } else {
+                output_text(value.get("output"))
+            };
+            vec![AgentEvent::ToolResult {
+                id: str_field(value, "tool_id"),
+                name: None,
+                output,
+                is_error,
+            }]
+        }
+        "error" => str_field(value, "message")
+            .map(|message| vec![AgentEvent::Error { message }])
+            .unwrap_or_default(),
+        "result" => {
+            let success = value.get("status").and_then(|s| s.as_str()) != Some("error");
+            let mut events = vec![AgentEvent::Usage {
+                input_tokens: u64_field(value, "/stats/input_tokens"),
+                output_tokens: u64_field(value, "/stats/output_tokens"),
+            }];
+            if !success {
+                events.push(AgentEvent::Error {
+                    message: output_text(value.pointer("/error/message")),
+                });
+            }
+            events.push(AgentEvent::Completed {
+                success,
+                result: None,
+            });
+            events
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Translate `opencode run --format json` events
+fn translate_opencode_event(value: &Value) -> Vec<AgentEvent> {
+    if value.get("part").is_none()
+        && let Some(parts) = value.pointer("/message/parts").and_then(|p| p.as_array())
+    {
+        let text: String = parts
+            .iter()
+            .filter(|p| p.get("type").and_then(|t| t.as_str()) == Some("text"))
+            .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+            .collect();
+        return if text.is_empty() {
+            Vec::new()
+        } else {
+            vec![AgentEvent::AssistantText { text, delta: false }]
+        };
+    }
+
+    let part = value.get("part").cloned().unwrap_or(Value::Null);
+    match value.get("type").and_then(|t| t.as_str()).unwrap_or("") {
+        "step_start" => str_field(value, "sessionID")
+            .map(|session_id| vec![AgentEvent::SessionStarted { session_id }])
+            .unwrap_or_default(),
+        "text" => str_field(&part, "text")
+            .map(|text| vec![AgentEvent::AssistantText { text, delta: false }])
+            .unwrap_or_default(),
+        "tool_use" => {
+            let id = str_field(&part, "callID");
+            let name = str_field(&part, "tool").unwrap_or_default();
+            let mut events = vec![AgentEvent::ToolCall {
+                id: id.clone(),
+                name: name.clone(),
+                input: part.pointer("/state/input").cloned().unwrap_or(Value::Null),
+            }];
+            let status = part.pointer("/state/status").and_then(|s| s.as_str());
+            if matches!(status, Some("completed" | "error")) {
+                let output = part
+                    .pointer("/state/output")
+                    .or_else(|| part.pointer("/state/error"));
+                events.push(AgentEvent::ToolResult {
+                    id,
+                    name: Some(name),
+                    output: output_text(output),
+                    is_error: status == Some("error"),
+                });
+            }
+            events
+        }
+        "step_finish" => {
+            let mut events = vec![AgentEvent::Usage {
+                input_tokens: u64_field(&part, "/tokens/input"),
+                output_tokens: u64_field(&part, "/tokens/output"),
+            }];
+            if part.get("reason").and_then(|r| r.as_str()) == Some("stop") {
+                events.push(AgentEvent::Completed {
+                    success: true,
+                    result: None,
+                });
+            }
+            events
+        }
+        "error" => {
+            let message = value
+                .pointer("/error/data/message")
+                .or_else(|| value.pointer("/error/message"))
+                .and_then(|m| m.as_str())
+                .unwrap_or("opencode error")
+                .to_string();
+            vec![AgentEvent::Error { message }]
+        }
+        _ => Vec::new(),
+    }
 }
 
 /// Select a backend by name
@@ -183,8 +826,21 @@ pub fn select_backend(name: Option<&str>) -> Result<Arc<dyn Backend>> {
         Some("claude") => Arc::new(ClaudeBackend),
         Some("gemini") => Arc::new(GeminiBackend),
         Some("opencode") => Arc::new(OpencodeBackend),
+        Some("mock") => Arc::new(MockBackend::new()),
         Some(other) => {
-            return Err(BackendError::NotFound(other.to_string()).into());
+            let mut custom = load_custom_backends(&backends_file())?;
+            match custom.remove(other) {
+                Some(config) => Arc::new(CustomBackend::new(config)),
+                None => {
+                    let available = BUILTIN_BACKENDS
+                        .iter()
+                        .map(|name| name.to_string())
+                        .chain(custom.into_keys())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    return Err(BackendError::NotFound(other.to_string(), available).into());
+                }
+            }
         }
         None => {
             // Auto-detect: prefer Claude, then Codex, then Gemini, then Opencode
@@ -209,15 +865,67 @@ pub fn select_backend(name: Option<&str>) -> Result<Arc<dyn Backend>> {
     Ok(backend)
 }
 
-/// Get list of available backend names
-#[allow(dead_code)]
-pub fn get_available_backends() -> Vec<&'static str> {
-    vec!["codex", "claude", "gemini", "opencode"]
+/// One entry of a backend fallback chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendChoice {
+    /// Backend name
+    pub name: String,
+    /// Model to use on this backend (`name:model`)
+    pub model: Option<String>,
+}
+
+/// Build the fallback chain from `--backend claude,codex:gpt-5` and the agent's
+/// `fallback` list; later duplicates of a backend are dropped
+pub fn backend_chain(backend: Option<&str>, fallback: &[String]) -> Vec<BackendChoice> {
+    let mut chain: Vec<BackendChoice> = Vec::new();
+    let entries = backend
+        .into_iter()
+        .flat_map(|b| b.split(','))
+        .chain(fallback.iter().map(String::as_str));
+
+    for entry in entries.map(str::trim).filter(|e| !e.is_empty()) {
+        let (name, model) = match entry.split_once(':') {
+            Some((name, model)) => (name.trim(), Some(model.trim().to_string())),
+            None => (entry, None),
+        };
+        let name = name.to_lowercase();
+        if !chain.iter().any(|c| c.name == name) {
+            chain.push(BackendChoice { name, model });
+        }
+    }
+
+    chain
+}
+
+/// Install instructions for a built-in backend
+pub fn install_hint(name: &str) -> String {
+    let package = match name {
+        "claude" => "@anthropic-ai/claude-code",
+        "codex" => "@openai/codex",
+        "gemini" => "@google/gemini-cli",
+        _ => name,
+    };
+    format!("npm install -g {}", package)
+}
+
+/// Every known backend: the built-in ones followed by those in backends.yaml
+pub fn all_backends() -> Result<Vec<Arc<dyn Backend>>> {
+    let mut backends: Vec<Arc<dyn Backend>> = vec![
+        Arc::new(ClaudeBackend),
+        Arc::new(CodexBackend),
+        Arc::new(GeminiBackend),
+        Arc::new(OpencodeBackend),
+    ];
+    for (_, config) in load_custom_backends(&backends_file())? {
+        backends.push(Arc::new(CustomBackend::new(config)));
+    }
+    Ok(backends)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock::MockOptions;
 
     #[test]
     fn test_codex_build_args() {
@@ -253,6 +961,164 @@ mod tests {
         assert!(args.contains(&"abc123".to_string()));
     }
 
+    fn translate_all(backend: &dyn Backend, events: &[Value]) -> Vec<AgentEvent> {
+        events
+            .iter()
+            .flat_map(|e| backend.translate_event(e))
+            .collect()
+    }
+
+    #[test]
+    fn test_translate_claude_events() {
+        let events = translate_all(
+            &ClaudeBackend,
+            &[
+                serde_json::json!({"type": "system", "subtype": "init", "session_id": "s1"}),
+                serde_json::json!({"type": "assistant", "message": {"content": [
+                    {"type": "text", "text": "Let me look."},
+                    {"type": "tool_use", "id": "tu1", "name": "Read", "input": {"file_path": "a.rs"}}
+                ]}}),
+                serde_json::json!({"type": "user", "message": {"content": [
+                    {"type": "tool_result", "tool_use_id": "tu1", "content": "fn main() {}"}
+                ]}}),
+                serde_json::json!({"type": "result", "subtype": "success", "result": "All good.",
+                    "usage": {"input_tokens": 10, "output_tokens": 5}}),
+            ],
+        );
+
+        assert_eq!(
+            events[0],
+            AgentEvent::SessionStarted {
+                session_id: "s1".to_string()
+            }
+        );
+        assert!(matches!(&events[2], AgentEvent::ToolCall { name, .. } if name == "Read"));
+        assert!(
+            matches!(&events[3], AgentEvent::ToolResult { output, is_error: false, .. } if output == "fn main() {}")
+        );
+        assert_eq!(
+            events[4],
+            AgentEvent::Usage {
+                input_tokens: 10,
+                output_tokens: 5
+            }
+        );
+        assert_eq!(
+            crate::parser::extract_final_message(&events),
+            Some("All good.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_translate_codex_events() {
+        let events = translate_all(
+            &CodexBackend,
+            &[
+                serde_json::json!({"type": "thread.started", "thread_id": "t1"}),
+                serde_json::json!({"type": "item.started", "item": {"id": "i1", "type": "command_execution", "command": "ls"}}),
+                serde_json::json!({"type": "item.completed", "item": {"id": "i1", "type": "command_execution", "aggregated_output": "a.rs", "exit_code": 0}}),
+                serde_json::json!({"type": "item.completed", "item": {"id": "i2", "type": "agent_message", "text": "Patched."}}),
+                serde_json::json!({"type": "turn.completed", "usage": {"input_tokens": 7, "output_tokens": 3}}),
+            ],
+        );
+
+        assert_eq!(
+            events[0],
+            AgentEvent::SessionStarted {
+                session_id: "t1".to_string()
+            }
+        );
+        assert!(matches!(&events[1], AgentEvent::ToolCall { name, .. } if name == "shell"));
+        assert!(matches!(
+            &events[2],
+            AgentEvent::ToolResult {
+                is_error: false,
+                ..
+            }
+        ));
+        assert_eq!(
+            crate::parser::extract_final_message(&events),
+            Some("Patched.".to_string())
+        );
+        assert!(events.contains(&AgentEvent::Usage {
+            input_tokens: 7,
+            output_tokens: 3
+        }));
+    }
+
+    #[test]
+    fn test_translate_gemini_events() {
+        let events = translate_all(
+            &GeminiBackend,
+            &[
+                serde_json::json!({"type": "init", "session_id": "g1", "model": "gemini-2.5-pro"}),
+                serde_json::json!({"type": "message", "role": "user", "content": "hi"}),
+                serde_json::json!({"type": "tool_use", "tool_name": "read_file", "tool_id": "x", "parameters": {}}),
+                serde_json::json!({"type": "message", "role": "assistant", "content": "Hello ", "delta": true}),
+                serde_json::json!({"type": "message", "role": "assistant", "content": "world", "delta": true}),
+                serde_json::json!({"type": "result", "status": "success", "stats": {"input_tokens": 4, "output_tokens": 2}}),
+            ],
+        );
+
+        assert_eq!(
+            crate::parser::extract_final_message(&events),
+            Some("Hello world".to_string())
+        );
+        assert!(matches!(
+            events.last(),
+            Some(AgentEvent::Completed { success: true, .. })
+        ));
+
+        let candidates = translate_all(
+            &GeminiBackend,
+            &[serde_json::json!({"candidates": [{"content": {"parts": [{"text": "Foo"}]}}]})],
+        );
+        assert_eq!(
+            candidates,
+            vec![AgentEvent::AssistantText {
+                text: "Foo".to_string(),
+                delta: true
+            }]
+        );
+    }
+
+    #[test]
+    fn test_translate_opencode_events() {
+        let events = translate_all(
+            &OpencodeBackend,
+            &[
+                serde_json::json!({"type": "step_start", "sessionID": "o1", "part": {"type": "step-start"}}),
+                serde_json::json!({"type": "tool_use", "sessionID": "o1", "part": {"type": "tool", "tool": "bash", "callID": "c1",
+                    "state": {"status": "completed", "input": {"command": "ls"}, "output": "a.rs"}}}),
+                serde_json::json!({"type": "text", "sessionID": "o1", "part": {"type": "text", "text": "Done"}}),
+                serde_json::json!({"type": "step_finish", "sessionID": "o1", "part": {"reason": "stop", "tokens": {"input": 9, "output": 1}}}),
+            ],
+        );
+
+        assert_eq!(
+            events[0],
+            AgentEvent::SessionStarted {
+                session_id: "o1".to_string()
+            }
+        );
+        assert!(matches!(&events[2], AgentEvent::ToolResult { output, .. } if output == "a.rs"));
+        assert_eq!(
+            crate::parser::extract_final_message(&events),
+            Some("Done".to_string())
+        );
+    }
+
+    #[test]
+    fn test_translate_detected_event() {
+        let value = serde_json::json!({"type": "thread.started", "thread_id": "t9"});
+        assert_eq!(
+            translate_detected_event(&value),
+            vec![AgentEvent::SessionStarted {
+                session_id: "t9".to_string()
+            }]
+        );
+    }
+
     #[test]
     fn test_select_backend_by_name() {
         let backend = select_backend(Some("claude")).unwrap();
@@ -262,6 +1128,135 @@ mod tests {
         assert_eq!(backend.name(), "codex");
     }
 
+    #[test]
+    fn test_backend_chain() {
+        let chain = backend_chain(
+            Some("claude, codex:gpt-5"),
+            &["gemini".to_string(), "claude".to_string()],
+        );
+        assert_eq!(
+            chain,
+            vec![
+                BackendChoice {
+                    name: "claude".to_string(),
+                    model: None
+                },
+                BackendChoice {
+                    name: "codex".to_string(),
+                    model: Some("gpt-5".to_string())
+                },
+                BackendChoice {
+                    name: "gemini".to_string(),
+                    model: None
+                },
+            ]
+        );
+        assert!(backend_chain(None, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_custom_build_args() {
+        let backend = CustomBackend::new(CustomBackendConfig {
+            name: "local".to_string(),
+            command: "local-agent".to_string(),
+            args: vec!["exec".to_string(), "--json".to_string()],
+            model: vec!["--model={model}".to_string()],
+            session: vec!["--resume".to_string(), "{session}".to_string()],
+            workdir: vec!["-C".to_string(), "{workdir}".to_string()],
+            permissions: vec!["--yolo".to_string()],
+            prompt: vec!["--message".to_string(), "{prompt}".to_string()],
+            ..Default::default()
+        });
+        let config = Config {
+            work_dir: "/tmp".into(),
+            model: Some("qwen3".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            backend.build_args(&config, "Fix it"),
+            vec![
+                "exec",
+                "--json",
+                "-C",
+                "/tmp",
+                "--model=qwen3",
+                "--message",
+                "Fix it"
+            ]
+        );
+        assert_eq!(backend.prompt_on_stdin(), Some(false));
+
+        let stdin_backend = CustomBackend::new(CustomBackendConfig {
+            stdin: true,
+            dialect: EventDialect::Codex,
+            ..backend.config.clone()
+        });
+        let config = Config {
+            session_id: Some("s1".to_string()),
+            skip_permissions: true,
+            ..config
+        };
+        let args = stdin_backend.build_args(&config, "-");
+        assert!(args.ends_with(&["--yolo".to_string()]));
+        assert!(args.contains(&"s1".to_string()));
+        assert!(!args.contains(&"--message".to_string()));
+        assert_eq!(
+            stdin_backend
+                .translate_event(&serde_json::json!({"type": "thread.started", "thread_id": "t1"})),
+            vec![AgentEvent::SessionStarted {
+                session_id: "t1".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_mock_build_args() {
+        let config = Config {
+            model: Some("gpt-5".to_string()),
+            session_id: Some("s1".to_string()),
+            mock: MockOptions {
+                file: Some("/fixtures/run.jsonl".into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let args = MockBackend::new().build_args(&config, "-");
+        assert_eq!(args[0], "mock-replay");
+        assert_eq!(args[1..3], ["--file", "/fixtures/run.jsonl"]);
+        assert!(!args.contains(&"gpt-5".to_string()));
+        assert!(args.contains(&"s1".to_string()));
+        assert_eq!(args[args.len() - 2..], ["--", "-"]);
+    }
+
+    #[test]
+    fn test_capabilities() {
+        assert!(CodexBackend.capabilities().reasoning_effort);
+        assert!(!ClaudeBackend.capabilities().reasoning_effort);
+        assert_eq!(
+            GeminiBackend.capabilities().permissions,
+            PermissionMode::AlwaysSkipped
+        );
+        assert_eq!(
+            OpencodeBackend.capabilities().permissions,
+            PermissionMode::Unsupported
+        );
+        // Long prompts stay on the command line for backends without stdin support
+        assert_eq!(OpencodeBackend.prompt_on_stdin(), Some(false));
+        assert_eq!(ClaudeBackend.prompt_on_stdin(), None);
+
+        let custom = CustomBackend::new(CustomBackendConfig {
+            name: "local".to_string(),
+            command: "local-agent".to_string(),
+            model: vec!["--model".to_string(), "{model}".to_string()],
+            ..Default::default()
+        });
+        let caps = custom.capabilities();
+        assert!(caps.model);
+        assert!(!caps.resume);
+        assert_eq!(caps.permissions, PermissionMode::Unsupported);
+    }
+
     #[test]
     fn test_select_unknown_backend() {
         let result = select_backend(Some("unknown"));
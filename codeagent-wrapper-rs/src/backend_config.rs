@@ -0,0 +1,218 @@
+//! User-defined backends
+//!
+//! `~/.codeagent/backends.yaml` declares extra backends (internal forks, local
+//! model runners, other agent CLIs) without code changes. Each entry names the
+//! command, argument templates and the JSON event dialect its output uses.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::errors::ConfigError;
+
+/// Built-in backend names, which custom entries may not reuse
+pub const BUILTIN_BACKENDS: [&str; 4] = ["codex", "claude", "gemini", "opencode"];
+
+/// Name of the built-in replay backend used for offline testing
+pub const MOCK_BACKEND: &str = "mock";
+
+/// Placeholders accepted in argument templates
+const PLACEHOLDERS: [&str; 4] = ["{model}", "{session}", "{workdir}", "{prompt}"];
+
+/// Event format of a custom backend's JSON output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventDialect {
+    /// Detect the format per event
+    #[default]
+    Auto,
+    Claude,
+    Codex,
+    Gemini,
+    Opencode,
+}
+
+/// Custom backend definition
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CustomBackendConfig {
+    /// Backend name (the key in backends.yaml)
+    #[serde(default)]
+    pub name: String,
+    /// Executable to run (looked up on PATH)
+    pub command: String,
+    /// Arguments always passed first
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Arguments added when a model is set (`{model}`)
+    #[serde(default)]
+    pub model: Vec<String>,
+    /// Arguments added when resuming a session (`{session}`)
+    #[serde(default)]
+    pub session: Vec<String>,
+    /// Arguments carrying the working directory (`{workdir}`)
+    #[serde(default)]
+    pub workdir: Vec<String>,
+    /// Arguments added with `--skip-permissions`
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    /// Arguments carrying the prompt (`{prompt}`); defaults to the bare prompt
+    #[serde(default = "default_prompt_args")]
+    pub prompt: Vec<String>,
+    /// Write the prompt to stdin instead of passing `prompt` arguments
+    #[serde(default)]
+    pub stdin: bool,
+    /// Event format of the JSON output
+    #[serde(default)]
+    pub dialect: EventDialect,
+}
+
+fn default_prompt_args() -> Vec<String> {
+    vec!["{prompt}".to_string()]
+}
+
+impl CustomBackendConfig {
+    /// Reject reserved names, a missing command and unknown placeholders
+    fn validate(&self) -> Result<()> {
+        let invalid = |reason: String| {
+            ConfigError::InvalidParameter(format!("backends.yaml: {}", self.name), reason)
+        };
+
+        if BUILTIN_BACKENDS.contains(&self.name.as_str()) || self.name == MOCK_BACKEND {
+            return Err(invalid("conflicts with a built-in backend".to_string()).into());
+        }
+        if self.command.trim().is_empty() {
+            return Err(invalid("`command` is required".to_string()).into());
+        }
+
+        let templates = [
+            &self.args,
+            &self.model,
+            &self.session,
+            &self.workdir,
+            &self.permissions,
+            &self.prompt,
+        ];
+        for arg in templates.into_iter().flatten() {
+            let mut rest = arg.as_str();
+            while let Some(start) = rest.find('{') {
+                let Some(len) = rest[start..].find('}') else {
+                    break;
+                };
+                let placeholder = &rest[start..=start + len];
+                if !PLACEHOLDERS.contains(&placeholder) {
+                    return Err(invalid(format!(
+                        "unknown placeholder {} (expected one of {})",
+                        placeholder,
+                        PLACEHOLDERS.join(", ")
+                    ))
+                    .into());
+                }
+                rest = &rest[start + len + 1..];
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Get the backends.yaml path
+pub fn backends_file() -> PathBuf {
+    let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    home.join(".codeagent").join("backends.yaml")
+}
+
+/// Load custom backends from the given backends.yaml (missing file means none)
+pub fn load_custom_backends(path: &Path) -> Result<BTreeMap<String, CustomBackendConfig>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read backends config: {}", path.display()))?;
+    if content.trim().is_empty() {
+        return Ok(BTreeMap::new());
+    }
+
+    let entries: BTreeMap<String, CustomBackendConfig> =
+        serde_yaml::from_str(&content).map_err(|e| {
+            ConfigError::InvalidParameter(
+                format!("backends.yaml ({})", path.display()),
+                e.to_string(),
+            )
+        })?;
+
+    let mut backends = BTreeMap::new();
+    for (name, mut config) in entries {
+        let name = name.to_lowercase();
+        config.name = name.clone();
+        config.validate()?;
+        backends.insert(name, config);
+    }
+
+    Ok(backends)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_custom_backends() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("backends.yaml");
+        std::fs::write(
+            &file,
+            r#"
+Aider:
+  command: aider-json
+  args: [--json]
+  model: [--model, "{model}"]
+  workdir: ["--cwd={workdir}"]
+  stdin: true
+  dialect: claude
+local:
+  command: local-agent
+"#,
+        )
+        .unwrap();
+
+        let backends = load_custom_backends(&file).unwrap();
+        let aider = &backends["aider"];
+        assert_eq!(aider.name, "aider");
+        assert_eq!(aider.command, "aider-json");
+        assert_eq!(aider.workdir, vec!["--cwd={workdir}"]);
+        assert!(aider.stdin);
+        assert_eq!(aider.dialect, EventDialect::Claude);
+
+        let local = &backends["local"];
+        assert_eq!(local.prompt, vec!["{prompt}"]);
+        assert_eq!(local.dialect, EventDialect::Auto);
+
+        assert!(
+            load_custom_backends(Path::new("/nonexistent/backends.yaml"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_invalid_custom_backends() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("backends.yaml");
+
+        let cases = [
+            ("claude:\n  command: my-claude\n", "built-in"),
+            ("mock:\n  command: my-mock\n", "built-in"),
+            ("x:\n  command: x\n  model: [\"{modle}\"]\n", "{modle}"),
+            ("x:\n  command: \"\"\n", "command"),
+            ("x:\n  command: x\n  dialect: klingon\n", "backends.yaml"),
+        ];
+        for (yaml, expected) in cases {
+            std::fs::write(&file, yaml).unwrap();
+            let err = load_custom_backends(&file).unwrap_err();
+            assert!(err.to_string().contains(expected), "{}: {}", yaml, err);
+            assert!(err.downcast_ref::<ConfigError>().is_some());
+        }
+    }
+}
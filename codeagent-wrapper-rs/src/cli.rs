@@ -2,6 +2,8 @@
 
 use clap::{Parser, Subcommand, ValueEnum};
 
+use crate::config::RetryCondition;
+
 /// Unified wrapper for AI CLI backends (Codex, Claude, Gemini, Opencode)
 #[derive(Parser, Debug, Clone)]
 #[command(name = "codeagent")]
@@ -37,6 +39,10 @@ pub struct Cli {
     #[arg(long, short = 'a', env = "CODEAGENT_AGENT")]
     pub agent: Option<String>,
 
+    /// Alias for this run's session, usable with `resume` and `sessions`
+    #[arg(long, value_name = "ALIAS")]
+    pub name: Option<String>,
+
     /// Path to prompt file
     #[arg(long, value_name = "PATH")]
     pub prompt_file: Option<String>,
@@ -45,6 +51,36 @@ pub struct Cli {
     #[arg(long, short = 't', default_value = "7200", env = "CODEX_TIMEOUT")]
     pub timeout: u64,
 
+    /// Fail if the backend produces no output (stdout event or stderr line) for this many seconds
+    #[arg(long, value_name = "SECONDS", env = "CODEAGENT_IDLE_TIMEOUT")]
+    pub idle_timeout: Option<u64>,
+
+    /// Extra attempts when a task fails
+    #[arg(long, value_name = "N", default_value = "0", env = "CODEAGENT_RETRIES")]
+    pub retries: u32,
+
+    /// Base delay in seconds before a retry (doubled after every attempt)
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value = "5",
+        env = "CODEAGENT_RETRY_BACKOFF"
+    )]
+    pub retry_backoff: u64,
+
+    /// Only retry these failures: exit codes or error kinds (e.g. rate-limited,network,1)
+    #[arg(long, value_name = "LIST", value_delimiter = ',')]
+    pub retry_on: Vec<RetryCondition>,
+
+    /// Seconds to wait after SIGTERM before force-killing the backend
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value = "10",
+        env = "CODEAGENT_GRACE_PERIOD"
+    )]
+    pub grace_period: u64,
+
     /// Skip permission checks (YOLO mode)
     #[arg(long, alias = "yolo", env = "CODEAGENT_SKIP_PERMISSIONS")]
     pub skip_permissions: bool,
@@ -57,6 +93,10 @@ pub struct Cli {
     #[arg(long)]
     pub minimal_env: bool,
 
+    /// Fail instead of warning when the backend does not support a requested option
+    #[arg(long, env = "CODEAGENT_STRICT")]
+    pub strict: bool,
+
     /// Max parallel workers
     #[arg(long, env = "CODEAGENT_MAX_PARALLEL_WORKERS")]
     pub max_parallel_workers: Option<usize>,
@@ -65,10 +105,59 @@ pub struct Cli {
     #[arg(long)]
     pub parallel: bool,
 
+    /// Parallel mode: cancel all running tasks on the first failure
+    #[arg(long, conflicts_with = "keep_going")]
+    pub fail_fast: bool,
+
+    /// Parallel mode: keep running every branch that does not depend on a failed task
+    #[arg(long)]
+    pub keep_going: bool,
+
+    /// Parallel mode: append every dependency's output to downstream task prompts
+    #[arg(long)]
+    pub include_dependency_outputs: bool,
+
     /// Suppress progress output
     #[arg(long, short = 'q', env = "CODEAGENT_QUIET")]
     pub quiet: bool,
 
+    /// Print only the final assistant message to stdout (for shell piping)
+    #[arg(long)]
+    pub message_only: bool,
+
+    /// Include the raw backend JSON events in the output
+    #[arg(long)]
+    pub raw_events: bool,
+
+    /// Record the backend's event stream to a replay file for the mock backend
+    #[arg(long, value_name = "FILE")]
+    pub record: Option<String>,
+
+    /// Replay file for the mock backend (without one it echoes the prompt)
+    #[arg(long, value_name = "FILE", env = "CODEAGENT_MOCK_FILE")]
+    pub mock_file: Option<String>,
+
+    /// Milliseconds the mock backend waits before each event
+    #[arg(long, value_name = "MS", env = "CODEAGENT_MOCK_DELAY_MS")]
+    pub mock_delay_ms: Option<u64>,
+
+    /// Exit code of the mock backend, overriding the replay file's
+    #[arg(
+        long,
+        value_name = "CODE",
+        allow_negative_numbers = true,
+        env = "CODEAGENT_MOCK_EXIT_CODE"
+    )]
+    pub mock_exit_code: Option<i32>,
+
+    /// Line the mock backend prints on stderr before replaying
+    #[arg(long, value_name = "LINE", env = "CODEAGENT_MOCK_STDERR")]
+    pub mock_stderr: Option<String>,
+
+    /// Recorded attempt the mock backend replays (default: the last)
+    #[arg(long, value_name = "N", env = "CODEAGENT_MOCK_ATTEMPT")]
+    pub mock_attempt: Option<u32>,
+
     /// Show full output in parallel mode
     #[arg(long)]
     pub full_output: bool,
@@ -95,7 +184,7 @@ pub struct Cli {
 pub enum Command {
     /// Resume a previous session
     Resume {
-        /// Session ID to resume
+        /// Session ID, alias, `last` or `last@<workdir>`
         session_id: String,
         /// Task to continue with
         task: String,
@@ -103,6 +192,99 @@ pub enum Command {
         workdir: Option<String>,
     },
 
+    /// Continue a recorded session on another backend from a summary of its transcript
+    Handoff {
+        /// Session ID, alias, `last` or `last@<workdir>`
+        session: String,
+        /// Backend to continue on
+        #[arg(long)]
+        to: String,
+        /// Next task (`-` reads it from stdin)
+        task: String,
+        /// Working directory (defaults to the session's)
+        workdir: Option<String>,
+        /// Approximate token budget for the handed-off context
+        #[arg(long, value_name = "TOKENS", default_value_t = crate::handoff::DEFAULT_BUDGET)]
+        budget: usize,
+    },
+
+    /// Render a session or a run's output/recording as a readable transcript
+    Export {
+        /// Session ID, alias, `last`, `last@<workdir>`, or a run's JSON output or event recording
+        reference: String,
+        /// Output format
+        #[arg(long, short = 'f', value_enum, default_value = "md")]
+        format: ExportFormat,
+        /// Write to a file instead of stdout
+        #[arg(long, short = 'o', value_name = "FILE")]
+        output: Option<String>,
+    },
+
+    /// List recorded runs, filtered by date, backend or status, with aggregate stats
+    History {
+        /// Only runs since YYYY-MM-DD, an RFC 3339 time or a duration ago (e.g. 7d, 12h)
+        #[arg(long)]
+        since: Option<String>,
+        /// Only runs before YYYY-MM-DD (inclusive), an RFC 3339 time or a duration ago
+        #[arg(long)]
+        until: Option<String>,
+        /// Only runs of this backend
+        #[arg(long)]
+        backend: Option<String>,
+        /// Only runs with this status
+        #[arg(long, value_parser = ["success", "failed", "skipped"])]
+        status: Option<String>,
+        /// Show at most this many runs
+        #[arg(long, short = 'n', default_value = "20")]
+        limit: usize,
+        /// Show success rate and p50/p95 duration per backend, and tokens per day
+        #[arg(long)]
+        stats: bool,
+        /// Print as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List, show or remove recorded sessions
+    Sessions {
+        #[command(subcommand)]
+        action: SessionsCommand,
+    },
+
+    /// Report installed backends, versions, credentials and wrapper directories
+    #[command(alias = "backends")]
+    Doctor {
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Replay a recorded event stream (run by the mock backend)
+    #[command(hide = true)]
+    MockReplay {
+        /// JSONL replay file; without it the prompt is echoed back
+        #[arg(long)]
+        file: Option<String>,
+        /// Milliseconds to wait before each event
+        #[arg(long, default_value = "0")]
+        delay_ms: u64,
+        /// Exit code to finish with
+        #[arg(long, allow_negative_numbers = true)]
+        exit_code: Option<i32>,
+        /// Line to print on stderr before replaying
+        #[arg(long)]
+        stderr: Vec<String>,
+        /// Session ID for the echoed reply
+        #[arg(long)]
+        session: Option<String>,
+        /// Recorded attempt to replay (default: the last)
+        #[arg(long)]
+        attempt: Option<u32>,
+        /// Prompt, or `-` to read it from stdin
+        #[arg(default_value = "-")]
+        prompt: String,
+    },
+
     /// Install codeagent skill to ~/.claude/skills/
     Init {
         /// Force overwrite existing installation
@@ -111,6 +293,46 @@ pub enum Command {
     },
 }
 
+/// `sessions` actions
+#[derive(Subcommand, Debug, Clone)]
+pub enum SessionsCommand {
+    /// List recorded sessions, most recent first
+    List {
+        /// Show at most this many sessions
+        #[arg(long, short = 'n', default_value = "20")]
+        limit: usize,
+        /// Print as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show one session
+    Show {
+        /// Session ID, alias, `last` or `last@<workdir>`
+        session: String,
+        /// Print as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Remove session records (the backend's own history is untouched)
+    Rm {
+        /// Session IDs, aliases, `last` or `last@<workdir>`
+        #[arg(required = true)]
+        sessions: Vec<String>,
+    },
+}
+
+/// `export` output formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// Markdown with collapsible tool calls
+    #[value(alias = "markdown")]
+    Md,
+    /// Self-contained HTML page
+    Html,
+    /// Session record and normalized events
+    Json,
+}
+
 /// Backend type enum for validation
 #[derive(Debug, Clone, Copy, ValueEnum)]
 pub enum BackendType {
@@ -199,6 +421,17 @@ mod tests {
         assert!(cli.minimal_env);
     }
 
+    #[test]
+    fn test_cli_failure_policy_flags() {
+        let cli = Cli::try_parse_from(["codeagent", "--parallel", "--fail-fast"]).unwrap();
+        assert!(cli.fail_fast);
+        assert!(!cli.keep_going);
+
+        let result =
+            Cli::try_parse_from(["codeagent", "--parallel", "--fail-fast", "--keep-going"]);
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_cli_combined_flags() {
         let cli = Cli::try_parse_from([
@@ -1,12 +1,23 @@
 //! Configuration parsing and validation
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::Arc;
 use tokio::io::{AsyncBufReadExt, BufReader};
+use tracing::warn;
 
+use crate::agent_config::{AgentConfig, get_agent_config, merge_agent_config};
+use crate::backend::{Backend, Capabilities, PermissionMode, backend_chain};
 use crate::cli::Cli;
-use crate::errors::ConfigError;
+use crate::diagnostics::ErrorKind;
+use crate::errors::{ConfigError, ExecutionError};
+use crate::mock::{MockOptions, Recorder};
+use crate::sessions::SessionRecord;
+use crate::signal::CancelToken;
+use crate::template;
+use crate::utils::expand_home;
 
 /// Runtime configuration
 #[allow(dead_code)]
@@ -14,34 +25,68 @@ use crate::errors::ConfigError;
 pub struct Config {
     /// Execution mode: "new" or "resume"
     pub mode: String,
+    /// Task ID (parallel mode)
+    pub task_id: Option<String>,
     /// Task content
     pub task: String,
     /// Session ID for resume mode
     pub session_id: Option<String>,
+    /// Alias recorded for the session (`--name`)
+    pub session_name: Option<String>,
     /// Working directory
     pub work_dir: PathBuf,
     /// Model name
     pub model: Option<String>,
-    /// Backend name
+    /// Backend name, or a comma-separated fallback chain
     pub backend: Option<String>,
+    /// Fallback backends from the agent preset
+    pub fallback: Vec<String>,
     /// Agent configuration name
     pub agent: Option<String>,
     /// Prompt file path
     pub prompt_file: Option<PathBuf>,
     /// Timeout in seconds
     pub timeout: u64,
+    /// Inactivity timeout in seconds (no stdout event or stderr line)
+    pub idle_timeout: Option<u64>,
+    /// Seconds between SIGTERM and SIGKILL when stopping the backend
+    pub grace_period: u64,
+    /// Retry behaviour for failed attempts
+    pub retry: RetryPolicy,
     /// Skip permission checks
     pub skip_permissions: bool,
     /// Reasoning effort level (for Codex backend)
     pub reasoning_effort: Option<String>,
     /// Use minimal environment variables (performance optimization)
     pub minimal_env: bool,
+    /// Fail instead of warning about options the backend does not support
+    pub strict: bool,
     /// Quiet mode
     pub quiet: bool,
+    /// Print only the final message instead of the JSON output
+    pub message_only: bool,
+    /// Keep raw backend JSON events in the output
+    pub raw_events: bool,
+    /// Replay file to record the backend's event stream to
+    pub record: Option<PathBuf>,
+    /// Open recording shared by every attempt of the task (set by `execute`)
+    pub recorder: Option<Arc<Recorder>>,
     /// Show backend output
     pub backend_output: bool,
     /// Debug mode
     pub debug: bool,
+    /// Extra environment variables for the backend process (from agent preset)
+    pub env: HashMap<String, String>,
+    /// Text prepended to the task prompt (from agent preset)
+    pub prompt_prefix: Option<String>,
+    /// Session this run was handed off from
+    pub handoff_from: Option<String>,
+    /// Summary of the handed-off session, prepended to the task
+    pub handoff_context: Option<String>,
+    /// Stops the backend gracefully when cancelled (parallel `--fail-fast`)
+    pub cancel: Option<CancelToken>,
+    /// Replay settings for the mock backend
+    pub mock: MockOptions,
 }
 
 impl Config {
@@ -55,20 +100,37 @@ impl Config {
 
         Ok(Self {
             mode: "new".to_string(),
+            task_id: None,
             task: task.to_string(),
             session_id: None,
+            session_name: cli.name.clone(),
             work_dir,
             model: cli.model.clone(),
             backend: cli.backend.clone(),
+            fallback: Vec::new(),
             agent: cli.agent.clone(),
             prompt_file: cli.prompt_file.as_ref().map(PathBuf::from),
             timeout: cli.timeout,
+            idle_timeout: cli.idle_timeout,
+            grace_period: cli.grace_period,
+            retry: RetryPolicy::from_cli(cli),
             skip_permissions: cli.skip_permissions,
             reasoning_effort: cli.reasoning_effort.clone(),
             minimal_env: cli.minimal_env,
+            strict: cli.strict,
             quiet: cli.quiet,
+            message_only: cli.message_only,
+            raw_events: cli.raw_events,
+            record: cli.record.as_ref().map(PathBuf::from),
+            recorder: None,
             backend_output: cli.backend_output || cli.debug,
             debug: cli.debug,
+            env: HashMap::new(),
+            prompt_prefix: None,
+            handoff_from: None,
+            handoff_context: None,
+            cancel: None,
+            mock: MockOptions::from_cli(cli),
         })
     }
 
@@ -90,26 +152,155 @@ impl Config {
 
         Ok(Self {
             mode: "resume".to_string(),
+            task_id: None,
             task: task.to_string(),
             session_id: Some(session_id.to_string()),
+            session_name: cli.name.clone(),
             work_dir,
             model: cli.model.clone(),
             backend: cli.backend.clone(),
+            fallback: Vec::new(),
             agent: cli.agent.clone(),
             prompt_file: cli.prompt_file.as_ref().map(PathBuf::from),
             timeout: cli.timeout,
+            idle_timeout: cli.idle_timeout,
+            grace_period: cli.grace_period,
+            retry: RetryPolicy::from_cli(cli),
             skip_permissions: cli.skip_permissions,
             reasoning_effort: cli.reasoning_effort.clone(),
             minimal_env: cli.minimal_env,
+            strict: cli.strict,
             quiet: cli.quiet,
+            message_only: cli.message_only,
+            raw_events: cli.raw_events,
+            record: cli.record.as_ref().map(PathBuf::from),
+            recorder: None,
             backend_output: cli.backend_output || cli.debug,
             debug: cli.debug,
+            env: HashMap::new(),
+            prompt_prefix: None,
+            handoff_from: None,
+            handoff_context: None,
+            cancel: None,
+            mock: MockOptions::from_cli(cli),
         })
     }
+
+    /// Resolve the `--agent` preset (if any) and apply it to this config
+    pub async fn resolve_agent(&mut self) -> Result<()> {
+        if let Some(ref name) = self.agent {
+            let agent_config = get_agent_config(name).await?;
+            self.apply_agent_config(&agent_config);
+        }
+        Ok(())
+    }
+
+    /// Resume on the backend, model and workdir the session was created with
+    ///
+    /// `--backend` / `--model` and an explicit workdir still win, but a session
+    /// ID is only meaningful to the backend that issued it, so naming a
+    /// different backend is an error.
+    pub fn apply_session_record(
+        &mut self,
+        record: &SessionRecord,
+        workdir_given: bool,
+    ) -> Result<()> {
+        match backend_chain(self.backend.as_deref(), &[]).first() {
+            None => self.backend = Some(record.backend.clone()),
+            Some(choice) if choice.name == record.backend => {}
+            Some(choice) => {
+                return Err(ConfigError::IncompatibleSession(
+                    record.id.clone(),
+                    record.backend.clone(),
+                    choice.name.clone(),
+                )
+                .into());
+            }
+        }
+        if self.model.is_none() {
+            self.model = record.model.clone();
+        }
+        if !workdir_given {
+            self.work_dir = record.workdir.clone();
+        }
+        Ok(())
+    }
+
+    /// Requested options that a backend with `caps` would ignore
+    pub fn unsupported_options(&self, caps: &Capabilities) -> Vec<&'static str> {
+        let mut options = Vec::new();
+        if self.model.is_some() && !caps.model {
+            options.push("--model");
+        }
+        if self.session_id.is_some() && !caps.resume {
+            options.push("session resume");
+        }
+        if self.reasoning_effort.is_some() && !caps.reasoning_effort {
+            options.push("--reasoning-effort");
+        }
+        if self.skip_permissions && caps.permissions == PermissionMode::Unsupported {
+            options.push("--skip-permissions");
+        }
+        // Permission prompts are requested by leaving out --skip-permissions
+        if !self.skip_permissions && caps.permissions == PermissionMode::AlwaysSkipped {
+            options.push("permission prompts (it always skips them)");
+        }
+        options
+    }
+
+    /// `[task-id] ` prefix for messages on stderr; empty outside parallel mode
+    pub fn log_prefix(&self) -> String {
+        self.task_id
+            .as_ref()
+            .map(|id| format!("[{}] ", id))
+            .unwrap_or_default()
+    }
+
+    /// Warn about options the backend would ignore, or fail under `--strict`
+    pub fn check_backend_support(&self, backend: &dyn Backend) -> Result<()> {
+        let options = self.unsupported_options(&backend.capabilities());
+        if options.is_empty() {
+            return Ok(());
+        }
+
+        let options = options.join(", ");
+        if self.strict {
+            return Err(ConfigError::UnsupportedOption(backend.name().to_string(), options).into());
+        }
+
+        warn!(backend = backend.name(), options = %options, "Ignoring unsupported options");
+        if !self.quiet {
+            let prefix = self.log_prefix();
+            eprintln!(
+                "{}Warning: backend '{}' does not support {}; ignoring (use --strict to fail)",
+                prefix,
+                backend.name(),
+                options
+            );
+        }
+        Ok(())
+    }
+
+    /// Apply an agent preset; values already set from CLI flags take precedence
+    pub fn apply_agent_config(&mut self, agent_config: &AgentConfig) {
+        let (model, backend) =
+            merge_agent_config(self.model.take(), self.backend.take(), agent_config);
+        self.model = model;
+        self.backend = backend;
+        self.skip_permissions |= agent_config.skip_permissions;
+        self.prompt_prefix = agent_config.prompt_prefix.clone();
+        if self.fallback.is_empty() {
+            self.fallback = agent_config.fallback.clone();
+        }
+
+        for (key, value) in &agent_config.env {
+            self.env.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+    }
 }
 
 /// Task specification for parallel mode
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TaskSpec {
     /// Task ID
     pub id: String,
@@ -145,6 +336,135 @@ pub struct TaskSpec {
     /// Use minimal environment variables
     #[serde(default, rename = "minimalEnv")]
     pub minimal_env: bool,
+    /// Append the outputs of all dependencies as a context section
+    #[serde(default, rename = "includeDependencyOutputs")]
+    pub include_dependency_outputs: bool,
+    /// Overall timeout in seconds (overrides `--timeout`)
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    /// Inactivity timeout in seconds (overrides `--idle-timeout`)
+    #[serde(default, rename = "idleTimeout")]
+    pub idle_timeout: Option<u64>,
+    /// Extra attempts after a failure (overrides `--retries`)
+    #[serde(default)]
+    pub retries: Option<u32>,
+    /// Base retry delay in seconds (overrides `--retry-backoff`)
+    #[serde(default, rename = "retryBackoff")]
+    pub retry_backoff: Option<u64>,
+    /// Failures worth retrying (overrides `--retry-on`)
+    #[serde(default, rename = "retryOn")]
+    pub retry_on: Option<Vec<RetryCondition>>,
+    /// Replay file for the mock backend (overrides `--mock-file`)
+    #[serde(default, rename = "mockFile")]
+    pub mock_file: Option<String>,
+}
+
+/// Failure that makes a task eligible for another attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "RawRetryCondition", into = "RawRetryCondition")]
+pub enum RetryCondition {
+    /// Backend exited with this code
+    ExitCode(i32),
+    /// Failure was classified as this kind
+    Kind(ErrorKind),
+}
+
+/// `retryOn` entries as written: a number or a kind name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum RawRetryCondition {
+    Code(i32),
+    Name(String),
+}
+
+impl TryFrom<RawRetryCondition> for RetryCondition {
+    type Error = String;
+
+    fn try_from(raw: RawRetryCondition) -> std::result::Result<Self, Self::Error> {
+        match raw {
+            RawRetryCondition::Code(code) => Ok(Self::ExitCode(code)),
+            RawRetryCondition::Name(name) => name.parse(),
+        }
+    }
+}
+
+impl From<RetryCondition> for RawRetryCondition {
+    fn from(condition: RetryCondition) -> Self {
+        match condition {
+            RetryCondition::ExitCode(code) => Self::Code(code),
+            RetryCondition::Kind(kind) => Self::Name(kind.as_str().to_string()),
+        }
+    }
+}
+
+impl std::str::FromStr for RetryCondition {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(code) = s.parse() {
+            return Ok(Self::ExitCode(code));
+        }
+        ErrorKind::parse(s).map(Self::Kind).ok_or_else(|| {
+            let kinds: Vec<&str> = ErrorKind::ALL.iter().map(|k| k.as_str()).collect();
+            format!(
+                "invalid retry condition '{}': expected an exit code or one of {}",
+                s,
+                kinds.join(", ")
+            )
+        })
+    }
+}
+
+/// How often and when a failed task is attempted again
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RetryPolicy {
+    /// Extra attempts after the first one
+    pub retries: u32,
+    /// Base delay in seconds, doubled after every attempt
+    pub backoff: u64,
+    /// Failures worth retrying; empty means any failure
+    pub retry_on: Vec<RetryCondition>,
+}
+
+impl RetryPolicy {
+    /// Policy from `--retries`, `--retry-backoff` and `--retry-on`
+    pub fn from_cli(cli: &Cli) -> Self {
+        Self {
+            retries: cli.retries,
+            backoff: cli.retry_backoff,
+            retry_on: cli.retry_on.clone(),
+        }
+    }
+
+    /// Policy for a parallel task: task fields override the global flags
+    pub fn for_task(spec: &TaskSpec, cli: &Cli) -> Self {
+        Self {
+            retries: spec.retries.unwrap_or(cli.retries),
+            backoff: spec.retry_backoff.unwrap_or(cli.retry_backoff),
+            retry_on: spec
+                .retry_on
+                .clone()
+                .unwrap_or_else(|| cli.retry_on.clone()),
+        }
+    }
+
+    /// Whether a failure with this exit code and kind should be retried
+    pub fn matches(&self, exit_code: i32, kind: Option<ErrorKind>) -> bool {
+        if self.retry_on.is_empty() {
+            return true;
+        }
+        self.retry_on.iter().any(|condition| match condition {
+            RetryCondition::ExitCode(code) => *code == exit_code,
+            RetryCondition::Kind(k) => Some(*k) == kind,
+        })
+    }
+
+    /// Delay before attempt `next_attempt` (2 for the first retry)
+    pub fn delay(&self, next_attempt: u32) -> std::time::Duration {
+        let factor = 1u64 << next_attempt.saturating_sub(2).min(16);
+        std::time::Duration::from_secs(self.backoff.saturating_mul(factor))
+    }
 }
 
 /// Parallel execution configuration
@@ -154,27 +474,362 @@ pub struct ParallelConfig {
     pub tasks: Vec<TaskSpec>,
 }
 
+impl ParallelConfig {
+    /// Validate the task graph before anything is spawned
+    ///
+    /// Reports empty/duplicate IDs, self-dependencies and unknown dependency IDs as
+    /// `ConfigError::InvalidTask`, and dependency cycles (with their path) as
+    /// `ExecutionError::CircularDependency`.
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+        let mut ids: HashMap<&str, usize> = HashMap::new();
+
+        for (idx, task) in self.tasks.iter().enumerate() {
+            if task.id.trim().is_empty() {
+                problems.push(format!("task #{} has an empty ID", idx + 1));
+            } else if ids.insert(task.id.as_str(), idx).is_some() {
+                problems.push(format!("duplicate task ID '{}'", task.id));
+            }
+        }
+
+        for task in &self.tasks {
+            for dep in &task.dependencies {
+                if dep == &task.id {
+                    problems.push(format!("task '{}' depends on itself", task.id));
+                } else if !ids.contains_key(dep.as_str()) {
+                    problems.push(format!(
+                        "task '{}' depends on unknown task '{}'",
+                        task.id, dep
+                    ));
+                }
+            }
+
+            // `{{x.y}}` that does not name a task here is ordinary text (Vue, Jinja, ...)
+            for placeholder in template::find_placeholders(&task.task)
+                .into_iter()
+                .filter(|p| ids.contains_key(p.task_id.as_str()))
+            {
+                if !task.dependencies.contains(&placeholder.task_id) {
+                    problems.push(format!(
+                        "task '{}' references '{{{{{}.{}}}}}' but does not depend on '{}'",
+                        task.id, placeholder.task_id, placeholder.field, placeholder.task_id
+                    ));
+                } else if !template::PLACEHOLDER_FIELDS.contains(&placeholder.field.as_str()) {
+                    problems.push(format!(
+                        "task '{}' references unknown field '{}' (expected one of: {})",
+                        task.id,
+                        placeholder.field,
+                        template::PLACEHOLDER_FIELDS.join(", ")
+                    ));
+                }
+            }
+        }
+
+        if !problems.is_empty() {
+            return Err(ConfigError::InvalidTask(problems.join("; ")).into());
+        }
+
+        if let Some(cycle) = find_cycle(&self.tasks, &ids) {
+            return Err(ExecutionError::CircularDependency(cycle.join(" -> ")).into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Find a dependency cycle, returned as a path whose first and last IDs match
+fn find_cycle(tasks: &[TaskSpec], ids: &HashMap<&str, usize>) -> Option<Vec<String>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        Unvisited,
+        InProgress,
+        Done,
+    }
+
+    fn visit(
+        idx: usize,
+        tasks: &[TaskSpec],
+        ids: &HashMap<&str, usize>,
+        marks: &mut [Mark],
+        stack: &mut Vec<usize>,
+    ) -> Option<Vec<String>> {
+        marks[idx] = Mark::InProgress;
+        stack.push(idx);
+
+        for dep in &tasks[idx].dependencies {
+            let Some(&dep_idx) = ids.get(dep.as_str()) else {
+                continue;
+            };
+            match marks[dep_idx] {
+                Mark::InProgress => {
+                    let start = stack.iter().position(|&i| i == dep_idx).unwrap_or(0);
+                    let mut path: Vec<String> = stack[start..]
+                        .iter()
+                        .map(|&i| tasks[i].id.clone())
+                        .collect();
+                    path.push(tasks[dep_idx].id.clone());
+                    return Some(path);
+                }
+                Mark::Unvisited => {
+                    if let Some(path) = visit(dep_idx, tasks, ids, marks, stack) {
+                        return Some(path);
+                    }
+                }
+                Mark::Done => {}
+            }
+        }
+
+        stack.pop();
+        marks[idx] = Mark::Done;
+        None
+    }
+
+    let mut marks = vec![Mark::Unvisited; tasks.len()];
+    let mut stack = Vec::new();
+    (0..tasks.len()).find_map(|idx| {
+        if marks[idx] == Mark::Unvisited {
+            visit(idx, tasks, ids, &mut marks, &mut stack)
+        } else {
+            None
+        }
+    })
+}
+
+/// Marker starting a task block in the Node-compatible parallel format
+const TASK_MARKER: &str = "---TASK---";
+
+/// Marker separating a task block's header from its content
+const CONTENT_MARKER: &str = "---CONTENT---";
+
 /// Parse parallel config from stdin
 pub async fn parse_parallel_config() -> Result<ParallelConfig> {
     let stdin = tokio::io::stdin();
     let reader = BufReader::new(stdin);
     let mut lines = reader.lines();
-    let mut tasks = Vec::new();
+    let mut input = Vec::new();
 
     while let Some(line) = lines.next_line().await? {
+        input.push(line);
+    }
+
+    parse_parallel_input(&input.join("\n"))
+}
+
+/// Parse parallel config text, auto-detecting block or JSONL format
+pub fn parse_parallel_input(input: &str) -> Result<ParallelConfig> {
+    if input.lines().any(|line| line.trim() == TASK_MARKER) {
+        parse_block_tasks(input)
+    } else {
+        parse_jsonl_tasks(input)
+    }
+}
+
+/// Parse one JSON `TaskSpec` per line
+fn parse_jsonl_tasks(input: &str) -> Result<ParallelConfig> {
+    let mut tasks = Vec::new();
+
+    for (idx, line) in input.lines().enumerate() {
         let line = line.trim();
         if line.is_empty() || line.starts_with('#') {
             continue;
         }
 
-        let task: TaskSpec = serde_json::from_str(line)
-            .with_context(|| format!("Failed to parse task: {}", line))?;
+        let task: TaskSpec = serde_json::from_str(line).map_err(|e| {
+            ConfigError::InvalidTask(format!(
+                "failed to parse task on line {}: {} ({})",
+                idx + 1,
+                e,
+                line
+            ))
+        })?;
         tasks.push(task);
     }
 
     Ok(ParallelConfig { tasks })
 }
 
+/// Parse `---TASK---` / `---CONTENT---` blocks (Node.js format)
+fn parse_block_tasks(input: &str) -> Result<ParallelConfig> {
+    let mut tasks = Vec::new();
+    let mut block: Option<TaskBlock> = None;
+
+    for (idx, line) in input.lines().enumerate() {
+        let line_no = idx + 1;
+        let trimmed = line.trim();
+
+        if trimmed == TASK_MARKER {
+            if let Some(done) = block.take() {
+                tasks.push(done.finish()?);
+            }
+            block = Some(TaskBlock::new(tasks.len() + 1, line_no));
+            continue;
+        }
+
+        let Some(current) = block.as_mut() else {
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            return Err(ConfigError::InvalidTask(format!(
+                "line {}: expected '{}' before task content",
+                line_no, TASK_MARKER
+            ))
+            .into());
+        };
+
+        if current.in_content {
+            current.content.push(line);
+        } else if trimmed == CONTENT_MARKER {
+            current.in_content = true;
+        } else {
+            current.parse_header_line(trimmed, line_no)?;
+        }
+    }
+
+    if let Some(done) = block.take() {
+        tasks.push(done.finish()?);
+    }
+
+    Ok(ParallelConfig { tasks })
+}
+
+/// A task block being accumulated by `parse_block_tasks`
+struct TaskBlock<'a> {
+    number: usize,
+    start_line: usize,
+    in_content: bool,
+    spec: TaskSpec,
+    content: Vec<&'a str>,
+}
+
+impl<'a> TaskBlock<'a> {
+    fn new(number: usize, start_line: usize) -> Self {
+        Self {
+            number,
+            start_line,
+            in_content: false,
+            spec: TaskSpec::default(),
+            content: Vec::new(),
+        }
+    }
+
+    fn error(&self, line_no: usize, message: impl std::fmt::Display) -> anyhow::Error {
+        ConfigError::InvalidTask(format!(
+            "block {} (line {}): {}",
+            self.number, line_no, message
+        ))
+        .into()
+    }
+
+    fn parse_header_line(&mut self, line: &str, line_no: usize) -> Result<()> {
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(());
+        }
+
+        // Like Node's buildTaskFromBlock, lines it does not understand are skipped
+        let Some((key, value)) = line.split_once(':') else {
+            warn!(block = self.number, line = line_no, content = %line, "Ignoring task header line without ':'");
+            return Ok(());
+        };
+        let value = value.trim();
+        let non_empty = |v: &str| (!v.is_empty()).then(|| v.to_string());
+
+        match key.trim().to_lowercase().as_str() {
+            "id" => self.spec.id = value.to_string(),
+            "workdir" => self.spec.work_dir = non_empty(&expand_home(value)),
+            "session_id" => self.spec.session_id = non_empty(value),
+            "backend" => self.spec.backend = non_empty(value),
+            "model" => self.spec.model = non_empty(value),
+            "agent" => self.spec.agent = non_empty(value),
+            "prompt_file" => self.spec.prompt_file = non_empty(&expand_home(value)),
+            "mock_file" => self.spec.mock_file = non_empty(&expand_home(value)),
+            "reasoning_effort" => self.spec.reasoning_effort = non_empty(value),
+            "dependencies" => {
+                self.spec.dependencies = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|d| !d.is_empty())
+                    .map(str::to_string)
+                    .collect();
+            }
+            "skip_permissions" => {
+                self.spec.skip_permissions = parse_bool(value)
+                    .ok_or_else(|| self.error(line_no, format!("invalid boolean '{}'", value)))?;
+            }
+            "minimal_env" => {
+                self.spec.minimal_env = parse_bool(value)
+                    .ok_or_else(|| self.error(line_no, format!("invalid boolean '{}'", value)))?;
+            }
+            "include_dependency_outputs" => {
+                self.spec.include_dependency_outputs = parse_bool(value)
+                    .ok_or_else(|| self.error(line_no, format!("invalid boolean '{}'", value)))?;
+            }
+            "timeout" => self.spec.timeout = Some(self.parse_seconds(line_no, value)?),
+            "idle_timeout" => self.spec.idle_timeout = Some(self.parse_seconds(line_no, value)?),
+            "retries" => {
+                self.spec.retries = Some(value.parse().map_err(|_| {
+                    self.error(line_no, format!("invalid retry count '{}'", value))
+                })?);
+            }
+            "retry_backoff" => self.spec.retry_backoff = Some(self.parse_seconds(line_no, value)?),
+            "retry_on" => {
+                let conditions = value
+                    .split(',')
+                    .filter(|c| !c.trim().is_empty())
+                    .map(str::parse)
+                    .collect::<std::result::Result<Vec<RetryCondition>, String>>()
+                    .map_err(|e| self.error(line_no, e))?;
+                self.spec.retry_on = Some(conditions);
+            }
+            other => {
+                warn!(block = self.number, line = line_no, key = %other, "Ignoring unknown task header key");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_seconds(&self, line_no: usize, value: &str) -> Result<u64> {
+        value
+            .parse()
+            .map_err(|_| self.error(line_no, format!("invalid number of seconds '{}'", value)))
+    }
+
+    fn finish(mut self) -> Result<TaskSpec> {
+        if self.spec.id.is_empty() {
+            return Err(self.error(self.start_line, "missing required 'id'"));
+        }
+        if !self.in_content {
+            return Err(self.error(
+                self.start_line,
+                format!(
+                    "task '{}' has no '{}' section",
+                    self.spec.id, CONTENT_MARKER
+                ),
+            ));
+        }
+
+        let content = self.content.join("\n").trim().to_string();
+        if content.is_empty() {
+            return Err(self.error(
+                self.start_line,
+                format!("task '{}' has empty content", self.spec.id),
+            ));
+        }
+
+        self.spec.task = content;
+        Ok(self.spec)
+    }
+}
+
+/// Parse a boolean header value
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "1" => Some(true),
+        "false" | "no" | "0" => Some(false),
+        _ => None,
+    }
+}
+
 /// Validate session ID format
 fn is_valid_session_id(session_id: &str) -> bool {
     // Session ID should be alphanumeric with optional hyphens/underscores
@@ -207,6 +862,121 @@ mod tests {
         assert!(!is_valid_session_id("invalid session"));
     }
 
+    #[test]
+    fn test_apply_session_record() {
+        let now = chrono::Utc::now();
+        let record = SessionRecord {
+            id: "t1".to_string(),
+            name: None,
+            backend: "codex".to_string(),
+            model: Some("gpt-5".to_string()),
+            workdir: PathBuf::from("/src/api"),
+            prompt: "Fix it".to_string(),
+            created_at: now,
+            updated_at: now,
+            status: "success".to_string(),
+            runs: 1,
+            handoff_from: None,
+        };
+
+        let mut config = Config {
+            work_dir: PathBuf::from("/elsewhere"),
+            ..Default::default()
+        };
+        config.apply_session_record(&record, false).unwrap();
+        assert_eq!(config.backend.as_deref(), Some("codex"));
+        assert_eq!(config.model.as_deref(), Some("gpt-5"));
+        assert_eq!(config.work_dir, PathBuf::from("/src/api"));
+
+        let mut explicit = Config {
+            backend: Some("Codex".to_string()),
+            model: Some("o3".to_string()),
+            work_dir: PathBuf::from("/elsewhere"),
+            ..Default::default()
+        };
+        explicit.apply_session_record(&record, true).unwrap();
+        assert_eq!(explicit.model.as_deref(), Some("o3"));
+        assert_eq!(explicit.work_dir, PathBuf::from("/elsewhere"));
+
+        let mut wrong = Config {
+            backend: Some("claude".to_string()),
+            ..Default::default()
+        };
+        let err = wrong.apply_session_record(&record, false).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ConfigError>(),
+            Some(ConfigError::IncompatibleSession(_, _, _))
+        ));
+        assert!(err.to_string().contains("created with codex"));
+    }
+
+    #[test]
+    fn test_unsupported_options() {
+        use crate::backend::{ClaudeBackend, CodexBackend, GeminiBackend, OpencodeBackend};
+
+        let config = Config {
+            reasoning_effort: Some("high".to_string()),
+            skip_permissions: true,
+            ..Default::default()
+        };
+        assert!(
+            config
+                .unsupported_options(&CodexBackend.capabilities())
+                .is_empty()
+        );
+        assert_eq!(
+            config.unsupported_options(&ClaudeBackend.capabilities()),
+            vec!["--reasoning-effort"]
+        );
+        assert_eq!(
+            config.unsupported_options(&OpencodeBackend.capabilities()),
+            vec!["--reasoning-effort", "--skip-permissions"]
+        );
+
+        // Gemini always runs with -y, so prompts cannot be kept
+        assert!(
+            config
+                .unsupported_options(&GeminiBackend.capabilities())
+                .contains(&"--reasoning-effort")
+        );
+        let prompts = Config::default();
+        assert_eq!(
+            prompts.unsupported_options(&GeminiBackend.capabilities()),
+            vec!["permission prompts (it always skips them)"]
+        );
+        assert!(
+            prompts
+                .unsupported_options(&ClaudeBackend.capabilities())
+                .is_empty()
+        );
+        let strict_prompts = Config {
+            strict: true,
+            ..prompts
+        };
+        assert!(
+            strict_prompts
+                .check_backend_support(&GeminiBackend)
+                .is_err()
+        );
+
+        let quiet = Config {
+            quiet: true,
+            ..config.clone()
+        };
+        assert!(quiet.check_backend_support(&ClaudeBackend).is_ok());
+
+        let strict = Config {
+            strict: true,
+            ..config
+        };
+        let err = strict.check_backend_support(&ClaudeBackend).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ConfigError>(),
+            Some(ConfigError::UnsupportedOption(_, _))
+        ));
+        assert!(err.to_string().contains("--reasoning-effort"));
+    }
+
     #[test]
     fn test_task_spec_parsing() {
         let json = r#"{"id": "task1", "task": "Test task", "dependencies": ["task0"]}"#;
@@ -216,6 +986,287 @@ mod tests {
         assert_eq!(spec.dependencies, vec!["task0"]);
     }
 
+    #[test]
+    fn test_parse_block_format() {
+        let input = r#"
+---TASK---
+id: analyze
+backend: claude
+---CONTENT---
+Analyze the codebase structure
+
+---TASK---
+id: implement
+backend: codex
+dependencies: analyze, lint
+skip_permissions: true
+session_id: abc123
+workdir: /tmp/project
+timeout: 3600
+idle_timeout: 120
+---CONTENT---
+Implement based on analysis
+key: value lines in content are kept
+"#;
+        let config = parse_parallel_input(input).unwrap();
+        assert_eq!(config.tasks.len(), 2);
+
+        let analyze = &config.tasks[0];
+        assert_eq!(analyze.id, "analyze");
+        assert_eq!(analyze.backend, Some("claude".to_string()));
+        assert_eq!(analyze.task, "Analyze the codebase structure");
+
+        let implement = &config.tasks[1];
+        assert_eq!(implement.dependencies, vec!["analyze", "lint"]);
+        assert!(implement.skip_permissions);
+        assert_eq!(implement.session_id, Some("abc123".to_string()));
+        assert_eq!(implement.work_dir, Some("/tmp/project".to_string()));
+        assert_eq!(implement.timeout, Some(3600));
+        assert_eq!(implement.idle_timeout, Some(120));
+        assert_eq!(
+            implement.task,
+            "Implement based on analysis\nkey: value lines in content are kept"
+        );
+    }
+
+    #[test]
+    fn test_parse_jsonl_format() {
+        let input = r#"
+# comment
+{"id": "a", "task": "First"}
+{"id": "b", "task": "Second", "dependencies": ["a"], "timeout": 120, "idleTimeout": 30}
+"#;
+        let config = parse_parallel_input(input).unwrap();
+        assert_eq!(config.tasks.len(), 2);
+        assert_eq!(config.tasks[1].dependencies, vec!["a"]);
+        assert_eq!(config.tasks[1].timeout, Some(120));
+        assert_eq!(config.tasks[1].idle_timeout, Some(30));
+    }
+
+    #[test]
+    fn test_parse_jsonl_error_reports_line() {
+        let err = parse_parallel_input("{\"id\": \"a\", \"task\": \"x\"}\nnot json").unwrap_err();
+        assert!(err.to_string().contains("line 2"));
+    }
+
+    #[test]
+    fn test_parse_retry_fields() {
+        let jsonl = r#"{"id": "a", "task": "x", "retries": 2, "retryBackoff": 1, "retryOn": ["rate-limited", 137]}"#;
+        let task = &parse_parallel_input(jsonl).unwrap().tasks[0];
+        assert_eq!(task.retries, Some(2));
+        assert_eq!(task.retry_backoff, Some(1));
+        assert_eq!(
+            task.retry_on,
+            Some(vec![
+                RetryCondition::Kind(ErrorKind::RateLimited),
+                RetryCondition::ExitCode(137),
+            ])
+        );
+
+        let block = "---TASK---\nid: a\nretries: 3\nretry_on: network, 1\n---CONTENT---\nx\n";
+        let task = &parse_parallel_input(block).unwrap().tasks[0];
+        assert_eq!(task.retries, Some(3));
+        assert_eq!(
+            task.retry_on,
+            Some(vec![
+                RetryCondition::Kind(ErrorKind::Network),
+                RetryCondition::ExitCode(1),
+            ])
+        );
+
+        let bad = r#"{"id": "a", "task": "x", "retryOn": ["sometimes"]}"#;
+        let err = parse_parallel_input(bad).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("invalid retry condition 'sometimes'")
+        );
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let any = RetryPolicy {
+            retries: 2,
+            backoff: 3,
+            retry_on: Vec::new(),
+        };
+        assert!(any.matches(1, None));
+        assert_eq!(any.delay(2).as_secs(), 3);
+        assert_eq!(any.delay(3).as_secs(), 6);
+        assert_eq!(any.delay(4).as_secs(), 12);
+
+        let rate_limited = RetryPolicy {
+            retry_on: vec![RetryCondition::Kind(ErrorKind::RateLimited)],
+            ..any
+        };
+        assert!(rate_limited.matches(1, Some(ErrorKind::RateLimited)));
+        assert!(!rate_limited.matches(1, Some(ErrorKind::AuthMissing)));
+    }
+
+    #[test]
+    fn test_parse_block_errors_report_block_and_line() {
+        let missing_id = "---TASK---\nbackend: claude\n---CONTENT---\nDo it\n";
+        let err = parse_parallel_input(missing_id).unwrap_err();
+        assert!(err.to_string().contains("block 1 (line 1)"));
+        assert!(err.to_string().contains("missing required 'id'"));
+
+        let bad_bool = "---TASK---\nid: a\n---CONTENT---\nx\n---TASK---\nid: b\nskip_permissions: maybe\n---CONTENT---\ny\n";
+        let err = parse_parallel_input(bad_bool).unwrap_err();
+        assert!(err.to_string().contains("block 2 (line 7)"));
+
+        let no_content = "---TASK---\nid: a\n";
+        let err = parse_parallel_input(no_content).unwrap_err();
+        assert!(err.to_string().contains("---CONTENT---"));
+    }
+
+    #[test]
+    fn test_parse_block_ignores_unknown_header_lines() {
+        let input =
+            "---TASK---\nid: a\ncolour: red\njust a note\nbackend: claude\n---CONTENT---\nx\n";
+        let tasks = parse_parallel_input(input).unwrap().tasks;
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].id, "a");
+        assert_eq!(tasks[0].backend.as_deref(), Some("claude"));
+    }
+
+    fn task(id: &str, deps: &[&str]) -> TaskSpec {
+        TaskSpec {
+            id: id.to_string(),
+            task: "do it".to_string(),
+            dependencies: deps.iter().map(|d| d.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_validate_valid_graph() {
+        let config = ParallelConfig {
+            tasks: vec![task("a", &[]), task("b", &["a"]), task("c", &["a", "b"])],
+        };
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_reports_invalid_tasks() {
+        let config = ParallelConfig {
+            tasks: vec![task("a", &["a"]), task("b", &["typo"]), task("b", &[])],
+        };
+        let err = config.validate().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ConfigError>(),
+            Some(ConfigError::InvalidTask(_))
+        ));
+        let message = err.to_string();
+        assert!(message.contains("duplicate task ID 'b'"));
+        assert!(message.contains("task 'a' depends on itself"));
+        assert!(message.contains("task 'b' depends on unknown task 'typo'"));
+    }
+
+    #[test]
+    fn test_validate_placeholders() {
+        let mut implement = task("implement", &["analyze"]);
+        implement.task = "Build {{analyze.message}} using {{lint.message}}".to_string();
+        let mut review = task("review", &["analyze"]);
+        review.task = "{{analyze.mood}}".to_string();
+        let mut vue = task("vue", &[]);
+        vue.task = "Write a Vue component showing {{user.name}}".to_string();
+        let config = ParallelConfig {
+            tasks: vec![
+                task("analyze", &[]),
+                task("lint", &[]),
+                implement,
+                review,
+                vue,
+            ],
+        };
+
+        let message = config.validate().unwrap_err().to_string();
+        assert!(!message.contains("user"));
+        assert!(message.contains("references '{{lint.message}}' but does not depend on 'lint'"));
+        assert!(message.contains("unknown field 'mood'"));
+        assert!(!message.contains("analyze.message"));
+    }
+
+    #[test]
+    fn test_validate_reports_cycle_path() {
+        let config = ParallelConfig {
+            tasks: vec![
+                task("start", &[]),
+                task("a", &["start", "b"]),
+                task("b", &["c"]),
+                task("c", &["a"]),
+            ],
+        };
+        let err = config.validate().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ExecutionError>(),
+            Some(ExecutionError::CircularDependency(_))
+        ));
+        assert!(err.to_string().contains("a -> b -> c -> a"));
+    }
+
+    #[test]
+    fn test_apply_agent_config_cli_wins() {
+        let agent = AgentConfig {
+            name: "oracle".to_string(),
+            model: Some("claude-opus".to_string()),
+            backend: Some("claude".to_string()),
+            skip_permissions: true,
+            env: HashMap::from([("FOO".to_string(), "bar".to_string())]),
+            prompt_prefix: Some("You are an oracle.".to_string()),
+            fallback: vec!["codex".to_string()],
+        };
+
+        let mut config = Config {
+            model: Some("cli-model".to_string()),
+            ..Default::default()
+        };
+        config.apply_agent_config(&agent);
+
+        assert_eq!(config.model, Some("cli-model".to_string()));
+        assert_eq!(config.backend, Some("claude".to_string()));
+        assert!(config.skip_permissions);
+        assert_eq!(config.env["FOO"], "bar");
+        assert_eq!(config.prompt_prefix, Some("You are an oracle.".to_string()));
+        assert_eq!(config.fallback, vec!["codex"]);
+    }
+
+    #[test]
+    fn test_apply_agent_config_cli_backend_drops_preset_model() {
+        let agent = AgentConfig {
+            name: "develop".to_string(),
+            model: Some("gpt-5-codex".to_string()),
+            backend: Some("codex".to_string()),
+            ..Default::default()
+        };
+
+        // `--agent develop --backend claude` must not pass a codex model to claude
+        let mut config = Config {
+            backend: Some("claude".to_string()),
+            ..Default::default()
+        };
+        config.apply_agent_config(&agent);
+        assert_eq!(config.backend, Some("claude".to_string()));
+        assert_eq!(config.model, None);
+
+        // A chain starting with the preset's backend keeps its model
+        let mut config = Config {
+            backend: Some("codex,claude".to_string()),
+            ..Default::default()
+        };
+        config.apply_agent_config(&agent);
+        assert_eq!(config.model, Some("gpt-5-codex".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_unknown_agent() {
+        let mut config = Config {
+            agent: Some("no-such-agent".to_string()),
+            ..Default::default()
+        };
+        let err = config.resolve_agent().await.unwrap_err();
+        assert!(err.downcast_ref::<ConfigError>().is_some());
+    }
+
     #[test]
     fn test_default_max_parallel_workers() {
         let workers = get_default_max_parallel_workers();
@@ -0,0 +1,535 @@
+//! Failure diagnostics: bounded stderr capture and error classification
+
+use regex::RegexSet;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::LazyLock;
+
+use crate::errors::{BackendError, ConfigError, ExecutionError, exit_codes};
+use crate::executor::PartialResult;
+use crate::filter::NoiseFilter;
+use crate::utils::truncate_chars;
+
+/// Maximum number of stderr lines kept per task
+pub const STDERR_TAIL_LINES: usize = 200;
+
+/// Maximum characters kept per stderr line
+const MAX_LINE_CHARS: usize = 2000;
+
+/// Number of meaningful lines included in an error detail
+const DETAIL_LINES: usize = 10;
+
+/// Ring buffer holding the last lines of a backend's stderr
+#[derive(Debug, Clone)]
+pub struct StderrTail {
+    lines: VecDeque<String>,
+    capacity: usize,
+    dropped: usize,
+}
+
+impl StderrTail {
+    /// Create a buffer keeping at most `capacity` lines
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::with_capacity(capacity.min(64)),
+            capacity: capacity.max(1),
+            dropped: 0,
+        }
+    }
+
+    /// Append a line, evicting the oldest one when full
+    pub fn push(&mut self, line: &str) {
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+            self.dropped += 1;
+        }
+        let line = line.trim_end_matches(['\n', '\r']);
+        self.lines.push_back(truncate_chars(line, MAX_LINE_CHARS));
+    }
+
+    /// Lines currently held
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().map(String::as_str)
+    }
+}
+
+impl std::fmt::Display for StderrTail {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.dropped > 0 {
+            writeln!(f, "[... {} earlier lines dropped ...]", self.dropped)?;
+        }
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Classified failure kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorKind {
+    AuthMissing,
+    RateLimited,
+    QuotaExceeded,
+    ModelNotFound,
+    ContextTooLong,
+    Network,
+    InvalidArgument,
+    BackendNotFound,
+    Timeout,
+    IdleTimeout,
+    Signal,
+    Unknown,
+}
+
+impl ErrorKind {
+    /// Every kind, in declaration order
+    pub const ALL: [ErrorKind; 12] = [
+        ErrorKind::AuthMissing,
+        ErrorKind::RateLimited,
+        ErrorKind::QuotaExceeded,
+        ErrorKind::ModelNotFound,
+        ErrorKind::ContextTooLong,
+        ErrorKind::Network,
+        ErrorKind::InvalidArgument,
+        ErrorKind::BackendNotFound,
+        ErrorKind::Timeout,
+        ErrorKind::IdleTimeout,
+        ErrorKind::Signal,
+        ErrorKind::Unknown,
+    ];
+
+    /// Look up a kind by its identifier (e.g. `rate-limited`)
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str() == name)
+    }
+
+    /// Kind identifier as serialized in JSON output
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorKind::AuthMissing => "auth-missing",
+            ErrorKind::RateLimited => "rate-limited",
+            ErrorKind::QuotaExceeded => "quota-exceeded",
+            ErrorKind::ModelNotFound => "model-not-found",
+            ErrorKind::ContextTooLong => "context-too-long",
+            ErrorKind::Network => "network",
+            ErrorKind::InvalidArgument => "invalid-argument",
+            ErrorKind::BackendNotFound => "backend-not-found",
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::IdleTimeout => "idle-timeout",
+            ErrorKind::Signal => "signal",
+            ErrorKind::Unknown => "unknown",
+        }
+    }
+
+    /// Documented process exit code for this kind, as `errors::get_exit_code`
+    /// maps the typed errors behind it; `None` for signals (`128 + N`)
+    pub fn exit_code(self) -> Option<i32> {
+        match self {
+            ErrorKind::InvalidArgument => Some(exit_codes::INVALID_ARGUMENT),
+            ErrorKind::BackendNotFound => Some(exit_codes::BACKEND_NOT_FOUND),
+            ErrorKind::Timeout => Some(exit_codes::TIMEOUT),
+            ErrorKind::IdleTimeout => Some(exit_codes::IDLE_TIMEOUT),
+            ErrorKind::Signal => None,
+            _ => Some(exit_codes::BACKEND_FAILED),
+        }
+    }
+
+    /// Remediation hint shown alongside the error
+    pub fn hint(self) -> Option<&'static str> {
+        match self {
+            ErrorKind::AuthMissing => Some(
+                "Set the backend's API key (ANTHROPIC_API_KEY, OPENAI_API_KEY, GEMINI_API_KEY) \
+                 or log in with the backend CLI",
+            ),
+            ErrorKind::RateLimited => {
+                Some("Wait and retry, or lower --max-parallel-workers to send fewer requests")
+            }
+            ErrorKind::QuotaExceeded => {
+                Some("Check the account's billing and usage limits, or switch backend")
+            }
+            ErrorKind::ModelNotFound => Some(
+                "Check --model or the agent preset; the model may be misspelled or unavailable \
+                 to this account",
+            ),
+            ErrorKind::ContextTooLong => {
+                Some("Shorten the prompt or split the task into smaller steps")
+            }
+            ErrorKind::Network => {
+                Some("Check network connectivity and HTTP_PROXY/HTTPS_PROXY settings")
+            }
+            ErrorKind::InvalidArgument => Some("Run with --help to see the expected usage"),
+            ErrorKind::BackendNotFound => Some(
+                "Install the backend CLI (claude, codex, gemini or opencode) and make sure it \
+                 is on PATH",
+            ),
+            ErrorKind::Timeout => Some("Increase --timeout or break the task into smaller steps"),
+            ErrorKind::IdleTimeout => Some(
+                "The backend may be stuck on a permission prompt or a hung request; consider \
+                 --skip-permissions or a larger --idle-timeout",
+            ),
+            ErrorKind::Signal | ErrorKind::Unknown => None,
+        }
+    }
+}
+
+/// Patterns per kind, checked in order (quota before rate limits, since quota
+/// errors are often reported with HTTP 429 too)
+///
+/// Each pattern needs an error context around its keyword, so informational
+/// lines such as "Using API key from env" stay unclassified.
+static CLASSIFIERS: LazyLock<Vec<(ErrorKind, RegexSet)>> = LazyLock::new(|| {
+    let table: [(ErrorKind, &[&str]); 6] = [
+        (
+            ErrorKind::QuotaExceeded,
+            &[
+                r"\bquota (exceeded|exhausted)\b",
+                r"exceeded (your |the )?(current )?quota",
+                r"insufficient_quota",
+                r"billing (error|issue|hard limit)",
+                r"credit balance (is )?too low",
+                r"(hit|reached|exceeded) (your |the )?usage limit",
+            ],
+        ),
+        (
+            ErrorKind::AuthMissing,
+            &[
+                r"(error|invalid|missing|expired|incorrect|no)\b.{0,40}api[ _-]?key",
+                r"api[ _-]?key (is )?(invalid|missing|expired|not (set|found))",
+                r"unauthori[sz]ed",
+                r"\b401\b",
+                r"not logged in",
+                r"log ?in required",
+                r"\b(authentication|auth) (failed|required|error)\b",
+                r"invalid x-api-key",
+                r"credentials? (not found|missing|expired)",
+            ],
+        ),
+        (
+            ErrorKind::RateLimited,
+            &[
+                r"rate[ _-]?limit(ed|s? (exceeded|reached|hit)|_exceeded|_error)",
+                r"\b429\b",
+                r"too many requests",
+                r"overloaded",
+            ],
+        ),
+        (
+            ErrorKind::ModelNotFound,
+            &[
+                r"model[_ ]not[_ ]found",
+                r"unknown model",
+                r"invalid model",
+                r"model .*(does not exist|not (found|available|supported))",
+            ],
+        ),
+        (
+            ErrorKind::ContextTooLong,
+            &[
+                r"context (length|window) (exceeded|is exceeded)",
+                r"exceeds? (the )?(model's )?(maximum )?context (length|window)",
+                r"prompt is too long",
+                r"maximum context length",
+                r"too many tokens",
+                r"(exceeds?|exceeded) (the )?token limit",
+                r"input is too long",
+            ],
+        ),
+        (
+            ErrorKind::Network,
+            &[
+                r"econnrefused",
+                r"econnreset",
+                r"enotfound",
+                r"etimedout",
+                r"connection (refused|reset|closed|error)",
+                r"getaddrinfo",
+                r"socket hang up",
+                r"network (error|is unreachable)",
+                r"dns (error|lookup failed|resolution failed)",
+                r"tls handshake (failed|error|timeout)",
+            ],
+        ),
+    ];
+
+    table
+        .into_iter()
+        .map(|(kind, patterns)| {
+            let patterns = patterns.iter().map(|p| format!("(?i){}", p));
+            (kind, RegexSet::new(patterns).unwrap())
+        })
+        .collect()
+});
+
+/// Classify a single line of error text
+pub fn classify_line(line: &str) -> ErrorKind {
+    CLASSIFIERS
+        .iter()
+        .find(|(_, set)| set.is_match(line))
+        .map(|(kind, _)| *kind)
+        .unwrap_or(ErrorKind::Unknown)
+}
+
+/// Structured description of why a task failed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ErrorDetail {
+    /// Classified kind
+    pub kind: ErrorKind,
+    /// Most relevant error message
+    pub message: String,
+    /// Remediation hint
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+    /// Underlying causes, outermost first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub causes: Vec<String>,
+    /// Last meaningful stderr/error lines
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub lines: Vec<String>,
+}
+
+impl ErrorDetail {
+    /// Build a detail of the given kind with its default hint
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            hint: kind.hint().map(str::to_string),
+            causes: Vec::new(),
+            lines: Vec::new(),
+        }
+    }
+
+    /// Build a detail from a single message, classifying it
+    pub fn from_message(message: impl Into<String>) -> Self {
+        let message = message.into();
+        Self::new(classify_line(&message), message)
+    }
+
+    /// Build a detail from an error returned before or while running a task
+    pub fn from_error(err: &anyhow::Error) -> Self {
+        let message = err.to_string();
+        let kind = if err.downcast_ref::<ConfigError>().is_some() {
+            ErrorKind::InvalidArgument
+        } else if let Some(e) = err.downcast_ref::<BackendError>() {
+            match e {
+                BackendError::NotFound(_, _) | BackendError::NotAvailable(_, _) => {
+                    ErrorKind::BackendNotFound
+                }
+                BackendError::Timeout(_) => ErrorKind::Timeout,
+                BackendError::IdleTimeout(_) => ErrorKind::IdleTimeout,
+                BackendError::ExecutionFailed(_) => classify_line(&message),
+            }
+        } else {
+            match err.downcast_ref::<ExecutionError>() {
+                Some(ExecutionError::SignalTerminated(_) | ExecutionError::Cancelled(_)) => {
+                    ErrorKind::Signal
+                }
+                Some(ExecutionError::CircularDependency(_)) => ErrorKind::InvalidArgument,
+                _ => classify_line(&format!("{:#}", err)),
+            }
+        };
+
+        Self {
+            causes: err
+                .chain()
+                .skip(1)
+                .filter(|c| !c.is::<PartialResult>())
+                .map(|c| c.to_string())
+                .collect(),
+            ..Self::new(kind, message)
+        }
+    }
+
+    /// Build a detail for a failed run from backend error events and stderr
+    ///
+    /// `fallback` is used as the message when nothing more specific is found.
+    pub fn from_failure(
+        event_errors: &[String],
+        stderr: &StderrTail,
+        backend: &str,
+        fallback: impl Into<String>,
+    ) -> Self {
+        let filter = NoiseFilter::for_backend(backend);
+        let meaningful: Vec<String> = stderr.lines().filter_map(|l| filter.clean(l)).collect();
+        let start = meaningful.len().saturating_sub(DETAIL_LINES);
+        let lines: Vec<String> = meaningful[start..].to_vec();
+
+        // Backend-reported errors are the most specific, then the last stderr lines
+        // from newest to oldest; older stderr is mostly startup chatter
+        let mut candidates = event_errors.iter().rev().chain(lines.iter().rev());
+        let classified = candidates
+            .clone()
+            .map(|line| (classify_line(line), line))
+            .find(|(kind, _)| *kind != ErrorKind::Unknown);
+
+        let (kind, message) = match classified {
+            Some((kind, line)) => (kind, line.clone()),
+            None => {
+                let message = candidates
+                    .next()
+                    .cloned()
+                    .unwrap_or_else(|| fallback.into());
+                (ErrorKind::Unknown, message)
+            }
+        };
+
+        Self {
+            lines,
+            ..Self::new(kind, message)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stderr_tail_is_bounded() {
+        let mut tail = StderrTail::new(3);
+        for i in 0..10 {
+            tail.push(&format!("line {}\n", i));
+        }
+        assert_eq!(
+            tail.lines().collect::<Vec<_>>(),
+            vec!["line 7", "line 8", "line 9"]
+        );
+        assert!(
+            tail.to_string()
+                .starts_with("[... 7 earlier lines dropped ...]")
+        );
+    }
+
+    #[test]
+    fn test_error_kind_parse_round_trip() {
+        for kind in ErrorKind::ALL {
+            assert_eq!(ErrorKind::parse(kind.as_str()), Some(kind));
+        }
+        assert_eq!(ErrorKind::parse("bogus"), None);
+    }
+
+    #[test]
+    fn test_classify_line() {
+        assert_eq!(
+            classify_line("Error: Invalid API key · Please run /login"),
+            ErrorKind::AuthMissing
+        );
+        assert_eq!(
+            classify_line("stream error: 429 Too Many Requests"),
+            ErrorKind::RateLimited
+        );
+        assert_eq!(
+            classify_line("429: You exceeded your current quota"),
+            ErrorKind::QuotaExceeded
+        );
+        assert_eq!(
+            classify_line("The model `gpt-9` does not exist"),
+            ErrorKind::ModelNotFound
+        );
+        assert_eq!(
+            classify_line("prompt is too long: 250000 tokens > 200000 maximum"),
+            ErrorKind::ContextTooLong
+        );
+        assert_eq!(
+            classify_line("request failed: connect ECONNREFUSED 127.0.0.1:443"),
+            ErrorKind::Network
+        );
+        assert_eq!(classify_line("something odd"), ErrorKind::Unknown);
+    }
+
+    #[test]
+    fn test_classify_line_ignores_informational_lines() {
+        for line in [
+            "Using API key from env",
+            "authentication: chatgpt",
+            "Please log in at https://example.com to sync settings",
+            "quota: 1000 requests/day",
+            "billing account: personal",
+            "rate limits: 50 requests/min",
+            "context window: 200000 tokens",
+            "dns: 1.1.1.1",
+        ] {
+            assert_eq!(classify_line(line), ErrorKind::Unknown, "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_error_detail_from_failure() {
+        let mut tail = StderrTail::new(STDERR_TAIL_LINES);
+        tail.push("[STARTUP] Loading config");
+        tail.push("\x1b[31mError: rate limit exceeded, retry later\x1b[0m");
+        tail.push("    at async main (cli.js:10:5)");
+
+        let detail = ErrorDetail::from_failure(&[], &tail, "gemini", "exit code 1");
+        assert_eq!(detail.kind, ErrorKind::RateLimited);
+        assert_eq!(detail.message, "Error: rate limit exceeded, retry later");
+        assert!(detail.hint.is_some());
+        assert_eq!(detail.lines.len(), 2);
+
+        let value = serde_json::to_value(&detail).unwrap();
+        assert_eq!(value["kind"], detail.kind.as_str());
+    }
+
+    #[test]
+    fn test_error_detail_classifies_only_last_lines() {
+        let mut tail = StderrTail::new(STDERR_TAIL_LINES);
+        tail.push("Error: API key expired, refreshing from keychain");
+        for i in 0..DETAIL_LINES {
+            tail.push(&format!("compiling crate {}", i));
+        }
+        tail.push("error: could not compile `app`");
+
+        let detail = ErrorDetail::from_failure(&[], &tail, "codex", "exit code 1");
+        assert_eq!(detail.kind, ErrorKind::Unknown);
+        assert_eq!(detail.message, "error: could not compile `app`");
+    }
+
+    #[test]
+    fn test_error_detail_prefers_event_errors() {
+        let mut tail = StderrTail::new(10);
+        tail.push("some unrelated warning");
+        let events = vec!["Invalid API key".to_string()];
+
+        let detail = ErrorDetail::from_failure(&events, &tail, "claude", "exit code 1");
+        assert_eq!(detail.kind, ErrorKind::AuthMissing);
+        assert_eq!(detail.message, "Invalid API key");
+    }
+
+    #[test]
+    fn test_error_detail_from_error() {
+        let timeout = anyhow::Error::new(BackendError::Timeout(30));
+        assert_eq!(ErrorDetail::from_error(&timeout).kind, ErrorKind::Timeout);
+
+        let signal = anyhow::Error::new(ExecutionError::SignalTerminated(15));
+        assert_eq!(ErrorDetail::from_error(&signal).kind, ErrorKind::Signal);
+
+        let config = anyhow::Error::new(ConfigError::InvalidSessionId("x".into()));
+        assert_eq!(
+            ErrorDetail::from_error(&config).kind,
+            ErrorKind::InvalidArgument
+        );
+
+        let missing = anyhow::Error::new(BackendError::NotFound("foo".into(), String::new()));
+        assert_eq!(
+            ErrorDetail::from_error(&missing).kind,
+            ErrorKind::BackendNotFound
+        );
+
+        let other = anyhow::anyhow!("connect ECONNREFUSED").context("Failed to fetch");
+        let detail = ErrorDetail::from_error(&other);
+        assert_eq!(detail.kind, ErrorKind::Network);
+        assert_eq!(detail.message, "Failed to fetch");
+        assert_eq!(detail.causes, vec!["connect ECONNREFUSED"]);
+    }
+
+    #[test]
+    fn test_error_detail_fallback() {
+        let tail = StderrTail::new(10);
+        let detail = ErrorDetail::from_failure(&[], &tail, "codex", "exited with code 1");
+        assert_eq!(detail.kind, ErrorKind::Unknown);
+        assert_eq!(detail.message, "exited with code 1");
+        assert!(detail.hint.is_none());
+    }
+}
@@ -0,0 +1,404 @@
+//! Doctor command - report usable backends and wrapper directories
+//!
+//! For every built-in and custom backend this resolves the binary, runs
+//! `--version`, checks it against the range the wrapper was tested with and
+//! looks for credentials in the environment or the backend's config files.
+
+use anyhow::Result;
+use regex::Regex;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::LazyLock;
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::agent_config::get_config_dir;
+use crate::backend::{Backend, Capabilities, all_backends};
+use crate::backend_config::{BUILTIN_BACKENDS, backends_file};
+use crate::errors::exit_codes;
+use crate::init::get_skill_dir;
+use crate::logger::get_log_dir;
+
+/// How long `--version` may take before the probe gives up
+const VERSION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Backend versions the wrapper is tested with: inclusive minimum, exclusive maximum
+const TESTED_VERSIONS: [(&str, &str, &str); 4] = [
+    ("claude", "1.0.0", "3.0.0"),
+    ("codex", "0.20.0", "1.0.0"),
+    ("gemini", "0.1.0", "1.0.0"),
+    ("opencode", "0.5.0", "2.0.0"),
+];
+
+/// Environment variables and files (relative to home) that hold each backend's credentials
+const AUTH_SOURCES: [(&str, &[&str], &[&str]); 4] = [
+    (
+        "claude",
+        &[
+            "ANTHROPIC_API_KEY",
+            "ANTHROPIC_AUTH_TOKEN",
+            "CLAUDE_CODE_OAUTH_TOKEN",
+            "CLAUDE_CODE_USE_BEDROCK",
+            "CLAUDE_CODE_USE_VERTEX",
+        ],
+        &[".claude/.credentials.json"],
+    ),
+    (
+        "codex",
+        &["OPENAI_API_KEY", "CODEX_API_KEY"],
+        &[".codex/auth.json"],
+    ),
+    (
+        "gemini",
+        &[
+            "GEMINI_API_KEY",
+            "GOOGLE_API_KEY",
+            "GOOGLE_GENAI_USE_VERTEXAI",
+        ],
+        &[".gemini/oauth_creds.json"],
+    ),
+    (
+        "opencode",
+        &["ANTHROPIC_API_KEY", "OPENAI_API_KEY", "OPENROUTER_API_KEY"],
+        &[".local/share/opencode/auth.json"],
+    ),
+];
+
+static VERSION_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(\d+)\.(\d+)(?:\.(\d+))?").unwrap());
+
+/// Full doctor report
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DoctorReport {
+    /// Wrapper version
+    pub version: &'static str,
+    /// Every known backend
+    pub backends: Vec<BackendReport>,
+    /// Wrapper directories and config files
+    pub paths: Vec<PathReport>,
+}
+
+/// Status of one backend
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackendReport {
+    /// Backend name
+    pub name: String,
+    /// `built-in` or `custom`
+    pub kind: &'static str,
+    /// Command looked up on PATH
+    pub command: String,
+    /// Whether the command was found
+    pub available: bool,
+    /// Resolved binary path
+    pub path: Option<PathBuf>,
+    /// First line of `--version`
+    pub version: Option<String>,
+    /// Version range the wrapper is tested with
+    pub tested_range: Option<String>,
+    /// Whether `version` falls inside `tested_range` (null when unknown)
+    pub compatible: Option<bool>,
+    /// Credential detection
+    pub auth: AuthStatus,
+    /// Supported options
+    pub capabilities: Capabilities,
+    /// How to install the backend when it is missing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub install_hint: Option<String>,
+}
+
+/// Whether credentials appear to be configured
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct AuthStatus {
+    /// `null` when the backend's credential sources are unknown
+    pub configured: Option<bool>,
+    /// Environment variables and files that were found
+    pub sources: Vec<String>,
+}
+
+/// A wrapper directory or config file
+#[derive(Debug, Serialize)]
+pub struct PathReport {
+    /// What the path is for
+    pub name: &'static str,
+    /// Location
+    pub path: PathBuf,
+    /// Whether it exists
+    pub exists: bool,
+}
+
+impl BackendReport {
+    /// Whether the backend is installed, in the tested range and has credentials
+    fn healthy(&self) -> bool {
+        self.available && self.compatible != Some(false) && self.auth.configured != Some(false)
+    }
+}
+
+/// Run the doctor command; exits non-zero when no backend is installed
+pub async fn run_doctor(json: bool) -> Result<i32> {
+    let report = build_report().await?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", format_report(&report));
+    }
+
+    Ok(if report.backends.iter().any(|b| b.available) {
+        exit_codes::SUCCESS
+    } else {
+        exit_codes::BACKEND_NOT_FOUND
+    })
+}
+
+/// Probe every backend and collect the wrapper paths
+pub async fn build_report() -> Result<DoctorReport> {
+    let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+
+    let mut backends = Vec::new();
+    for backend in all_backends()? {
+        backends.push(probe_backend(backend.as_ref(), &home).await);
+    }
+
+    let config_dir = get_config_dir();
+    let paths = [
+        ("config", config_dir.clone()),
+        ("agents", config_dir.join("agents.yaml")),
+        ("backends", backends_file()),
+        ("logs", get_log_dir()),
+        ("history", config_dir.join("history.jsonl")),
+        ("skill", get_skill_dir()?),
+    ]
+    .into_iter()
+    .map(|(name, path)| PathReport {
+        name,
+        exists: path.exists(),
+        path,
+    })
+    .collect();
+
+    Ok(DoctorReport {
+        version: env!("CARGO_PKG_VERSION"),
+        backends,
+        paths,
+    })
+}
+
+/// Check one backend's binary, version and credentials
+async fn probe_backend(backend: &dyn Backend, home: &Path) -> BackendReport {
+    let name = backend.name().to_string();
+    let path = which::which(backend.command()).ok();
+    let version = match path {
+        Some(ref path) => probe_version(path).await,
+        None => None,
+    };
+    let range = TESTED_VERSIONS
+        .iter()
+        .find(|(backend, _, _)| *backend == name)
+        .map(|&(_, min, max)| (min, max));
+    let compatible = match (version.as_deref(), range) {
+        (Some(version), Some((min, max))) => version_in_range(version, min, max),
+        _ => None,
+    };
+
+    BackendReport {
+        kind: if BUILTIN_BACKENDS.contains(&name.as_str()) {
+            "built-in"
+        } else {
+            "custom"
+        },
+        command: backend.command().to_string(),
+        available: path.is_some(),
+        install_hint: path.is_none().then(|| backend.install_hint()),
+        path,
+        version,
+        tested_range: range.map(|(min, max)| format!(">={}, <{}", min, max)),
+        compatible,
+        auth: detect_auth(&name, home, |key| std::env::var(key).ok()),
+        capabilities: backend.capabilities(),
+        name,
+    }
+}
+
+/// Run `<binary> --version` and return its first non-empty line
+async fn probe_version(path: &Path) -> Option<String> {
+    let output = Command::new(path)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(VERSION_TIMEOUT, output)
+        .await
+        .ok()?
+        .ok()?;
+
+    [&output.stdout, &output.stderr]
+        .into_iter()
+        .flat_map(|bytes| {
+            String::from_utf8_lossy(bytes)
+                .lines()
+                .map(|line| line.trim().to_string())
+                .collect::<Vec<_>>()
+        })
+        .find(|line| !line.is_empty())
+}
+
+/// Parse the first `major.minor[.patch]` in a version string
+fn parse_version(text: &str) -> Option<(u64, u64, u64)> {
+    let caps = VERSION_RE.captures(text)?;
+    let part = |i: usize| {
+        caps.get(i)
+            .and_then(|m| m.as_str().parse().ok())
+            .unwrap_or(0)
+    };
+    Some((part(1), part(2), part(3)))
+}
+
+/// Whether `version` is within `[min, max)`; `None` when it cannot be parsed
+fn version_in_range(version: &str, min: &str, max: &str) -> Option<bool> {
+    let version = parse_version(version)?;
+    Some(version >= parse_version(min)? && version < parse_version(max)?)
+}
+
+/// Look for a backend's credentials in the environment and under `home`
+fn detect_auth(name: &str, home: &Path, env: impl Fn(&str) -> Option<String>) -> AuthStatus {
+    let Some(&(_, vars, files)) = AUTH_SOURCES.iter().find(|(backend, _, _)| *backend == name)
+    else {
+        return AuthStatus::default();
+    };
+
+    let mut sources: Vec<String> = vars
+        .iter()
+        .filter(|var| env(var).is_some_and(|value| !value.is_empty()))
+        .map(|var| var.to_string())
+        .collect();
+    sources.extend(
+        files
+            .iter()
+            .filter(|file| home.join(file).exists())
+            .map(|file| format!("~/{}", file)),
+    );
+
+    AuthStatus {
+        configured: Some(!sources.is_empty()),
+        sources,
+    }
+}
+
+/// Render the report for a terminal
+fn format_report(report: &DoctorReport) -> String {
+    let use_ascii = std::env::var("CODEAGENT_ASCII_MODE").is_ok();
+    let (ok_sym, warn_sym, missing_sym) = if use_ascii {
+        ("[OK]", "[WARN]", "[MISSING]")
+    } else {
+        ("✅", "⚠️ ", "❌")
+    };
+
+    let mut out = format!("codeagent {}\n\nBackends:\n", report.version);
+    for backend in &report.backends {
+        let symbol = if !backend.available {
+            missing_sym
+        } else if backend.healthy() {
+            ok_sym
+        } else {
+            warn_sym
+        };
+        out.push_str(&format!(
+            "  {} {} ({})\n",
+            symbol, backend.name, backend.kind
+        ));
+
+        let Some(ref path) = backend.path else {
+            out.push_str(&format!(
+                "     not found on PATH: {}\n",
+                backend.install_hint.as_deref().unwrap_or(&backend.command)
+            ));
+            continue;
+        };
+        out.push_str(&format!("     path:    {}\n", path.display()));
+
+        let mut version = backend
+            .version
+            .clone()
+            .unwrap_or_else(|| "unknown".to_string());
+        if let Some(ref range) = backend.tested_range {
+            let verdict = match backend.compatible {
+                Some(true) => "tested",
+                Some(false) => "untested, expected",
+                None => "tested range",
+            };
+            version.push_str(&format!(" ({} {})", verdict, range));
+        }
+        out.push_str(&format!("     version: {}\n", version));
+
+        let auth = match backend.auth.configured {
+            Some(true) => backend.auth.sources.join(", "),
+            Some(false) => "no credentials found".to_string(),
+            None => "unknown".to_string(),
+        };
+        out.push_str(&format!("     auth:    {}\n", auth));
+    }
+
+    out.push_str("\nPaths:\n");
+    for path in &report.paths {
+        out.push_str(&format!(
+            "  {:<9} {}{}\n",
+            path.name,
+            path.path.display(),
+            if path.exists { "" } else { " (missing)" }
+        ));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("2.0.14 (Claude Code)"), Some((2, 0, 14)));
+        assert_eq!(parse_version("codex-cli 0.46.0"), Some((0, 46, 0)));
+        assert_eq!(parse_version("v1.2"), Some((1, 2, 0)));
+        assert_eq!(parse_version("unknown"), None);
+    }
+
+    #[test]
+    fn test_version_in_range() {
+        assert_eq!(version_in_range("2.0.14", "1.0.0", "3.0.0"), Some(true));
+        assert_eq!(version_in_range("3.0.0", "1.0.0", "3.0.0"), Some(false));
+        assert_eq!(version_in_range("0.9.9", "1.0.0", "3.0.0"), Some(false));
+        assert_eq!(version_in_range("dev build", "1.0.0", "3.0.0"), None);
+    }
+
+    #[test]
+    fn test_detect_auth() {
+        let home = tempfile::tempdir().unwrap();
+        let no_env = |_: &str| None;
+
+        let missing = detect_auth("codex", home.path(), no_env);
+        assert_eq!(missing.configured, Some(false));
+
+        std::fs::create_dir_all(home.path().join(".codex")).unwrap();
+        std::fs::write(home.path().join(".codex/auth.json"), "{}").unwrap();
+        let from_file = detect_auth("codex", home.path(), no_env);
+        assert_eq!(from_file.sources, vec!["~/.codex/auth.json"]);
+
+        let from_env = detect_auth("gemini", home.path(), |key| {
+            (key == "GEMINI_API_KEY").then(|| "secret".to_string())
+        });
+        assert_eq!(from_env.configured, Some(true));
+        assert_eq!(from_env.sources, vec!["GEMINI_API_KEY"]);
+
+        let empty_env = detect_auth("gemini", home.path(), |_| Some(String::new()));
+        assert_eq!(empty_env.configured, Some(false));
+
+        assert_eq!(
+            detect_auth("my-custom", home.path(), no_env),
+            AuthStatus::default()
+        );
+    }
+}
@@ -30,13 +30,25 @@ pub enum ConfigError {
 
     #[error("Invalid task: {0}")]
     InvalidTask(String),
+
+    #[error("Unknown agent: {0}. Available: {1}")]
+    UnknownAgent(String, String),
+
+    #[error("Backend '{0}' does not support {1}")]
+    UnsupportedOption(String, String),
+
+    #[error("No recorded session matches '{0}'")]
+    UnknownSession(String),
+
+    #[error("Session '{0}' was created with {1} and cannot be resumed with {2}")]
+    IncompatibleSession(String, String, String),
 }
 
 /// Backend-related errors
 #[derive(Error, Debug)]
 pub enum BackendError {
-    #[error("Backend not found: {0}. Available: codex, claude, gemini, opencode")]
-    NotFound(String),
+    #[error("Backend not found: {0}. Available: {1}")]
+    NotFound(String, String),
 
     #[error("Backend '{0}' is not available. Please install: {1}")]
     NotAvailable(String, String),
@@ -46,6 +58,9 @@ pub enum BackendError {
 
     #[error("Backend timeout after {0} seconds")]
     Timeout(u64),
+
+    #[error("Backend produced no output for {0} seconds")]
+    IdleTimeout(u64),
 }
 
 /// Execution-related errors
@@ -57,6 +72,9 @@ pub enum ExecutionError {
     #[error("Process terminated with signal: {0}")]
     SignalTerminated(i32),
 
+    #[error("Task cancelled: {0}")]
+    Cancelled(String),
+
     #[error("Task failed with exit code: {0}")]
     TaskFailed(i32),
 
@@ -88,31 +106,78 @@ pub mod exit_codes {
     pub const BACKEND_NOT_FOUND: i32 = 3;
     pub const BACKEND_FAILED: i32 = 4;
     pub const TIMEOUT: i32 = 5;
+    pub const IDLE_TIMEOUT: i32 = 6;
     pub const SIGNAL_TERMINATED: i32 = 128;
 }
 
 /// Get exit code for an error
 pub fn get_exit_code(err: &anyhow::Error) -> i32 {
-    if let Some(e) = err.downcast_ref::<ConfigError>() {
-        match e {
-            ConfigError::InvalidParameter(_, _) => exit_codes::INVALID_ARGUMENT,
-            ConfigError::MissingParameter(_) => exit_codes::INVALID_ARGUMENT,
-            _ => exit_codes::GENERAL_ERROR,
-        }
+    if err.downcast_ref::<ConfigError>().is_some() {
+        // Every configuration problem comes from user input
+        exit_codes::INVALID_ARGUMENT
     } else if let Some(e) = err.downcast_ref::<BackendError>() {
         match e {
-            BackendError::NotFound(_) => exit_codes::BACKEND_NOT_FOUND,
+            BackendError::NotFound(_, _) => exit_codes::BACKEND_NOT_FOUND,
             BackendError::NotAvailable(_, _) => exit_codes::BACKEND_NOT_FOUND,
             BackendError::ExecutionFailed(_) => exit_codes::BACKEND_FAILED,
             BackendError::Timeout(_) => exit_codes::TIMEOUT,
+            BackendError::IdleTimeout(_) => exit_codes::IDLE_TIMEOUT,
         }
     } else if let Some(e) = err.downcast_ref::<ExecutionError>() {
         match e {
             ExecutionError::SignalTerminated(sig) => exit_codes::SIGNAL_TERMINATED + sig,
+            // The backend was stopped with SIGTERM
+            ExecutionError::Cancelled(_) => exit_codes::SIGNAL_TERMINATED + 15,
             ExecutionError::TaskFailed(code) => *code,
+            ExecutionError::CircularDependency(_) => exit_codes::INVALID_ARGUMENT,
             _ => exit_codes::GENERAL_ERROR,
         }
     } else {
         exit_codes::GENERAL_ERROR
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_exit_code() {
+        let cases = [
+            (
+                anyhow::Error::new(ConfigError::InvalidSessionId("x".into())),
+                exit_codes::INVALID_ARGUMENT,
+            ),
+            (
+                anyhow::Error::new(BackendError::NotFound("foo".into(), String::new())),
+                exit_codes::BACKEND_NOT_FOUND,
+            ),
+            (
+                anyhow::Error::new(BackendError::Timeout(10)),
+                exit_codes::TIMEOUT,
+            ),
+            (
+                anyhow::Error::new(BackendError::IdleTimeout(10)),
+                exit_codes::IDLE_TIMEOUT,
+            ),
+            (
+                anyhow::Error::new(ExecutionError::SignalTerminated(15)),
+                143,
+            ),
+            (
+                anyhow::Error::new(ExecutionError::Cancelled("task 'a' failed".into())),
+                143,
+            ),
+            (anyhow::anyhow!("something else"), exit_codes::GENERAL_ERROR),
+        ];
+        for (err, code) in cases {
+            assert_eq!(get_exit_code(&err), code, "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_get_exit_code_through_context() {
+        let err = anyhow::Error::new(BackendError::Timeout(10)).context("running task");
+        assert_eq!(get_exit_code(&err), exit_codes::TIMEOUT);
+    }
+}
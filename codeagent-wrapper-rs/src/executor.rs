@@ -1,21 +1,35 @@
 //! Task executor for running backend commands
 
-use anyhow::{Context, Result};
+use anyhow::Result;
+use serde::Serialize;
 use std::collections::HashMap;
+use std::future::Future;
 use std::process::Stdio;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::Command;
-use tokio::time::timeout;
 use tracing::{debug, info, warn};
 
-use crate::backend::Backend;
+use crate::backend::{Backend, backend_chain, select_backend};
 use crate::cli::Cli;
-use crate::config::{Config, ParallelConfig, TaskSpec};
+use crate::config::{Config, ParallelConfig, RetryPolicy, TaskSpec};
+use crate::diagnostics::{ErrorDetail, ErrorKind, STDERR_TAIL_LINES, StderrTail};
+use crate::errors::{BackendError, ConfigError, ExecutionError, exit_codes, get_exit_code};
+use crate::filter::NoiseFilter;
+use crate::handoff::touched_files;
+use crate::history::{self, HistoryEntry};
 use crate::logger::Logger;
-use crate::parser::JsonStreamParser;
-use crate::signal::setup_signal_handler;
+use crate::mock::{MockOptions, Recorder, task_record_path};
+use crate::parser::{AgentEvent, JsonStreamParser, MessageAccumulator, TokenUsage};
+use crate::progress::ProgressReporter;
+use crate::sessions::SessionRegistry;
+use crate::signal::{
+    self, CancelToken, setup_signal_handler, shutdown_requested, wait_for_exit,
+    wait_for_graceful_shutdown,
+};
+use crate::template;
+use crate::utils::truncate_chars;
 
 /// Essential environment variables for AI CLI backends
 const ESSENTIAL_ENV_VARS: &[&str] = &[
@@ -104,15 +118,251 @@ pub struct TaskResult {
     pub duration: Duration,
     /// Session ID (if returned by backend)
     pub session_id: Option<String>,
-    /// Parsed events
-    pub events: Vec<serde_json::Value>,
-    /// Stderr output
-    #[allow(dead_code)] // Reserved: stderr will be used for error reporting
-    pub stderr: String,
+    /// Final assistant message
+    pub message: Option<String>,
+    /// Normalized events
+    pub events: Vec<AgentEvent>,
+    /// Raw backend JSON events (only kept with `--raw-events`)
+    pub raw_events: Vec<serde_json::Value>,
+    /// Aggregated token usage
+    pub usage: Option<TokenUsage>,
     /// Files changed count
     pub files_changed: Option<usize>,
     /// Coverage percentage
     pub coverage: Option<f64>,
+    /// Task ID (parallel mode)
+    pub task_id: Option<String>,
+    /// Classified failure detail
+    pub error: Option<ErrorDetail>,
+    /// Why the task was skipped or cancelled without producing a result
+    pub skip_reason: Option<String>,
+    /// Every attempt made when retries are enabled
+    pub attempts: Vec<Attempt>,
+    /// Backend that produced this result
+    pub backend: Option<String>,
+    /// Model requested from that backend
+    pub model: Option<String>,
+    /// Backends of the fallback chain that were tried or skipped first
+    pub skipped_backends: Vec<SkippedBackend>,
+}
+
+/// A backend passed over while walking the fallback chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkippedBackend {
+    /// Backend name
+    pub backend: String,
+    /// Why the next backend was tried instead
+    pub reason: String,
+}
+
+/// One attempt at running a task
+#[derive(Debug, Clone, Default)]
+pub struct Attempt {
+    /// Attempt number, starting at 1
+    pub number: u32,
+    /// How long the attempt ran
+    pub duration: Duration,
+    /// Backend exit code
+    pub exit_code: i32,
+    /// Why the attempt failed
+    pub error: Option<ErrorDetail>,
+}
+
+impl TaskResult {
+    /// Result for a task that failed before the backend produced a result
+    pub fn failed(task_id: &str, error: impl Into<String>) -> Self {
+        Self {
+            exit_code: -1,
+            task_id: Some(task_id.to_string()),
+            error: Some(ErrorDetail::from_message(error)),
+            ..Default::default()
+        }
+    }
+
+    /// Result for a task whose execution returned an error, keeping the
+    /// partial result of a stopped run
+    ///
+    /// Typed errors carry their documented exit code; anything else gets -1.
+    pub fn from_error(task_id: &str, err: &anyhow::Error) -> Self {
+        let typed = err.downcast_ref::<ConfigError>().is_some()
+            || err.downcast_ref::<BackendError>().is_some()
+            || err.downcast_ref::<ExecutionError>().is_some();
+        let exit_code = if typed { get_exit_code(err) } else { -1 };
+        let partial = err
+            .downcast_ref::<PartialResult>()
+            .map(|partial| (*partial.0).clone())
+            .unwrap_or_default();
+        Self {
+            success: false,
+            exit_code,
+            task_id: Some(task_id.to_string()),
+            error: Some(ErrorDetail::from_error(err)),
+            ..partial
+        }
+    }
+
+    /// Result for a task that was never run (or was cancelled)
+    pub fn skipped(task_id: &str, reason: impl Into<String>) -> Self {
+        Self {
+            exit_code: -1,
+            task_id: Some(task_id.to_string()),
+            skip_reason: Some(reason.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Process exit code reported for this result (see `errors::exit_codes`)
+    pub fn process_exit_code(&self) -> i32 {
+        if self.success {
+            return exit_codes::SUCCESS;
+        }
+        match self.error.as_ref().map(|e| e.kind) {
+            Some(ErrorKind::Signal) => self.exit_code,
+            Some(kind) => kind.exit_code().unwrap_or(exit_codes::BACKEND_FAILED),
+            None => exit_codes::BACKEND_FAILED,
+        }
+    }
+
+    /// Status label used in output: "success", "failed" or "skipped"
+    pub fn status(&self) -> &'static str {
+        if self.success {
+            "success"
+        } else if self.skip_reason.is_some() {
+            "skipped"
+        } else {
+            "failed"
+        }
+    }
+}
+
+/// What a stopped run (timeout, idle timeout, signal, cancel) produced, or the
+/// backends a fallback chain passed over before its last backend failed
+///
+/// Attached beneath the typed error, so `get_exit_code` and error kinds still
+/// see the error while session, history and output keep the partial data.
+#[derive(Debug)]
+pub struct PartialResult(pub Box<TaskResult>);
+
+impl PartialResult {
+    /// Wrap `error` around the partial result of a run
+    pub fn attach<E>(error: E, result: TaskResult) -> anyhow::Error
+    where
+        E: std::fmt::Display + Send + Sync + 'static,
+    {
+        anyhow::Error::new(Self(Box::new(result))).context(error)
+    }
+}
+
+impl std::fmt::Display for PartialResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "partial result with {} events", self.0.events.len())
+    }
+}
+
+impl std::error::Error for PartialResult {}
+
+/// How parallel execution reacts to a failed task
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FailurePolicy {
+    /// Stop starting new tasks; let running tasks finish
+    #[default]
+    StopScheduling,
+    /// Keep running every task that does not depend on a failed task
+    KeepGoing,
+    /// Cancel running tasks and skip everything not yet finished
+    FailFast,
+}
+
+impl FailurePolicy {
+    /// Derive the policy from `--fail-fast` / `--keep-going`
+    pub fn from_cli(cli: &Cli) -> Self {
+        if cli.fail_fast {
+            Self::FailFast
+        } else if cli.keep_going {
+            Self::KeepGoing
+        } else {
+            Self::StopScheduling
+        }
+    }
+}
+
+/// Collects the normalized event stream of a run
+struct EventCollector {
+    keep_raw: bool,
+    events: Vec<AgentEvent>,
+    raw_events: Vec<serde_json::Value>,
+    session_id: Option<String>,
+    message: MessageAccumulator,
+    usage: Option<TokenUsage>,
+}
+
+impl EventCollector {
+    fn new(keep_raw: bool) -> Self {
+        Self {
+            keep_raw,
+            events: Vec::new(),
+            raw_events: Vec::new(),
+            session_id: None,
+            message: MessageAccumulator::default(),
+            usage: None,
+        }
+    }
+
+    /// Record one raw event together with its translation
+    fn push(&mut self, raw: serde_json::Value, translated: Vec<AgentEvent>) {
+        for event in &translated {
+            match event {
+                AgentEvent::SessionStarted { session_id } => {
+                    self.session_id.get_or_insert_with(|| session_id.clone());
+                }
+                AgentEvent::Usage {
+                    input_tokens,
+                    output_tokens,
+                } => {
+                    self.usage
+                        .get_or_insert_with(TokenUsage::default)
+                        .add(*input_tokens, *output_tokens);
+                }
+                _ => {}
+            }
+            self.message.push(event);
+        }
+
+        // Fall back to any session ID field the translator did not surface
+        if self.session_id.is_none() {
+            self.session_id = extract_session_id(&raw);
+        }
+
+        self.events.extend(translated);
+        if self.keep_raw {
+            self.raw_events.push(raw);
+        }
+    }
+
+    /// Error messages reported by the backend
+    fn error_messages(&self) -> Vec<String> {
+        self.events
+            .iter()
+            .filter_map(|event| match event {
+                AgentEvent::Error { message } => Some(message.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn into_result(self) -> TaskResult {
+        // Counted from edit and file-change tool calls; unknown when nothing was seen
+        let files_changed = (!self.events.is_empty()).then(|| touched_files(&self.events).len());
+        TaskResult {
+            session_id: self.session_id,
+            files_changed,
+            message: self.message.finish(),
+            events: self.events,
+            raw_events: self.raw_events,
+            usage: self.usage,
+            ..Default::default()
+        }
+    }
 }
 
 /// Task executor
@@ -133,13 +383,104 @@ impl TaskExecutor {
         })
     }
 
+    /// Run the task, retrying failed attempts according to the retry policy
+    ///
+    /// Without retries this is `run`. With retries, execution errors become
+    /// failed results so every attempt can be reported; configuration errors
+    /// are returned immediately and interrupted runs are never retried.
+    pub async fn run_with_retries(&self) -> Result<TaskResult> {
+        let policy = &self.config.retry;
+        if policy.retries == 0 {
+            return self.run().await;
+        }
+
+        let mut attempts = Vec::new();
+        loop {
+            let number = attempts.len() as u32 + 1;
+            let start = Instant::now();
+            let mut result = match self.run().await {
+                Ok(result) => result,
+                Err(e) => {
+                    let detail = ErrorDetail::from_error(&e);
+                    if matches!(
+                        detail.kind,
+                        ErrorKind::InvalidArgument | ErrorKind::BackendNotFound | ErrorKind::Signal
+                    ) {
+                        return Err(e);
+                    }
+                    let mut result = TaskResult::from_error("", &e);
+                    result.task_id = self.config.task_id.clone();
+                    result.duration = start.elapsed();
+                    result
+                }
+            };
+
+            attempts.push(Attempt {
+                number,
+                duration: result.duration,
+                exit_code: result.exit_code,
+                error: result.error.clone(),
+            });
+
+            let kind = result.error.as_ref().map(|e| e.kind);
+            let retry = !result.success
+                && number <= policy.retries
+                && kind != Some(ErrorKind::Signal)
+                && !self
+                    .config
+                    .cancel
+                    .as_ref()
+                    .is_some_and(CancelToken::is_cancelled)
+                && policy.matches(result.exit_code, kind)
+                && !signal::is_signal_received();
+            if !retry {
+                result.attempts = attempts;
+                return Ok(result);
+            }
+
+            let delay = policy.delay(number + 1);
+            let reason = result
+                .error
+                .as_ref()
+                .map(|e| e.message.clone())
+                .unwrap_or_else(|| format!("exit code {}", result.exit_code));
+            warn!(attempt = number, delay_secs = delay.as_secs(), reason = %reason, "Retrying task");
+            if !self.config.quiet {
+                let prefix = self.config.log_prefix();
+                eprintln!(
+                    "{}Retrying in {}s (attempt {}/{}): {}",
+                    prefix,
+                    delay.as_secs(),
+                    number + 1,
+                    policy.retries + 1,
+                    truncate_chars(&reason, 120)
+                );
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = shutdown_requested() => {
+                    result.attempts = attempts;
+                    return Ok(result);
+                }
+                _ = cancelled(self.config.cancel.as_ref()) => {
+                    result.attempts = attempts;
+                    return Ok(result);
+                }
+            }
+        }
+    }
+
     /// Run the task
     pub async fn run(&self) -> Result<TaskResult> {
         let start = Instant::now();
 
         // Build command arguments
         let task_content = self.get_target()?;
-        let use_stdin = should_use_stdin(&task_content);
+        let use_stdin = self
+            .backend
+            .prompt_on_stdin()
+            .unwrap_or_else(|| should_use_stdin(&task_content));
         let target = if use_stdin {
             "-".to_string()
         } else {
@@ -154,10 +495,12 @@ impl TaskExecutor {
         );
 
         // Build environment (use minimal env if requested for performance)
-        let process_env = build_process_env(self.config.minimal_env);
+        let mut process_env = build_process_env(self.config.minimal_env);
+        process_env.extend(self.config.env.clone());
 
         // Spawn process
-        let mut child = Command::new(self.backend.command())
+        let mut command = Command::new(self.backend.command());
+        command
             .args(&args)
             .current_dir(&self.config.work_dir)
             .env_clear()
@@ -165,12 +508,21 @@ impl TaskExecutor {
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .spawn()
-            .with_context(|| format!("Failed to spawn {}", self.backend.command()))?;
+            .kill_on_drop(true);
+        // Own process group, so timeouts and Ctrl-C reach everything the backend spawns
+        #[cfg(unix)]
+        command.process_group(0);
+        let mut child = command.spawn().map_err(|e| {
+            ExecutionError::SpawnFailed(format!("{}: {}", self.backend.command(), e))
+        })?;
+        let recorder = self.config.recorder.clone();
+        if let Some(ref recorder) = recorder {
+            recorder.start_attempt(self.backend.name());
+        }
 
         // Setup signal handler
         let child_id = child.id().unwrap_or(0);
-        let _signal_guard = setup_signal_handler(child_id);
+        let signal_guard = setup_signal_handler(child_id);
 
         // Write to stdin if using stdin mode
         if let Some(mut stdin) = child.stdin.take() {
@@ -185,55 +537,148 @@ impl TaskExecutor {
         let stdout_reader = BufReader::new(stdout);
         let mut parser = JsonStreamParser::new(stdout_reader);
 
-        // Collect stderr in background
+        // Collect stderr in background, forwarding it live with --backend-output
         let stderr = child.stderr.take().unwrap();
+        let forward = self.config.backend_output.then(|| {
+            let prefix = self
+                .config
+                .task_id
+                .clone()
+                .unwrap_or_else(|| self.backend.name().to_string());
+            (prefix, NoiseFilter::for_backend(self.backend.name()))
+        });
+        let activity = Arc::new(ActivityClock::new());
+        let stderr_activity = Arc::clone(&activity);
+        let stderr_recorder = recorder.clone();
         let stderr_handle = tokio::spawn(async move {
             let mut reader = BufReader::new(stderr);
-            let mut buf = String::new();
-            while reader.read_line(&mut buf).await.unwrap_or(0) > 0 {}
-            buf
+            let mut tail = StderrTail::new(STDERR_TAIL_LINES);
+            let mut line = Vec::new();
+            loop {
+                line.clear();
+                match reader.read_until(b'\n', &mut line).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {}
+                }
+                let text = String::from_utf8_lossy(&line);
+                if let Some((ref prefix, ref filter)) = forward
+                    && let Some(cleaned) = filter.clean(&text)
+                {
+                    eprintln!("[{}] {}", prefix, cleaned);
+                }
+                stderr_activity.touch();
+                if let Some(ref recorder) = stderr_recorder {
+                    recorder.stderr(&text);
+                }
+                tail.push(&text);
+            }
+            tail
         });
 
-        // Parse events with timeout
+        // Parse events until the backend finishes, times out or we are interrupted
         let timeout_duration = Duration::from_secs(self.config.timeout);
-        let mut events = Vec::new();
-        let mut session_id = None;
-
-        let parse_result = timeout(timeout_duration, async {
-            while let Some(event) = parser.next_event().await {
-                match event {
-                    Ok(value) => {
-                        // Extract session ID if present
-                        if let Some(id) = extract_session_id(&value) {
-                            session_id = Some(id);
+        let mut collector = EventCollector::new(self.config.raw_events);
+        let mut progress = ProgressReporter::new(&self.config);
+
+        let interrupted = tokio::select! {
+            _ = async {
+                while let Some(event) = parser.next_event().await {
+                    activity.touch();
+                    match event {
+                        Ok(value) => {
+                            if let Some(ref recorder) = recorder {
+                                recorder.event(&value);
+                            }
+                            let translated = self.backend.translate_event(&value);
+                            for event in &translated {
+                                progress.on_event(event);
+                            }
+                            collector.push(value, translated);
+                        }
+                        Err(e) => {
+                            warn!("Parse error: {}", e);
                         }
-                        events.push(value);
-                    }
-                    Err(e) => {
-                        warn!("Parse error: {}", e);
                     }
                 }
+            } => None,
+            _ = tokio::time::sleep(timeout_duration) => Some(Interruption::Timeout),
+            _ = activity.idle(self.config.idle_timeout) => Some(Interruption::Idle),
+            signal = shutdown_requested() => Some(Interruption::Signal(signal)),
+            _ = cancelled(self.config.cancel.as_ref()) => Some(Interruption::Cancelled),
+        };
+
+        // Wait for process, stopping its process group if interrupted
+        let status = match interrupted {
+            Some(reason) => {
+                warn!(
+                    reason = ?reason,
+                    grace_period = self.config.grace_period,
+                    "Stopping backend"
+                );
+                wait_for_graceful_shutdown(&mut child, self.config.grace_period).await?
             }
-        })
-        .await;
+            None => wait_for_exit(&mut child).await?,
+        };
+        drop(signal_guard);
+        let stderr_tail = stderr_handle
+            .await
+            .unwrap_or_else(|_| StderrTail::new(STDERR_TAIL_LINES));
 
-        if parse_result.is_err() {
-            warn!("Task timed out after {} seconds", self.config.timeout);
-            let _ = child.kill().await;
+        let duration = start.elapsed();
+        let exit_code = status.code().unwrap_or(-1);
+        progress.finish(status.success(), exit_code);
+        if let Some(ref recorder) = recorder {
+            recorder.finish(exit_code);
         }
 
-        // Wait for process
-        let status = child.wait().await?;
-        let stderr_output = stderr_handle.await.unwrap_or_default();
+        // Stopped runs fail with a typed error that carries what the backend produced
+        let partial = |collector: EventCollector| TaskResult {
+            exit_code,
+            duration,
+            backend: Some(self.backend.name().to_string()),
+            model: self.config.model.clone(),
+            ..collector.into_result()
+        };
+        match interrupted {
+            Some(Interruption::Timeout) => {
+                let err = BackendError::Timeout(self.config.timeout);
+                return Err(PartialResult::attach(err, partial(collector)));
+            }
+            Some(Interruption::Idle) => {
+                let err = BackendError::IdleTimeout(self.config.idle_timeout.unwrap_or_default());
+                return Err(PartialResult::attach(err, partial(collector)));
+            }
+            Some(Interruption::Signal(signal)) => {
+                let err = ExecutionError::SignalTerminated(signal);
+                return Err(PartialResult::attach(err, partial(collector)));
+            }
+            Some(Interruption::Cancelled) => {
+                let err = ExecutionError::Cancelled("stopped by the scheduler".to_string());
+                return Err(PartialResult::attach(err, partial(collector)));
+            }
+            None => {}
+        }
+        if let Some(signal) = terminating_signal(&status) {
+            warn!(signal, "Backend terminated by signal");
+            let err = ExecutionError::SignalTerminated(signal);
+            return Err(PartialResult::attach(err, partial(collector)));
+        }
 
-        let duration = start.elapsed();
-        let exit_code = status.code().unwrap_or(-1);
+        let error = (!status.success()).then(|| {
+            debug!(stderr = %stderr_tail, "Backend stderr tail");
+            ErrorDetail::from_failure(
+                &collector.error_messages(),
+                &stderr_tail,
+                self.backend.name(),
+                format!("{} exited with code {}", self.backend.command(), exit_code),
+            )
+        });
 
         info!(
             success = status.success(),
             exit_code = exit_code,
             duration_ms = duration.as_millis(),
-            events_count = events.len(),
+            events_count = collector.events.len(),
             "Task completed"
         );
 
@@ -241,25 +686,111 @@ impl TaskExecutor {
             success: status.success(),
             exit_code,
             duration,
-            session_id,
-            events,
-            stderr: stderr_output,
-            files_changed: None,
-            coverage: None,
+            backend: Some(self.backend.name().to_string()),
+            model: self.config.model.clone(),
+            error,
+            ..collector.into_result()
         })
     }
 
     /// Get the target argument (task or prompt file content)
     fn get_target(&self) -> Result<String> {
-        if let Some(ref prompt_file) = self.config.prompt_file {
-            std::fs::read_to_string(prompt_file)
-                .with_context(|| format!("Failed to read prompt file: {}", prompt_file.display()))
+        let task = if let Some(ref prompt_file) = self.config.prompt_file {
+            read_prompt_file(prompt_file)?
         } else {
-            Ok(self.config.task.clone())
+            self.config.task.clone()
+        };
+
+        let task = match self.config.handoff_context {
+            Some(ref context) => format!("{}\n\n{}", context, task),
+            None => task,
+        };
+        Ok(match self.config.prompt_prefix {
+            Some(ref prefix) if !prefix.trim().is_empty() => format!("{}\n\n{}", prefix, task),
+            _ => task,
+        })
+    }
+}
+
+/// Read a prompt file, mapping I/O failures to configuration errors
+fn read_prompt_file(path: &std::path::Path) -> Result<String, ConfigError> {
+    std::fs::read_to_string(path).map_err(|e| {
+        let path = path.display().to_string();
+        match e.kind() {
+            std::io::ErrorKind::NotFound => ConfigError::FileNotFound(path),
+            std::io::ErrorKind::PermissionDenied => ConfigError::PermissionDenied(path),
+            _ => ConfigError::InvalidFilePath(format!("{}: {}", path, e)),
+        }
+    })
+}
+
+/// Why a running backend was stopped early
+#[derive(Debug, Clone, Copy)]
+enum Interruption {
+    Timeout,
+    Idle,
+    /// A termination signal with this number was received
+    Signal(i32),
+    Cancelled,
+}
+
+/// Resolve once the token is cancelled (never, without a token)
+async fn cancelled(token: Option<&CancelToken>) {
+    match token {
+        Some(token) => token.cancelled().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Time of the last stdout event or stderr line from a backend
+struct ActivityClock(std::sync::Mutex<Instant>);
+
+impl ActivityClock {
+    fn new() -> Self {
+        Self(std::sync::Mutex::new(Instant::now()))
+    }
+
+    /// Record activity now
+    fn touch(&self) {
+        if let Ok(mut last) = self.0.lock() {
+            *last = Instant::now();
+        }
+    }
+
+    /// Resolve once no activity has been seen for `idle_timeout` seconds
+    /// (never, when unset)
+    async fn idle(&self, idle_timeout: Option<u64>) {
+        let Some(secs) = idle_timeout else {
+            return std::future::pending().await;
+        };
+        let idle = Duration::from_secs(secs);
+        loop {
+            let last = self
+                .0
+                .lock()
+                .map(|last| *last)
+                .unwrap_or_else(|_| Instant::now());
+            let deadline = last + idle;
+            if Instant::now() >= deadline {
+                return;
+            }
+            tokio::time::sleep_until(deadline.into()).await;
         }
     }
 }
 
+/// Signal that terminated the process, if any
+#[cfg(unix)]
+fn terminating_signal(status: &std::process::ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn terminating_signal(_status: &std::process::ExitStatus) -> Option<i32> {
+    None
+}
+
 /// Determine if task should use stdin for input
 /// Use stdin for long tasks or tasks with special characters that may cause shell issues
 fn should_use_stdin(task: &str) -> bool {
@@ -279,114 +810,537 @@ fn should_use_stdin(task: &str) -> bool {
 
 /// Run tasks in parallel
 pub async fn run_parallel_tasks(cli: &Cli, config: ParallelConfig) -> Result<Vec<TaskResult>> {
-    use tokio::sync::mpsc;
+    config.validate()?;
 
     let max_workers = cli
         .max_parallel_workers
         .unwrap_or_else(crate::config::get_default_max_parallel_workers);
+    let policy = FailurePolicy::from_cli(cli);
+
+    let mut tasks = config.tasks;
+    if cli.include_dependency_outputs {
+        for task in &mut tasks {
+            task.include_dependency_outputs = true;
+        }
+    }
 
     debug!(
-        task_count = config.tasks.len(),
+        task_count = tasks.len(),
         max_workers = max_workers,
+        policy = ?policy,
         "Starting parallel execution"
     );
 
-    // Build dependency graph
+    let cli = cli.clone();
+    let results = schedule_tasks(tasks, max_workers, policy, move |spec, cancel| {
+        let cli = cli.clone();
+        async move {
+            let task_id = spec.id.clone();
+            match run_single_task(&cli, spec, cancel).await {
+                Ok(mut result) => {
+                    result.task_id = Some(task_id);
+                    result
+                }
+                Err(e) => TaskResult::from_error(&task_id, &e),
+            }
+        }
+    })
+    .await?;
+
+    // Executed tasks are recorded by `execute`; skipped ones never got there
+    let skipped: Vec<HistoryEntry> = results
+        .iter()
+        .filter(|r| r.skip_reason.is_some())
+        .map(|r| HistoryEntry::new(r, None))
+        .collect();
+    history::record(&skipped);
+    Ok(results)
+}
+
+/// Schedule tasks over the dependency graph, propagating failures to dependents
+async fn schedule_tasks<F, Fut>(
+    tasks: Vec<TaskSpec>,
+    max_workers: usize,
+    policy: FailurePolicy,
+    run: F,
+) -> Result<Vec<TaskResult>>
+where
+    F: Fn(TaskSpec, CancelToken) -> Fut,
+    Fut: Future<Output = TaskResult> + Send + 'static,
+{
+    use tokio::task::JoinSet;
+
+    let max_workers = max_workers.max(1);
     let mut results: HashMap<String, TaskResult> = HashMap::new();
-    let mut pending: Vec<TaskSpec> = config.tasks.clone();
-    let (tx, mut rx) = mpsc::channel::<(String, TaskResult)>(max_workers);
+    let mut pending: Vec<TaskSpec> = tasks.clone();
+    let mut running: HashMap<tokio::task::Id, String> = HashMap::new();
+    let mut join_set: JoinSet<TaskResult> = JoinSet::new();
+    // ID of the failed task that stopped further scheduling
+    let mut halted: Option<String> = None;
+    // Stops running tasks gracefully under --fail-fast
+    let cancel = CancelToken::new();
+
+    loop {
+        // Skip tasks whose dependencies failed or were skipped
+        while let Some(idx) = pending
+            .iter()
+            .position(|task| blocked_reason(task, &results).is_some())
+        {
+            let task = pending.remove(idx);
+            let reason = blocked_reason(&task, &results).unwrap_or_default();
+            info!(task_id = %task.id, reason = %reason, "Skipping task");
+            results.insert(task.id.clone(), TaskResult::skipped(&task.id, reason));
+        }
+
+        if signal::is_signal_received() {
+            for task in pending.drain(..) {
+                results.insert(
+                    task.id.clone(),
+                    TaskResult::skipped(&task.id, "not started: interrupted"),
+                );
+            }
+        }
 
-    let mut running = 0;
+        if let Some(ref failed_id) = halted {
+            for task in pending.drain(..) {
+                let reason = format!("not started: task '{}' failed", failed_id);
+                results.insert(task.id.clone(), TaskResult::skipped(&task.id, reason));
+            }
+        }
 
-    while !pending.is_empty() || running > 0 {
         // Start tasks with satisfied dependencies
-        while running < max_workers && !pending.is_empty() {
+        while running.len() < max_workers {
             let ready_idx = pending.iter().position(|task| {
                 task.dependencies
                     .iter()
                     .all(|dep| results.contains_key(dep))
             });
+            let Some(idx) = ready_idx else { break };
 
-            if let Some(idx) = ready_idx {
-                let task = pending.remove(idx);
-                let task_id = task.id.clone();
-                let tx = tx.clone();
-                let cli = cli.clone();
-
-                tokio::spawn(async move {
-                    let result = run_single_task(&cli, task).await;
-                    let _ = tx.send((task_id, result.unwrap_or_default())).await;
-                });
+            let mut task = pending.remove(idx);
+            inject_dependency_outputs(&mut task, &results);
+            let task_id = task.id.clone();
+            let handle = join_set.spawn(run(task, cancel.clone()));
+            running.insert(handle.id(), task_id);
+        }
 
-                running += 1;
-            } else if running == 0 {
-                // No tasks can run and none are running - circular dependency
-                return Err(anyhow::anyhow!(
-                    "Circular dependency detected in tasks: {:?}",
-                    pending.iter().map(|t| &t.id).collect::<Vec<_>>()
-                ));
-            } else {
+        if running.is_empty() {
+            if pending.is_empty() {
                 break;
             }
+            // Unreachable after `ParallelConfig::validate`, kept as a safety net
+            let ids: Vec<&str> = pending.iter().map(|t| t.id.as_str()).collect();
+            return Err(ExecutionError::CircularDependency(ids.join(", ")).into());
         }
 
         // Wait for a task to complete
-        if running > 0
-            && let Some((task_id, result)) = rx.recv().await
+        let Some(joined) = join_set.join_next_with_id().await else {
+            break;
+        };
+        let (task_id, mut result) = match joined {
+            Ok((id, result)) => (running.remove(&id).unwrap_or_default(), result),
+            Err(e) => {
+                let task_id = running.remove(&e.id()).unwrap_or_default();
+                let result = TaskResult::failed(&task_id, format!("task panicked: {}", e));
+                (task_id, result)
+            }
+        };
+
+        // Tasks stopped by --fail-fast report what they did, but count as cancelled;
+        // siblings that failed on their own still count as failed
+        let stopped = result.error.as_ref().map(|e| e.kind) == Some(ErrorKind::Signal)
+            && !signal::is_signal_received();
+        if !result.success
+            && stopped
+            && cancel.is_cancelled()
+            && let Some(ref failed_id) = halted
         {
-            results.insert(task_id, result);
-            running -= 1;
+            let reason = format!("cancelled: task '{}' failed (--fail-fast)", failed_id);
+            result.skip_reason = Some(reason);
+        }
+
+        let failed = result.status() == "failed";
+        results.insert(task_id.clone(), result);
+
+        if !failed {
+            continue;
+        }
+
+        warn!(task_id = %task_id, policy = ?policy, "Parallel task failed");
+        match policy {
+            FailurePolicy::KeepGoing => {}
+            FailurePolicy::StopScheduling => {
+                halted.get_or_insert(task_id);
+            }
+            FailurePolicy::FailFast => {
+                // Running tasks get SIGTERM and the grace period, then SIGKILL
+                if halted.is_none() {
+                    cancel.cancel();
+                }
+                halted.get_or_insert(task_id);
+            }
         }
     }
 
     // Return results in original order
-    Ok(config
-        .tasks
-        .iter()
-        .filter_map(|t| results.remove(&t.id))
-        .collect())
+    Ok(tasks.iter().filter_map(|t| results.remove(&t.id)).collect())
+}
+
+/// Resolve `{{dep.field}}` placeholders and optionally append dependency outputs
+///
+/// A prompt file is read here and becomes the task content, so its placeholders
+/// are resolved too; if it cannot be read, it is left for `get_target` to report.
+fn inject_dependency_outputs(task: &mut TaskSpec, results: &HashMap<String, TaskResult>) {
+    if task.dependencies.is_empty() {
+        return;
+    }
+
+    if let Some(ref path) = task.prompt_file {
+        match read_prompt_file(path.as_ref()) {
+            Ok(content) => {
+                task.task = content;
+                task.prompt_file = None;
+            }
+            Err(_) => return,
+        }
+    }
+    task.task = template::render(&task.task, results);
+    if task.include_dependency_outputs {
+        let context = template::dependency_context(&task.dependencies, results);
+        task.task = format!("{}\n\n{}", task.task, context);
+    }
+}
+
+/// Reason a task can no longer run because one of its dependencies did not succeed
+fn blocked_reason(task: &TaskSpec, results: &HashMap<String, TaskResult>) -> Option<String> {
+    task.dependencies.iter().find_map(|dep| {
+        let result = results.get(dep)?;
+        if result.success {
+            None
+        } else if result.skip_reason.is_some() {
+            Some(format!("dependency '{}' was skipped", dep))
+        } else {
+            Some(format!("dependency '{}' failed", dep))
+        }
+    })
 }
 
 /// Run a single task from parallel config
-async fn run_single_task(cli: &Cli, spec: TaskSpec) -> Result<TaskResult> {
-    let config = Config {
+async fn run_single_task(cli: &Cli, spec: TaskSpec, cancel: CancelToken) -> Result<TaskResult> {
+    let retry = RetryPolicy::for_task(&spec, cli);
+    let record = cli
+        .record
+        .as_ref()
+        .map(|path| task_record_path(path.as_ref(), &spec.id));
+    let session = match spec.session_id {
+        Some(ref reference) => SessionRegistry::open_default().resolve(reference)?,
+        None => None,
+    };
+    let workdir_given = spec.work_dir.is_some();
+    let mut config = Config {
         mode: if spec.session_id.is_some() {
             "resume"
         } else {
             "new"
         }
         .to_string(),
+        task_id: Some(spec.id),
         task: spec.task,
         session_id: spec.session_id,
         work_dir: spec
             .work_dir
             .map(Into::into)
-            .unwrap_or_else(|| std::env::current_dir().unwrap()),
+            .unwrap_or_else(|| std::env::current_dir().unwrap_or_default()),
         model: spec.model.or_else(|| cli.model.clone()),
         backend: spec.backend.or_else(|| cli.backend.clone()),
         agent: spec.agent.or_else(|| cli.agent.clone()),
         prompt_file: spec.prompt_file.map(Into::into),
-        timeout: cli.timeout,
+        timeout: spec.timeout.unwrap_or(cli.timeout),
+        retry,
+        idle_timeout: spec.idle_timeout.or(cli.idle_timeout),
+        grace_period: cli.grace_period,
         skip_permissions: spec.skip_permissions || cli.skip_permissions,
         reasoning_effort: spec
             .reasoning_effort
             .or_else(|| cli.reasoning_effort.clone()),
         minimal_env: spec.minimal_env || cli.minimal_env,
+        strict: cli.strict,
         quiet: cli.quiet,
-        backend_output: cli.backend_output,
+        raw_events: cli.raw_events,
+        record,
+        backend_output: cli.backend_output || cli.debug,
         debug: cli.debug,
+        cancel: Some(cancel),
+        mock: MockOptions::from_cli(cli).with_file(spec.mock_file.as_deref()),
+        ..Default::default()
+    };
+    if let Some(ref record) = session {
+        config.session_id = Some(record.id.clone());
+        config.apply_session_record(record, workdir_given)?;
+    }
+    config.resolve_agent().await?;
+    execute(config).await
+}
+
+/// Run a task and record it in the session registry and run history
+///
+/// Every backend that ran gets its own history entry; a task that failed
+/// before any backend ran gets one for the failure.
+pub async fn execute(mut config: Config) -> Result<TaskResult> {
+    let start = Instant::now();
+    let mut attempts = Vec::new();
+    // One recording per task, so retries and fallbacks do not overwrite each other
+    let outcome = match config.record.as_deref().map(Recorder::create).transpose() {
+        Ok(recorder) => {
+            config.recorder = recorder.map(Arc::new);
+            execute_chain(&config, &mut attempts).await
+        }
+        Err(e) => Err(e),
+    };
+    if attempts.is_empty()
+        && let Err(ref e) = outcome
+    {
+        let task_id = config.task_id.as_deref().unwrap_or_default();
+        let mut failed = TaskResult::from_error(task_id, e);
+        failed.duration = start.elapsed();
+        attempts.push(HistoryEntry::new(&failed, Some(&config)));
+    }
+    history::record(&attempts);
+
+    // A stopped run keeps its session, so it can be resumed
+    let recorded = match outcome {
+        Ok(ref result) => SessionRegistry::open_default().record_run(&config, result),
+        Err(ref e) if e.is::<PartialResult>() => {
+            let task_id = config.task_id.as_deref().unwrap_or_default();
+            SessionRegistry::open_default().record_run(&config, &TaskResult::from_error(task_id, e))
+        }
+        Err(_) => Ok(()),
+    };
+    if let Err(e) = recorded {
+        warn!(error = %e, "Failed to record session");
+    }
+    outcome
+}
+
+/// Run one backend, adding a history entry for the attempt
+async fn run_attempt(
+    backend: Arc<dyn Backend>,
+    config: &Config,
+    attempts: &mut Vec<HistoryEntry>,
+) -> Result<TaskResult> {
+    let start = Instant::now();
+    let name = backend.name().to_string();
+    let outcome = match TaskExecutor::new(backend, config) {
+        Ok(executor) => executor.run_with_retries().await,
+        Err(e) => Err(e),
+    };
+
+    let entry = match outcome {
+        Ok(ref result) => HistoryEntry::new(result, Some(config)),
+        Err(ref e) => {
+            let task_id = config.task_id.as_deref().unwrap_or_default();
+            let mut failed = TaskResult::from_error(task_id, e);
+            if failed.duration.is_zero() {
+                failed.duration = start.elapsed();
+            }
+            failed.backend = Some(name);
+            HistoryEntry::new(&failed, Some(config))
+        }
     };
+    attempts.push(entry);
+    outcome
+}
+
+/// Run a task, walking the backend fallback chain
+///
+/// A backend is passed over when it is not installed, fails with an auth,
+/// rate-limit or quota error before calling any tool, or exits without
+/// producing any event. The last
+/// backend's outcome is returned as is.
+async fn execute_chain(config: &Config, attempts: &mut Vec<HistoryEntry>) -> Result<TaskResult> {
+    let mut chain = backend_chain(config.backend.as_deref(), &config.fallback);
+    // A session only exists on the backend that created it
+    if config.session_id.is_some() {
+        chain.truncate(1);
+    }
+    if chain.is_empty() {
+        let backend = select_backend(None)?;
+        config.check_backend_support(backend.as_ref())?;
+        return run_attempt(backend, config, attempts).await;
+    }
+
+    // Reject unknown names (and, under --strict, unsupported options) before running anything
+    let backends = chain
+        .iter()
+        .enumerate()
+        .map(|(idx, choice)| {
+            let backend = select_backend(Some(&choice.name))?;
+            let mut attempt_config = config.clone();
+            attempt_config.backend = Some(backend.name().to_string());
+            // `--model` belongs to the first backend; later ones use their pinned model or default
+            attempt_config.model = match idx {
+                0 => choice.model.clone().or_else(|| config.model.clone()),
+                _ => choice.model.clone(),
+            };
+            if config.strict {
+                attempt_config.check_backend_support(backend.as_ref())?;
+            }
+            Ok((backend, attempt_config))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // There is no cross-backend model mapping: a later backend without a pinned
+    // model runs its default, which the skip reason says
+    let model_notes: Vec<Option<String>> = backends
+        .iter()
+        .map(
+            |(backend, attempt_config)| match (&config.model, &attempt_config.model) {
+                (Some(model), None) => Some(format!(
+                    "{} runs its default model instead of '{}'",
+                    backend.name(),
+                    model
+                )),
+                _ => None,
+            },
+        )
+        .collect();
+
+    let mut skipped: Vec<SkippedBackend> = Vec::new();
+    let last = backends.len() - 1;
+    for (idx, (backend, attempt_config)) in backends.into_iter().enumerate() {
+        let name = backend.name().to_string();
+
+        let outcome = if backend.is_available() {
+            // Only warn about the backends that actually run
+            attempt_config.check_backend_support(backend.as_ref())?;
+            run_attempt(backend, &attempt_config, attempts).await
+        } else {
+            Err(BackendError::NotAvailable(name.clone(), backend.install_hint()).into())
+        };
+
+        let reason = if idx == last {
+            None
+        } else {
+            fallback_reason(&outcome).map(|reason| match model_notes[idx + 1] {
+                Some(ref note) => format!("{}; {}", reason, note),
+                None => reason,
+            })
+        };
+        match reason {
+            Some(reason) => {
+                warn!(backend = %name, reason = %reason, "Falling back to next backend");
+                if !config.quiet {
+                    let prefix = config.log_prefix();
+                    eprintln!("{}Skipping {}: {}", prefix, name, reason);
+                }
+                skipped.push(SkippedBackend {
+                    backend: name,
+                    reason,
+                });
+            }
+            None => {
+                return match outcome {
+                    Ok(mut result) => {
+                        result.backend = Some(name);
+                        result.skipped_backends = skipped;
+                        Ok(result)
+                    }
+                    Err(e) if skipped.is_empty() => Err(e),
+                    Err(e) => Err(attach_skipped(e, name, skipped)),
+                };
+            }
+        }
+    }
+
+    unreachable!("the last backend of a non-empty chain always returns")
+}
+
+/// Attach the backends passed over to the error of the last one, so the
+/// output still says why they were skipped
+fn attach_skipped(
+    mut err: anyhow::Error,
+    backend: String,
+    skipped: Vec<SkippedBackend>,
+) -> anyhow::Error {
+    if let Some(partial) = err.downcast_mut::<PartialResult>() {
+        partial.0.backend = Some(backend);
+        partial.0.skipped_backends = skipped;
+        return err;
+    }
+
+    let result = TaskResult {
+        backend: Some(backend),
+        skipped_backends: skipped,
+        ..Default::default()
+    };
+    match err.downcast::<BackendError>() {
+        Ok(e) => PartialResult::attach(e, result),
+        Err(err) => match err.downcast::<ExecutionError>() {
+            Ok(e) => PartialResult::attach(e, result),
+            Err(err) => err,
+        },
+    }
+}
+
+/// Why a backend's outcome warrants trying the next backend, if it does
+fn fallback_reason(outcome: &Result<TaskResult>) -> Option<String> {
+    match outcome {
+        Ok(result) if result.success => None,
+        Ok(result) => {
+            if let Some(ref error) = result.error {
+                match error.kind {
+                    // Another backend would start over on a half-edited workdir
+                    ErrorKind::AuthMissing | ErrorKind::RateLimited | ErrorKind::QuotaExceeded
+                        if did_real_work(result) =>
+                    {
+                        return None;
+                    }
+                    ErrorKind::AuthMissing | ErrorKind::RateLimited | ErrorKind::QuotaExceeded => {
+                        return Some(format!("{}: {}", error.kind.as_str(), error.message));
+                    }
+                    // Stopped by us, not a problem of the backend
+                    ErrorKind::Timeout | ErrorKind::IdleTimeout | ErrorKind::Signal => return None,
+                    _ => {}
+                }
+            }
+            result.events.is_empty().then(|| {
+                format!(
+                    "exited with code {} before producing any event",
+                    result.exit_code
+                )
+            })
+        }
+        Err(e) => match (
+            e.downcast_ref::<BackendError>(),
+            e.downcast_ref::<ExecutionError>(),
+        ) {
+            (Some(BackendError::NotAvailable(..)), _) => Some("not found on PATH".to_string()),
+            (_, Some(ExecutionError::SpawnFailed(msg))) => {
+                Some(format!("failed to start: {}", msg))
+            }
+            _ => None,
+        },
+    }
+}
 
-    let backend = crate::backend::select_backend(config.backend.as_deref())?;
-    let executor = TaskExecutor::new(backend, &config)?;
-    executor.run().await
+/// Whether a run called tools or changed files before it stopped
+fn did_real_work(result: &TaskResult) -> bool {
+    result.files_changed.is_some_and(|n| n > 0)
+        || result.events.iter().any(|event| {
+            matches!(
+                event,
+                AgentEvent::ToolCall { .. } | AgentEvent::ToolResult { .. }
+            )
+        })
 }
 
-/// Extract session ID from a JSON event
+/// Extract session ID from a raw JSON event
 fn extract_session_id(value: &serde_json::Value) -> Option<String> {
     value
         .get("session_id")
         .or_else(|| value.get("sessionId"))
+        .or_else(|| value.get("sessionID"))
+        .or_else(|| value.get("thread_id"))
         .and_then(|v| v.as_str())
         .map(|s| s.to_string())
 }
@@ -406,4 +1360,337 @@ mod tests {
         let value = serde_json::json!({"other": "data"});
         assert_eq!(extract_session_id(&value), None);
     }
+
+    fn spec(id: &str, task: &str, deps: &[&str]) -> TaskSpec {
+        TaskSpec {
+            id: id.to_string(),
+            task: task.to_string(),
+            dependencies: deps.iter().map(|d| d.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Fake runner: tasks whose content is "fail" fail, "slow" sleeps first
+    async fn fake_run(spec: TaskSpec, cancel: CancelToken) -> TaskResult {
+        if spec.task == "slow" {
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(5)) => {}
+                _ = cancel.cancelled() => {
+                    let err = ExecutionError::Cancelled("stopped by the scheduler".to_string());
+                    return TaskResult {
+                        message: Some("partial".to_string()),
+                        ..TaskResult::from_error(&spec.id, &err.into())
+                    };
+                }
+            }
+        }
+        TaskResult {
+            success: spec.task != "fail",
+            exit_code: if spec.task == "fail" { 1 } else { 0 },
+            task_id: Some(spec.id),
+            ..Default::default()
+        }
+    }
+
+    fn statuses(results: &[TaskResult]) -> Vec<(&str, &str)> {
+        results
+            .iter()
+            .map(|r| (r.task_id.as_deref().unwrap_or(""), r.status()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_dependents_of_failed_task_are_skipped() {
+        let tasks = vec![
+            spec("a", "fail", &[]),
+            spec("b", "ok", &["a"]),
+            spec("c", "ok", &["b"]),
+            spec("d", "ok", &[]),
+        ];
+        let results = schedule_tasks(tasks, 4, FailurePolicy::KeepGoing, fake_run)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            statuses(&results),
+            vec![
+                ("a", "failed"),
+                ("b", "skipped"),
+                ("c", "skipped"),
+                ("d", "success")
+            ]
+        );
+        assert_eq!(
+            results[1].skip_reason.as_deref(),
+            Some("dependency 'a' failed")
+        );
+        assert_eq!(
+            results[2].skip_reason.as_deref(),
+            Some("dependency 'b' was skipped")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dependency_outputs_reach_downstream_prompt() {
+        let mut implement = spec("implement", "Implement: {{analyze.message}}", &["analyze"]);
+        implement.include_dependency_outputs = true;
+        let tasks = vec![spec("analyze", "ok", &[]), implement];
+
+        let results = schedule_tasks(tasks, 2, FailurePolicy::KeepGoing, |spec, _| async move {
+            let output = if spec.id == "analyze" {
+                "Use a token bucket.".to_string()
+            } else {
+                spec.task
+            };
+            TaskResult {
+                success: true,
+                task_id: Some(spec.id),
+                message: Some(output),
+                ..Default::default()
+            }
+        })
+        .await
+        .unwrap();
+
+        let prompt = results[1].message.clone().unwrap_or_default();
+        assert!(prompt.starts_with("Implement: Use a token bucket."));
+        assert!(prompt.contains("### analyze"));
+    }
+
+    #[tokio::test]
+    async fn test_dependency_outputs_reach_prompt_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let prompt_file = dir.path().join("implement.md");
+        std::fs::write(&prompt_file, "Implement: {{analyze.message}}").unwrap();
+        let mut implement = spec("implement", "", &["analyze"]);
+        implement.prompt_file = Some(prompt_file.display().to_string());
+        implement.include_dependency_outputs = true;
+        let tasks = vec![spec("analyze", "ok", &[]), implement];
+
+        let results = schedule_tasks(tasks, 2, FailurePolicy::KeepGoing, |spec, _| async move {
+            let output = if spec.id == "analyze" {
+                "Use a token bucket.".to_string()
+            } else {
+                assert!(spec.prompt_file.is_none());
+                spec.task
+            };
+            TaskResult {
+                success: true,
+                task_id: Some(spec.id),
+                message: Some(output),
+                ..Default::default()
+            }
+        })
+        .await
+        .unwrap();
+
+        let prompt = results[1].message.clone().unwrap_or_default();
+        assert!(prompt.starts_with("Implement: Use a token bucket."));
+        assert!(prompt.contains("### analyze"));
+    }
+
+    #[tokio::test]
+    async fn test_stop_scheduling_skips_unstarted_tasks() {
+        let tasks = vec![spec("a", "fail", &[]), spec("b", "ok", &[])];
+        let results = schedule_tasks(tasks, 1, FailurePolicy::StopScheduling, fake_run)
+            .await
+            .unwrap();
+
+        assert_eq!(statuses(&results), vec![("a", "failed"), ("b", "skipped")]);
+        assert_eq!(
+            results[1].skip_reason.as_deref(),
+            Some("not started: task 'a' failed")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fail_fast_cancels_running_tasks() {
+        let tasks = vec![
+            spec("slow", "slow", &[]),
+            spec("a", "fail", &[]),
+            spec("later", "ok", &["slow"]),
+        ];
+        let start = Instant::now();
+        let results = schedule_tasks(tasks, 4, FailurePolicy::FailFast, fake_run)
+            .await
+            .unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(
+            statuses(&results),
+            vec![("slow", "skipped"), ("a", "failed"), ("later", "skipped")]
+        );
+        assert_eq!(
+            results[0].skip_reason.as_deref(),
+            Some("cancelled: task 'a' failed (--fail-fast)")
+        );
+        // The cancelled task stopped gracefully and kept its partial result
+        assert_eq!(results[0].message.as_deref(), Some("partial"));
+    }
+
+    #[tokio::test]
+    async fn test_fail_fast_keeps_real_sibling_failures() {
+        let tasks = vec![spec("a", "fail", &[]), spec("b", "own-timeout", &[])];
+        let results = schedule_tasks(
+            tasks,
+            4,
+            FailurePolicy::FailFast,
+            |spec, cancel| async move {
+                if spec.task == "own-timeout" {
+                    // Fails on its own once the run is already being cancelled
+                    cancel.cancelled().await;
+                    let err = BackendError::Timeout(1);
+                    return TaskResult::from_error(&spec.id, &err.into());
+                }
+                fake_run(spec, cancel).await
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(statuses(&results), vec![("a", "failed"), ("b", "failed")]);
+        assert_eq!(results[1].process_exit_code(), exit_codes::TIMEOUT);
+    }
+
+    #[test]
+    fn test_partial_result_rides_with_typed_error() {
+        let partial = TaskResult {
+            session_id: Some("s1".to_string()),
+            duration: Duration::from_secs(3),
+            ..Default::default()
+        };
+        let err = PartialResult::attach(BackendError::Timeout(3), partial);
+        assert!(matches!(
+            err.downcast_ref::<BackendError>(),
+            Some(BackendError::Timeout(3))
+        ));
+        assert_eq!(get_exit_code(&err), exit_codes::TIMEOUT);
+
+        let result = TaskResult::from_error("a", &err);
+        assert_eq!(result.session_id.as_deref(), Some("s1"));
+        assert_eq!(result.duration, Duration::from_secs(3));
+        let error = result.error.unwrap();
+        assert_eq!(error.kind, ErrorKind::Timeout);
+        assert_eq!(error.message, "Backend timeout after 3 seconds");
+        assert!(error.causes.is_empty());
+    }
+
+    #[test]
+    fn test_fallback_reason() {
+        let ok = TaskResult {
+            success: true,
+            ..Default::default()
+        };
+        assert_eq!(fallback_reason(&Ok(ok)), None);
+
+        let silent = TaskResult {
+            exit_code: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            fallback_reason(&Ok(silent)).as_deref(),
+            Some("exited with code 1 before producing any event")
+        );
+
+        let rate_limited = TaskResult {
+            exit_code: 1,
+            events: vec![AgentEvent::Error {
+                message: "429".to_string(),
+            }],
+            error: Some(ErrorDetail::from_message("429 Too Many Requests")),
+            ..Default::default()
+        };
+        assert!(
+            fallback_reason(&Ok(rate_limited))
+                .unwrap()
+                .starts_with("rate-limited")
+        );
+
+        // A rate limit hit after tool calls is not retried elsewhere
+        let rate_limited_after_edit = TaskResult {
+            exit_code: 1,
+            events: vec![
+                AgentEvent::ToolCall {
+                    id: None,
+                    name: "edit".to_string(),
+                    input: serde_json::json!({"path": "src/lib.rs"}),
+                },
+                AgentEvent::Error {
+                    message: "429".to_string(),
+                },
+            ],
+            error: Some(ErrorDetail::from_message("429 Too Many Requests")),
+            ..Default::default()
+        };
+        assert_eq!(fallback_reason(&Ok(rate_limited_after_edit)), None);
+
+        // Failures after real work are not retried elsewhere
+        let worked = TaskResult {
+            exit_code: 1,
+            events: vec![AgentEvent::AssistantText {
+                text: "Tests still fail".to_string(),
+                delta: false,
+            }],
+            ..Default::default()
+        };
+        assert_eq!(fallback_reason(&Ok(worked)), None);
+
+        // A timed-out backend is not swapped for another, even before its first event
+        let timeout = TaskResult {
+            exit_code: 5,
+            error: Some(ErrorDetail::new(
+                ErrorKind::Timeout,
+                "timeout after 5 seconds",
+            )),
+            ..Default::default()
+        };
+        assert_eq!(fallback_reason(&Ok(timeout)), None);
+    }
+
+    #[test]
+    fn test_event_collector() {
+        let backend = crate::backend::CodexBackend;
+        let raw = [
+            serde_json::json!({"type": "thread.started", "thread_id": "t1"}),
+            serde_json::json!({"type": "item.completed", "item": {"type": "agent_message", "text": "Hi"}}),
+            serde_json::json!({"type": "item.completed", "item": {"type": "file_change",
+                "changes": [{"path": "src/a.rs", "kind": "update"}, {"path": "src/b.rs", "kind": "add"}]}}),
+            serde_json::json!({"type": "turn.completed", "usage": {"input_tokens": 5, "output_tokens": 2}}),
+            serde_json::json!({"type": "turn.completed", "usage": {"input_tokens": 1, "output_tokens": 1}}),
+        ];
+
+        let mut collector = EventCollector::new(false);
+        for value in raw.iter().cloned() {
+            let translated = backend.translate_event(&value);
+            collector.push(value, translated);
+        }
+        let result = collector.into_result();
+
+        assert_eq!(result.session_id, Some("t1".to_string()));
+        assert_eq!(result.message, Some("Hi".to_string()));
+        assert_eq!(
+            result.usage,
+            Some(TokenUsage {
+                input_tokens: 6,
+                output_tokens: 3
+            })
+        );
+        assert_eq!(result.files_changed, Some(2));
+        assert!(result.raw_events.is_empty());
+        assert_eq!(EventCollector::new(false).into_result().files_changed, None);
+    }
+
+    #[test]
+    fn test_get_target_with_prompt_prefix() {
+        let config = Config {
+            task: "Analyze the code".to_string(),
+            prompt_prefix: Some("You are a reviewer.".to_string()),
+            ..Default::default()
+        };
+        let executor = TaskExecutor::new(Arc::new(crate::backend::ClaudeBackend), &config).unwrap();
+        assert_eq!(
+            executor.get_target().unwrap(),
+            "You are a reviewer.\n\nAnalyze the code"
+        );
+    }
 }
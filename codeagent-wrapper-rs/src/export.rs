@@ -0,0 +1,729 @@
+//! Transcript export
+//!
+//! `codeagent export` renders a recorded session, a run's JSON output or a
+//! raw event recording (`--record`, replay files) as a readable conversation
+//! in Markdown, HTML or JSON: prompts, assistant messages, collapsible tool
+//! calls with their inputs and outputs, diffs of edited files, token usage and
+//! timing.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::backend::translate_event_as;
+use crate::cli::ExportFormat;
+use crate::errors::{ConfigError, exit_codes};
+use crate::handoff::describe_tool_call;
+use crate::parser::{AgentEvent, BackendType, TokenUsage, detect_backend_type};
+use crate::sessions::{SessionRecord, SessionRegistry, TranscriptTurn};
+use crate::utils::{format_duration, truncate_chars};
+
+/// Longest tool output kept in an export
+const MAX_OUTPUT_CHARS: usize = 10_000;
+
+/// A conversation ready to render
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Transcript {
+    /// Heading of the export
+    pub title: String,
+    /// Registry record, when exporting a session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<SessionRecord>,
+    /// Runs in order
+    pub turns: Vec<TranscriptTurn>,
+}
+
+impl Transcript {
+    /// Token usage summed over all turns
+    fn usage(&self) -> Option<TokenUsage> {
+        self.turns
+            .iter()
+            .filter_map(|turn| turn.usage)
+            .reduce(|mut total, usage| {
+                total.add(usage.input_tokens, usage.output_tokens);
+                total
+            })
+    }
+
+    fn duration(&self) -> Duration {
+        Duration::from_millis(self.turns.iter().map(|turn| turn.duration_ms).sum())
+    }
+}
+
+/// One rendered element of a turn
+#[derive(Debug, Clone, PartialEq)]
+enum Item {
+    Text(String),
+    Thinking(String),
+    Tool {
+        id: Option<String>,
+        name: String,
+        input: Value,
+        output: Option<String>,
+        is_error: bool,
+    },
+    Error(String),
+}
+
+/// Group a turn's events into messages and tool calls paired with their results
+fn items(events: &[AgentEvent]) -> Vec<Item> {
+    let mut items: Vec<Item> = Vec::new();
+    for event in events {
+        match event {
+            AgentEvent::AssistantText { text, delta } => match items.last_mut() {
+                Some(Item::Text(current)) if *delta => current.push_str(text),
+                _ => items.push(Item::Text(text.clone())),
+            },
+            AgentEvent::Thinking { text } => items.push(Item::Thinking(text.clone())),
+            AgentEvent::ToolCall { id, name, input } => items.push(Item::Tool {
+                id: id.clone(),
+                name: name.clone(),
+                input: input.clone(),
+                output: None,
+                is_error: false,
+            }),
+            AgentEvent::ToolResult {
+                id,
+                output: result,
+                is_error: failed,
+                ..
+            } => {
+                // Match by ID, else attach to the latest call still waiting for output
+                let call = items.iter_mut().rev().find(|item| {
+                    matches!(item, Item::Tool { id: call_id, output: None, .. }
+                        if id.is_none() || call_id == id)
+                });
+                if let Some(Item::Tool {
+                    output, is_error, ..
+                }) = call
+                {
+                    *output = Some(result.clone());
+                    *is_error = *failed;
+                }
+            }
+            AgentEvent::Error { message } => items.push(Item::Error(message.clone())),
+            AgentEvent::Completed {
+                result: Some(result),
+                ..
+            } if !items.iter().any(|item| matches!(item, Item::Text(_))) => {
+                items.push(Item::Text(result.clone()));
+            }
+            _ => {}
+        }
+    }
+    items.retain(|item| !matches!(item, Item::Text(text) if text.trim().is_empty()));
+    items
+}
+
+/// Unified diff of an edit tool call, when its input describes the change
+pub fn edit_diff(name: &str, input: &Value) -> Option<String> {
+    let field = |key: &str| input.get(key).and_then(Value::as_str);
+    let path = ["file_path", "filePath", "path"]
+        .iter()
+        .find_map(|key| field(key))
+        .unwrap_or("file");
+    let hunk = |old: &str, new: &str| {
+        let mut diff = String::new();
+        for line in old.lines() {
+            diff.push_str(&format!("-{}\n", line));
+        }
+        for line in new.lines() {
+            diff.push_str(&format!("+{}\n", line));
+        }
+        diff
+    };
+    let header = |from: &str| format!("--- {}\n+++ {}\n", from, path);
+
+    // apply_patch input is already a patch
+    if let Some(patch) = field("patch").or_else(|| field("input"))
+        && patch.contains("*** ")
+    {
+        return Some(patch.to_string());
+    }
+    if let (Some(old), Some(new)) = (field("old_string"), field("new_string")) {
+        return Some(header(path) + &hunk(old, new));
+    }
+    if let Some(edits) = input.get("edits").and_then(Value::as_array) {
+        let mut diff = header(path);
+        for edit in edits {
+            let old = edit.get("old_string").and_then(Value::as_str).unwrap_or("");
+            let new = edit.get("new_string").and_then(Value::as_str).unwrap_or("");
+            diff.push_str("@@\n");
+            diff.push_str(&hunk(old, new));
+        }
+        return Some(diff);
+    }
+    if matches!(name.to_lowercase().as_str(), "write" | "write_file")
+        && let Some(content) = field("content")
+    {
+        return Some(header("/dev/null") + &hunk("", content));
+    }
+    None
+}
+
+/// Load a session (ID, alias, `last`, `last@<workdir>`) or a run file
+pub fn load_transcript(reference: &str, registry: &SessionRegistry) -> Result<Transcript> {
+    let path = Path::new(reference);
+    if path.is_file() {
+        return load_run_file(path);
+    }
+
+    let record = registry
+        .resolve(reference)?
+        .ok_or_else(|| ConfigError::UnknownSession(reference.to_string()))?;
+    Ok(Transcript {
+        title: match record.name {
+            Some(ref name) => format!("Session {} ({})", record.id, name),
+            None => format!("Session {}", record.id),
+        },
+        turns: registry.transcript(&record.id)?,
+        session: Some(record),
+    })
+}
+
+/// Load a run's JSON output or a raw event recording
+fn load_run_file(path: &Path) -> Result<Transcript> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| ConfigError::FileNotFound(format!("{}: {}", path.display(), e)))?;
+    let started_at: DateTime<Utc> = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map(Into::into)
+        .unwrap_or_else(|_| Utc::now());
+    let title = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string());
+    let invalid =
+        |reason: String| ConfigError::InvalidParameter(path.display().to_string(), reason);
+
+    // The JSON document printed by a single run
+    if let Ok(output) = serde_json::from_str::<Value>(&content)
+        && let Some(events) = output.get("events")
+    {
+        let events: Vec<AgentEvent> = serde_json::from_value(events.clone())
+            .map_err(|e| invalid(format!("unreadable events: {}", e)))?;
+        let turn = TranscriptTurn {
+            prompt: String::new(),
+            backend: output
+                .get("backend")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown")
+                .to_string(),
+            model: None,
+            started_at,
+            duration_ms: output.get("duration").and_then(Value::as_u64).unwrap_or(0),
+            success: output
+                .get("success")
+                .and_then(Value::as_bool)
+                .unwrap_or(true),
+            usage: output
+                .get("usage")
+                .and_then(|usage| serde_json::from_value(usage.clone()).ok()),
+            events,
+        };
+        return Ok(Transcript {
+            title,
+            session: None,
+            turns: vec![turn],
+        });
+    }
+
+    // A raw backend event stream, as written by --record. Single events can be
+    // ambiguous (Codex `turn.completed` looks like Claude), so the dialect of the
+    // first recognizable event applies to the whole stream.
+    let mut values = Vec::new();
+    let mut success = true;
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        let value: Value = serde_json::from_str(line)
+            .map_err(|e| invalid(format!("neither a run output nor an event stream: {}", e)))?;
+        match value.get("mock").and_then(Value::as_str) {
+            Some("exit") => success = value.get("code").and_then(Value::as_i64) == Some(0),
+            Some(_) => {}
+            None => values.push(value),
+        }
+    }
+    let dialect = values
+        .iter()
+        .map(detect_backend_type)
+        .find(|dialect| *dialect != BackendType::Unknown)
+        .unwrap_or(BackendType::Unknown);
+    let events: Vec<AgentEvent> = values
+        .iter()
+        .flat_map(|value| translate_event_as(dialect, value))
+        .collect();
+    let usage = events
+        .iter()
+        .filter_map(|event| match event {
+            AgentEvent::Usage {
+                input_tokens,
+                output_tokens,
+            } => Some((*input_tokens, *output_tokens)),
+            _ => None,
+        })
+        .fold(None, |total: Option<TokenUsage>, (input, output)| {
+            let mut total = total.unwrap_or_default();
+            total.add(input, output);
+            Some(total)
+        });
+
+    Ok(Transcript {
+        title,
+        session: None,
+        turns: vec![TranscriptTurn {
+            prompt: String::new(),
+            backend: format!("{:?}", dialect).to_lowercase(),
+            model: None,
+            started_at,
+            duration_ms: 0,
+            success,
+            usage,
+            events,
+        }],
+    })
+}
+
+/// `1234 in / 56 out`
+fn format_usage(usage: &TokenUsage) -> String {
+    format!("{} in / {} out", usage.input_tokens, usage.output_tokens)
+}
+
+/// Heading line for a turn: number, start time, duration, tokens
+fn turn_heading(number: usize, turn: &TranscriptTurn) -> String {
+    let mut heading = format!(
+        "Turn {} · {}",
+        number,
+        turn.started_at
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S")
+    );
+    if turn.duration_ms > 0 {
+        heading.push_str(&format!(
+            " · {}",
+            format_duration(Duration::from_millis(turn.duration_ms))
+        ));
+    }
+    if let Some(ref usage) = turn.usage {
+        heading.push_str(&format!(" · {}", format_usage(usage)));
+    }
+    if !turn.success {
+        heading.push_str(" · failed");
+    }
+    heading
+}
+
+/// Session details shown under the title
+fn summary_lines(transcript: &Transcript) -> Vec<(&'static str, String)> {
+    let mut lines = Vec::new();
+    if let Some(ref record) = transcript.session {
+        let backend = match record.model {
+            Some(ref model) => format!("{} ({})", record.backend, model),
+            None => record.backend.clone(),
+        };
+        lines.push(("Backend", backend));
+        lines.push(("Workdir", record.workdir.display().to_string()));
+        if let Some(ref from) = record.handoff_from {
+            lines.push(("Handed off from", from.clone()));
+        }
+    }
+    lines.push(("Turns", transcript.turns.len().to_string()));
+    if !transcript.duration().is_zero() {
+        lines.push(("Duration", format_duration(transcript.duration())));
+    }
+    if let Some(usage) = transcript.usage() {
+        lines.push(("Tokens", format_usage(&usage)));
+    }
+    lines
+}
+
+/// Pretty-printed tool input
+fn tool_input(input: &Value) -> String {
+    match input {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => serde_json::to_string_pretty(other).unwrap_or_default(),
+    }
+}
+
+/// A fenced code block whose fence is longer than any backtick run inside it
+fn fence(lang: &str, body: &str) -> String {
+    let longest = body.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!(
+        "{}{}\n{}\n{}\n",
+        fence,
+        lang,
+        body.trim_end_matches('\n'),
+        fence
+    )
+}
+
+/// Render as Markdown (tool calls collapse with `<details>`)
+pub fn render_markdown(transcript: &Transcript) -> String {
+    let mut out = format!("# {}\n\n", transcript.title);
+    for (key, value) in summary_lines(transcript) {
+        out.push_str(&format!("- **{}:** {}\n", key, value));
+    }
+
+    for (index, turn) in transcript.turns.iter().enumerate() {
+        if !out.ends_with("\n\n") {
+            out.push('\n');
+        }
+        out.push_str(&format!("## {}\n\n", turn_heading(index + 1, turn)));
+        if !turn.prompt.trim().is_empty() {
+            out.push_str("**User**\n\n");
+            for line in turn.prompt.trim().lines() {
+                out.push_str(&format!("> {}\n", line));
+            }
+            out.push('\n');
+        }
+
+        for item in items(&turn.events) {
+            match item {
+                Item::Text(text) => out.push_str(&format!("**Assistant**\n\n{}\n\n", text.trim())),
+                Item::Thinking(text) => out.push_str(&format!(
+                    "<details>\n<summary>Thinking</summary>\n\n{}\n\n</details>\n\n",
+                    text.trim()
+                )),
+                Item::Tool {
+                    name,
+                    input,
+                    output,
+                    is_error,
+                    ..
+                } => {
+                    out.push_str(&format!(
+                        "<details>\n<summary>Tool: {}{}</summary>\n\n",
+                        // Raw HTML: `<`, `&` and backticks would otherwise be interpreted
+                        escape_html(&describe_tool_call(&name, &input)).replace('`', "&#96;"),
+                        if is_error { " (error)" } else { "" }
+                    ));
+                    match edit_diff(&name, &input) {
+                        Some(diff) => out.push_str(&fence("diff", &diff)),
+                        None if !input.is_null() => {
+                            out.push_str(&fence("json", &tool_input(&input)))
+                        }
+                        None => {}
+                    }
+                    if let Some(output) = output.filter(|o| !o.trim().is_empty()) {
+                        out.push_str("\nOutput:\n\n");
+                        out.push_str(&fence("", &truncate_chars(&output, MAX_OUTPUT_CHARS)));
+                    }
+                    out.push_str("\n</details>\n\n");
+                }
+                Item::Error(message) => out.push_str(&format!(
+                    "> **Error:** {}\n\n",
+                    escape_html(message.trim()).replace('\n', "\n> ")
+                )),
+            }
+        }
+    }
+
+    if transcript.turns.is_empty() {
+        out.push_str("\n_No transcript recorded for this session._\n");
+    }
+    out.trim_end().to_string() + "\n"
+}
+
+/// Escape text for HTML
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Diff lines wrapped in spans so additions and removals can be colored
+fn html_diff(diff: &str) -> String {
+    diff.lines()
+        .map(|line| {
+            let class = match line.chars().next() {
+                Some('+') if !line.starts_with("+++") => "add",
+                Some('-') if !line.starts_with("---") => "del",
+                _ => "ctx",
+            };
+            format!("<span class=\"{}\">{}</span>", class, escape_html(line))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+const HTML_STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:960px;margin:2em auto;\
+padding:0 1em;color:#222}pre{background:#f6f8fa;padding:.8em;overflow-x:auto;white-space:pre-wrap}\
+.user{border-left:4px solid #0969da;padding-left:1em}.text{white-space:pre-wrap}\
+details{margin:.6em 0;border:1px solid #ddd;border-radius:4px;padding:.3em .8em}\
+summary{cursor:pointer;font-family:monospace}.error{color:#b42318}\
+.add{color:#1a7f37}.del{color:#b42318}.meta{color:#555}";
+
+/// Render as a self-contained HTML page
+pub fn render_html(transcript: &Transcript) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>{}</style>\n</head>\n<body>\n<h1>{}</h1>\n<ul class=\"meta\">\n",
+        escape_html(&transcript.title),
+        HTML_STYLE,
+        escape_html(&transcript.title)
+    );
+    for (key, value) in summary_lines(transcript) {
+        out.push_str(&format!(
+            "<li><strong>{}:</strong> {}</li>\n",
+            key,
+            escape_html(&value)
+        ));
+    }
+    out.push_str("</ul>\n");
+
+    for (index, turn) in transcript.turns.iter().enumerate() {
+        out.push_str(&format!(
+            "<section class=\"turn\">\n<h2>{}</h2>\n",
+            escape_html(&turn_heading(index + 1, turn))
+        ));
+        if !turn.prompt.trim().is_empty() {
+            out.push_str(&format!(
+                "<div class=\"user\"><h3>User</h3><div class=\"text\">{}</div></div>\n",
+                escape_html(turn.prompt.trim())
+            ));
+        }
+
+        for item in items(&turn.events) {
+            match item {
+                Item::Text(text) => out.push_str(&format!(
+                    "<h3>Assistant</h3><div class=\"text\">{}</div>\n",
+                    escape_html(text.trim())
+                )),
+                Item::Thinking(text) => out.push_str(&format!(
+                    "<details><summary>Thinking</summary><div class=\"text\">{}</div></details>\n",
+                    escape_html(text.trim())
+                )),
+                Item::Tool {
+                    name,
+                    input,
+                    output,
+                    is_error,
+                    ..
+                } => {
+                    out.push_str(&format!(
+                        "<details class=\"tool\"><summary{}>{}{}</summary>\n",
+                        if is_error { " class=\"error\"" } else { "" },
+                        escape_html(&describe_tool_call(&name, &input)),
+                        if is_error { " (error)" } else { "" }
+                    ));
+                    match edit_diff(&name, &input) {
+                        Some(diff) => out.push_str(&format!("<pre>{}</pre>\n", html_diff(&diff))),
+                        None if !input.is_null() => out.push_str(&format!(
+                            "<pre>{}</pre>\n",
+                            escape_html(&tool_input(&input))
+                        )),
+                        None => {}
+                    }
+                    if let Some(output) = output.filter(|o| !o.trim().is_empty()) {
+                        out.push_str(&format!(
+                            "<p>Output:</p><pre>{}</pre>\n",
+                            escape_html(&truncate_chars(&output, MAX_OUTPUT_CHARS))
+                        ));
+                    }
+                    out.push_str("</details>\n");
+                }
+                Item::Error(message) => out.push_str(&format!(
+                    "<p class=\"error\"><strong>Error:</strong> {}</p>\n",
+                    escape_html(&message)
+                )),
+            }
+        }
+        out.push_str("</section>\n");
+    }
+
+    if transcript.turns.is_empty() {
+        out.push_str("<p><em>No transcript recorded for this session.</em></p>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Handle `export`
+pub fn run_export(reference: &str, format: ExportFormat, output: Option<&Path>) -> Result<i32> {
+    let transcript = load_transcript(reference, &SessionRegistry::open_default())?;
+    let rendered = match format {
+        ExportFormat::Md => render_markdown(&transcript),
+        ExportFormat::Html => render_html(&transcript),
+        ExportFormat::Json => serde_json::to_string_pretty(&transcript)? + "\n",
+    };
+
+    match output {
+        Some(path) => std::fs::write(path, rendered)
+            .with_context(|| format!("Failed to write export: {}", path.display()))?,
+        None => print!("{}", rendered),
+    }
+    Ok(exit_codes::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn transcript(events: Vec<AgentEvent>) -> Transcript {
+        Transcript {
+            title: "Session s1".to_string(),
+            session: None,
+            turns: vec![TranscriptTurn {
+                prompt: "Fix the <login> bug".to_string(),
+                backend: "claude".to_string(),
+                model: None,
+                started_at: Utc::now(),
+                duration_ms: 12_300,
+                success: true,
+                usage: Some(TokenUsage {
+                    input_tokens: 100,
+                    output_tokens: 20,
+                }),
+                events,
+            }],
+        }
+    }
+
+    fn edit_events() -> Vec<AgentEvent> {
+        vec![
+            AgentEvent::AssistantText {
+                text: "Looking ".to_string(),
+                delta: true,
+            },
+            AgentEvent::AssistantText {
+                text: "at it.".to_string(),
+                delta: true,
+            },
+            AgentEvent::ToolCall {
+                id: Some("t1".to_string()),
+                name: "Edit".to_string(),
+                input: json!({"file_path": "src/auth.rs", "old_string": "a < b", "new_string": "a <= b"}),
+            },
+            AgentEvent::ToolResult {
+                id: Some("t1".to_string()),
+                name: None,
+                output: "Applied".to_string(),
+                is_error: false,
+            },
+            AgentEvent::Completed {
+                success: true,
+                result: Some("Done".to_string()),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_items_pair_tool_results() {
+        let items = items(&edit_events());
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0], Item::Text("Looking at it.".to_string()));
+        assert!(matches!(&items[1], Item::Tool { output: Some(o), .. } if o == "Applied"));
+    }
+
+    #[test]
+    fn test_edit_diff() {
+        let diff = edit_diff(
+            "Edit",
+            &json!({"file_path": "a.rs", "old_string": "x\ny", "new_string": "z"}),
+        )
+        .unwrap();
+        assert_eq!(diff, "--- a.rs\n+++ a.rs\n-x\n-y\n+z\n");
+        let write = edit_diff(
+            "Write",
+            &json!({"file_path": "new.rs", "content": "fn main() {}"}),
+        );
+        assert_eq!(
+            write.as_deref(),
+            Some("--- /dev/null\n+++ new.rs\n+fn main() {}\n")
+        );
+        assert!(edit_diff("Bash", &json!({"command": "ls"})).is_none());
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let markdown = render_markdown(&transcript(edit_events()));
+        assert!(markdown.starts_with("# Session s1\n"));
+        assert!(markdown.contains("- **Tokens:** 100 in / 20 out"));
+        assert!(markdown.contains("> Fix the <login> bug"));
+        assert!(markdown.contains("**Assistant**\n\nLooking at it."));
+        assert!(markdown.contains("<summary>Tool: Edit(src/auth.rs)</summary>"));
+        assert!(
+            markdown.contains("```diff\n--- src/auth.rs\n+++ src/auth.rs\n-a < b\n+a <= b\n```")
+        );
+        assert!(markdown.contains("Output:\n\n```\nApplied\n```"));
+    }
+
+    #[test]
+    fn test_render_markdown_escapes_html_lines() {
+        let events = vec![
+            AgentEvent::ToolCall {
+                id: None,
+                name: "Bash".to_string(),
+                input: json!({"command": "echo `id` </summary><script>x</script>"}),
+            },
+            AgentEvent::Error {
+                message: "bad <img src=x onerror=alert(1)>\nsecond & last".to_string(),
+            },
+        ];
+        let markdown = render_markdown(&transcript(events));
+        assert!(markdown.contains(
+            "<summary>Tool: Bash(echo &#96;id&#96; &lt;/summary&gt;&lt;script&gt;x&lt;/script&gt;)</summary>"
+        ));
+        assert!(markdown.contains(
+            "> **Error:** bad &lt;img src=x onerror=alert(1)&gt;\n> second &amp; last\n"
+        ));
+    }
+
+    #[test]
+    fn test_render_html_escapes() {
+        let html = render_html(&transcript(edit_events()));
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("Fix the &lt;login&gt; bug"));
+        assert!(html.contains("<span class=\"del\">-a &lt; b</span>"));
+        assert!(html.contains("<details class=\"tool\">"));
+        assert!(!html.contains("<login>"));
+    }
+
+    #[test]
+    fn test_fence_longer_than_content() {
+        assert_eq!(fence("", "a ``` b"), "````\na ``` b\n````\n");
+        assert_eq!(fence("json", "{}"), "```json\n{}\n```\n");
+    }
+
+    #[test]
+    fn test_load_recorded_event_stream() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.jsonl");
+        std::fs::write(
+            &path,
+            [
+                r#"{"type":"thread.started","thread_id":"t1"}"#,
+                r#"{"type":"item.completed","item":{"id":"i1","type":"agent_message","text":"Hi"}}"#,
+                r#"{"type":"turn.completed","usage":{"input_tokens":5,"output_tokens":2}}"#,
+                r#"{"mock":"exit","code":1}"#,
+            ]
+            .join("\n"),
+        )
+        .unwrap();
+
+        let registry = SessionRegistry::new(dir.path().join("sessions"));
+        let transcript = load_transcript(path.to_str().unwrap(), &registry).unwrap();
+        assert_eq!(transcript.title, "run.jsonl");
+        let turn = &transcript.turns[0];
+        assert_eq!(turn.backend, "codex");
+        assert!(!turn.success);
+        assert_eq!(turn.usage.map(|u| u.input_tokens), Some(5));
+        assert_eq!(items(&turn.events), vec![Item::Text("Hi".to_string())]);
+
+        assert!(load_transcript("unknown", &registry).is_err());
+    }
+}
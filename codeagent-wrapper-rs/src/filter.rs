@@ -15,8 +15,10 @@ impl OutputFilter {
     /// Create a new output filter with default patterns
     pub fn new() -> Self {
         let patterns = vec![
-            // ANSI escape codes
-            Regex::new(r"\x1b\[[0-9;]*[a-zA-Z]").unwrap(),
+            // ANSI escape codes (CSI, including private modes like ESC[?25l)
+            Regex::new(r"\x1b\[[0-9;?]*[a-zA-Z]").unwrap(),
+            // OSC sequences (window titles, hyperlinks)
+            Regex::new(r"\x1b\][^\x07\x1b]*(?:\x07|\x1b\\)").unwrap(),
             // Control characters
             Regex::new(r"[\x00-\x08\x0b\x0c\x0e-\x1f]").unwrap(),
         ];
@@ -40,6 +42,75 @@ impl Default for OutputFilter {
     }
 }
 
+/// Gemini stderr noise (matches Node.js `GEMINI_NOISE_PATTERNS`)
+const GEMINI_NOISE_PATTERNS: &[&str] = &[
+    r"^\[STARTUP\]",
+    r"Session cleanup disabled",
+    r"^Warning:",
+    r"^\(node:",
+    r"Loaded cached credentials",
+    r"Loading extension:",
+];
+
+/// Codex stderr noise (matches Node.js `CODEX_NOISE_PATTERNS`)
+const CODEX_NOISE_PATTERNS: &[&str] = &[
+    r"ERROR codex_core::codex: needs_follow_up:",
+    r"ERROR codex_core::skills::loader:",
+];
+
+/// Spinner and banner lines common to all backends
+const COMMON_NOISE_PATTERNS: &[&str] = &[
+    // Braille spinner frames, optionally followed by a status word
+    r"^\s*[\u{2800}-\u{28FF}]",
+    // Lines made only of classic spinner characters
+    r"^\s*[|/\\-]\s*$",
+    // Box-drawing banners
+    r"^\s*[╭╮╰╯│─┌┐└┘├┤━┃]",
+];
+
+/// Per-backend filter for forwarded stderr (Node.js `createNoiseFilter`)
+pub struct NoiseFilter {
+    output: OutputFilter,
+    patterns: Vec<Regex>,
+}
+
+impl NoiseFilter {
+    /// Create a noise filter for the given backend name
+    pub fn for_backend(backend: &str) -> Self {
+        let backend_patterns: Vec<&str> = match backend {
+            "gemini" => GEMINI_NOISE_PATTERNS.to_vec(),
+            "codex" => CODEX_NOISE_PATTERNS.to_vec(),
+            _ => [GEMINI_NOISE_PATTERNS, CODEX_NOISE_PATTERNS].concat(),
+        };
+
+        let patterns = backend_patterns
+            .into_iter()
+            .chain(COMMON_NOISE_PATTERNS.iter().copied())
+            .filter_map(|p| Regex::new(p).ok())
+            .collect();
+
+        Self {
+            output: OutputFilter::new(),
+            patterns,
+        }
+    }
+
+    /// Clean a stderr line, returning `None` if it is noise
+    pub fn clean(&self, line: &str) -> Option<String> {
+        // Spinners redraw with carriage returns; keep only the final frame
+        let line = line.trim_end_matches(['\n', '\r']);
+        let line = line.rsplit('\r').next().unwrap_or(line);
+
+        let cleaned = self.output.filter(line);
+        let cleaned = cleaned.trim_end();
+        if cleaned.trim().is_empty() || self.patterns.iter().any(|p| p.is_match(cleaned)) {
+            return None;
+        }
+
+        Some(cleaned.to_string())
+    }
+}
+
 /// Sanitize output for JSON embedding
 pub fn sanitize_for_json(input: &str) -> String {
     input.replace(['\x00', '\x08', '\x0c'], "")
@@ -124,6 +195,37 @@ mod tests {
         assert_eq!(output, "Hello World");
     }
 
+    #[test]
+    fn test_output_filter_private_modes() {
+        let filter = OutputFilter::new();
+        assert_eq!(filter.filter("\x1b[?25lHidden\x1b[?25h"), "Hidden");
+        assert_eq!(filter.filter("\x1b]0;title\x07Text"), "Text");
+    }
+
+    #[test]
+    fn test_noise_filter() {
+        let gemini = NoiseFilter::for_backend("gemini");
+        assert_eq!(gemini.clean("[STARTUP] Loading config\n"), None);
+        assert_eq!(gemini.clean("Loaded cached credentials."), None);
+        assert_eq!(
+            gemini.clean("\x1b[31mAPI error: 429\x1b[0m\n"),
+            Some("API error: 429".to_string())
+        );
+
+        let codex = NoiseFilter::for_backend("codex");
+        assert_eq!(
+            codex.clean("2025-01-01 ERROR codex_core::codex: needs_follow_up: true"),
+            None
+        );
+        assert_eq!(codex.clean("⠋ Thinking..."), None);
+        assert_eq!(codex.clean("╭──────────╮"), None);
+        assert_eq!(codex.clean("   \n"), None);
+        assert_eq!(
+            codex.clean("⠋ Working\r⠙ Working\rDone"),
+            Some("Done".to_string())
+        );
+    }
+
     #[test]
     fn test_extract_coverage() {
         assert_eq!(extract_coverage("Coverage: 85.5%"), Some(85.5));
@@ -0,0 +1,380 @@
+//! Cross-backend session handoff
+//!
+//! A session ID is only understood by the backend that created it. `handoff`
+//! rebuilds a session from its recorded transcript (prompts, assistant
+//! messages, tool calls and touched files), compresses it to a token budget
+//! and prepends it to the next task of a new session on another backend.
+
+use serde_json::Value;
+
+use crate::parser::{AgentEvent, extract_final_message};
+use crate::progress::EDIT_TOOLS;
+use crate::sessions::{SessionRecord, TranscriptTurn};
+use crate::utils::truncate_chars;
+
+/// Default context budget in tokens
+pub const DEFAULT_BUDGET: usize = 4000;
+
+/// Rough characters per token, used to turn the budget into a length
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Most tool calls listed for a single turn
+const MAX_TOOLS_PER_TURN: usize = 20;
+
+/// Room kept for the omitted-turns note
+const OMITTED_NOTE_CHARS: usize = 40;
+
+/// Input fields that best describe a tool call, in order of preference
+const SUMMARY_FIELDS: [&str; 7] = [
+    "command",
+    "file_path",
+    "filePath",
+    "path",
+    "pattern",
+    "query",
+    "url",
+];
+
+/// Input fields naming the file an edit tool writes
+const PATH_FIELDS: [&str; 4] = ["file_path", "filePath", "path", "notebook_path"];
+
+/// `apply_patch` headers naming the files a patch touches
+const PATCH_HEADERS: [&str; 3] = ["*** Add File: ", "*** Update File: ", "*** Delete File: "];
+
+/// How much of a turn to keep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Detail {
+    /// Prompt, every tool call and the final message
+    Full,
+    /// Shortened prompt and message, tool calls counted
+    Compact,
+    /// Shortened prompt only
+    Brief,
+}
+
+/// One-line description of a tool call: `name(argument)`
+pub fn describe_tool_call(name: &str, input: &Value) -> String {
+    let argument = SUMMARY_FIELDS
+        .iter()
+        .find_map(|field| match input.get(field)? {
+            Value::String(s) => Some(s.clone()),
+            Value::Null => None,
+            other => Some(other.to_string()),
+        });
+    match argument {
+        Some(argument) => format!(
+            "{}({})",
+            name,
+            truncate_chars(argument.lines().next().unwrap_or_default(), 80)
+        ),
+        None => name.to_string(),
+    }
+}
+
+/// Files written by edit tools, in the order they were first touched
+pub fn touched_files(events: &[AgentEvent]) -> Vec<String> {
+    let mut files: Vec<String> = Vec::new();
+    let mut add = |path: &str| {
+        let path = path.trim();
+        if !path.is_empty() && !files.iter().any(|f| f == path) {
+            files.push(path.to_string());
+        }
+    };
+
+    for event in events {
+        let AgentEvent::ToolCall { name, input, .. } = event else {
+            continue;
+        };
+        if !EDIT_TOOLS.contains(&name.to_lowercase().as_str()) {
+            continue;
+        }
+
+        for field in PATH_FIELDS {
+            if let Some(path) = input.get(field).and_then(Value::as_str) {
+                add(path);
+            }
+        }
+        // Codex file_change items list their changes
+        for change in input
+            .get("changes")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            if let Some(path) = change.get("path").and_then(Value::as_str) {
+                add(path);
+            }
+        }
+        if let Some(patch) = input
+            .get("patch")
+            .or_else(|| input.get("input"))
+            .and_then(Value::as_str)
+        {
+            for line in patch.lines() {
+                if let Some(path) = PATCH_HEADERS.iter().find_map(|h| line.strip_prefix(h)) {
+                    add(path);
+                }
+            }
+        }
+    }
+
+    files
+}
+
+/// Render one turn at the given level of detail
+fn render_turn(number: usize, turn: &TranscriptTurn, detail: Detail) -> String {
+    let (prompt_chars, message_chars) = match detail {
+        Detail::Full => (2000, 4000),
+        Detail::Compact => (300, 600),
+        Detail::Brief => (120, 0),
+    };
+    let mut out = format!(
+        "## Turn {} ({}{})\nUser: {}\n",
+        number,
+        turn.backend,
+        if turn.success { "" } else { ", failed" },
+        truncate_chars(turn.prompt.trim(), prompt_chars)
+    );
+    if detail == Detail::Brief {
+        return out;
+    }
+
+    let tools: Vec<String> = turn
+        .events
+        .iter()
+        .filter_map(|event| match event {
+            AgentEvent::ToolCall { name, input, .. } => Some(describe_tool_call(name, input)),
+            _ => None,
+        })
+        .collect();
+    if !tools.is_empty() {
+        if detail == Detail::Full {
+            let mut listed = tools
+                .iter()
+                .take(MAX_TOOLS_PER_TURN)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join("; ");
+            if tools.len() > MAX_TOOLS_PER_TURN {
+                listed.push_str(&format!(
+                    "; ... and {} more",
+                    tools.len() - MAX_TOOLS_PER_TURN
+                ));
+            }
+            out.push_str(&format!("Tools: {}\n", listed));
+        } else {
+            out.push_str(&format!("Tools: {} calls\n", tools.len()));
+        }
+    }
+
+    if let Some(message) = extract_final_message(&turn.events) {
+        out.push_str(&format!(
+            "Assistant: {}\n",
+            truncate_chars(&message, message_chars)
+        ));
+    }
+    out
+}
+
+/// Summarize a recorded session within roughly `budget` tokens
+///
+/// The newest turns are kept in full; older ones are shortened, then dropped,
+/// as the budget runs out.
+pub fn build_context(record: &SessionRecord, turns: &[TranscriptTurn], budget: usize) -> String {
+    let limit = budget.saturating_mul(CHARS_PER_TOKEN);
+    let footer = "\n# Next task";
+
+    let mut header = format!(
+        "# Context handed off from a {} session\n\nSession {} in {}. \
+         Earlier turns may be summarized.\n",
+        record.backend,
+        record.id,
+        record.workdir.display()
+    );
+    let mut files: Vec<String> = Vec::new();
+    for file in turns.iter().flat_map(|turn| touched_files(&turn.events)) {
+        if !files.contains(&file) {
+            files.push(file);
+        }
+    }
+    if !files.is_empty() {
+        header.push_str(&format!(
+            "\nFiles touched: {}\n",
+            truncate_chars(&files.join(", "), limit / 4)
+        ));
+    }
+    // Sessions recorded before transcripts only have their first prompt
+    if turns.is_empty() {
+        header.push_str(&format!("\nOriginal task: {}\n", record.prompt));
+    }
+
+    let mut remaining =
+        limit.saturating_sub(header.chars().count() + footer.chars().count() + OMITTED_NOTE_CHARS);
+    let mut kept: Vec<String> = Vec::new();
+    for (index, turn) in turns.iter().enumerate().rev() {
+        let rendered = [Detail::Full, Detail::Compact, Detail::Brief]
+            .into_iter()
+            .map(|detail| render_turn(index + 1, turn, detail))
+            .find(|text| text.chars().count() < remaining);
+        match rendered {
+            Some(text) => {
+                remaining -= text.chars().count() + 1;
+                kept.push(text);
+            }
+            None => {
+                // Always carry at least part of the latest turn
+                if kept.is_empty() && remaining > 0 {
+                    kept.push(truncate_chars(
+                        &render_turn(index + 1, turn, Detail::Compact),
+                        remaining,
+                    ));
+                }
+                break;
+            }
+        }
+    }
+
+    let mut out = header;
+    let omitted = turns.len() - kept.len();
+    if omitted > 0 {
+        out.push_str(&format!("\n({} earlier turns omitted)\n", omitted));
+    }
+    for text in kept.iter().rev() {
+        out.push('\n');
+        out.push_str(text);
+    }
+    out.push_str(footer);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn record() -> SessionRecord {
+        SessionRecord {
+            id: "s1".to_string(),
+            name: None,
+            backend: "codex".to_string(),
+            model: None,
+            workdir: PathBuf::from("/src/api"),
+            prompt: "Fix the login bug".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            status: "success".to_string(),
+            runs: 1,
+            handoff_from: None,
+        }
+    }
+
+    fn turn(prompt: &str, events: Vec<AgentEvent>) -> TranscriptTurn {
+        TranscriptTurn {
+            prompt: prompt.to_string(),
+            backend: "codex".to_string(),
+            model: None,
+            started_at: Utc::now(),
+            duration_ms: 1000,
+            success: true,
+            usage: None,
+            events,
+        }
+    }
+
+    fn tool(name: &str, input: Value) -> AgentEvent {
+        AgentEvent::ToolCall {
+            id: None,
+            name: name.to_string(),
+            input,
+        }
+    }
+
+    fn text(text: &str) -> AgentEvent {
+        AgentEvent::AssistantText {
+            text: text.to_string(),
+            delta: false,
+        }
+    }
+
+    #[test]
+    fn test_describe_tool_call() {
+        assert_eq!(
+            describe_tool_call("shell", &json!({"command": "cargo test\nmore"})),
+            "shell(cargo test)"
+        );
+        assert_eq!(
+            describe_tool_call("Read", &json!({"file_path": "src/main.rs"})),
+            "Read(src/main.rs)"
+        );
+        assert_eq!(describe_tool_call("todo", &Value::Null), "todo");
+    }
+
+    #[test]
+    fn test_touched_files() {
+        let events = vec![
+            tool("Edit", json!({"file_path": "src/auth.rs"})),
+            tool("Read", json!({"file_path": "src/lib.rs"})),
+            tool(
+                "file_change",
+                json!({"changes": [{"path": "src/auth.rs"}, {"path": "tests/auth.rs"}]}),
+            ),
+            tool(
+                "apply_patch",
+                json!({"input": "*** Begin Patch\n*** Update File: README.md\n@@\n"}),
+            ),
+        ];
+        assert_eq!(
+            touched_files(&events),
+            vec!["src/auth.rs", "tests/auth.rs", "README.md"]
+        );
+    }
+
+    #[test]
+    fn test_build_context() {
+        let turns = vec![
+            turn(
+                "Fix the login bug",
+                vec![
+                    tool("Edit", json!({"file_path": "src/auth.rs"})),
+                    text("Fixed the token check."),
+                ],
+            ),
+            turn(
+                "Add tests",
+                vec![
+                    tool("shell", json!({"command": "cargo test"})),
+                    text("Tests pass."),
+                ],
+            ),
+        ];
+
+        let context = build_context(&record(), &turns, DEFAULT_BUDGET);
+        assert!(context.starts_with("# Context handed off from a codex session"));
+        assert!(context.contains("Files touched: src/auth.rs"));
+        assert!(context.contains("## Turn 1 (codex)\nUser: Fix the login bug"));
+        assert!(context.contains("Tools: shell(cargo test)"));
+        assert!(context.contains("Assistant: Tests pass."));
+        assert!(context.ends_with("# Next task"));
+        assert!(context.find("Turn 1").unwrap() < context.find("Turn 2").unwrap());
+    }
+
+    #[test]
+    fn test_build_context_budget() {
+        let long = "x".repeat(3000);
+        let turns: Vec<TranscriptTurn> = (0..20)
+            .map(|i| turn(&format!("Step {}", i), vec![text(&long)]))
+            .collect();
+
+        let context = build_context(&record(), &turns, 500);
+        assert!(context.chars().count() <= 500 * CHARS_PER_TOKEN);
+        assert!(context.contains("earlier turns omitted"));
+        assert!(context.contains("User: Step 19"));
+        assert!(!context.contains("User: Step 0\n"));
+
+        // Without a transcript the recorded prompt is used
+        let context = build_context(&record(), &[], DEFAULT_BUDGET);
+        assert!(context.contains("Original task: Fix the login bug"));
+    }
+}
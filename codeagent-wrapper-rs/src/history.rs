@@ -0,0 +1,546 @@
+//! Persistent run history
+//!
+//! Every task result (single runs, parallel tasks, skipped tasks) is appended
+//! as one JSON line to `~/.codeagent/history.jsonl`. `codeagent history` lists
+//! and filters the entries and aggregates them: success rate and p50/p95
+//! duration per backend, token spend per day.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::agent_config::get_config_dir;
+use crate::backend::backend_chain;
+use crate::config::Config;
+use crate::errors::ConfigError;
+use crate::executor::TaskResult;
+use crate::utils::{format_duration, truncate_chars};
+
+/// One recorded task result
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    /// When the task finished
+    pub finished_at: DateTime<Utc>,
+    /// Task ID (parallel mode)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
+    /// `success`, `failed` or `skipped`
+    pub status: String,
+    /// Backend that ran the task (or was requested)
+    #[serde(default)]
+    pub backend: Option<String>,
+    /// Model requested from the backend
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Agent preset
+    #[serde(default)]
+    pub agent: Option<String>,
+    /// Working directory
+    #[serde(default)]
+    pub workdir: Option<PathBuf>,
+    /// Task duration
+    pub duration_ms: u64,
+    /// Exit code reported for the task
+    pub exit_code: i32,
+    /// Input tokens
+    #[serde(default)]
+    pub input_tokens: u64,
+    /// Output tokens
+    #[serde(default)]
+    pub output_tokens: u64,
+    /// Files changed
+    #[serde(default)]
+    pub files_changed: Option<usize>,
+    /// Classified error kind of a failed task
+    #[serde(default)]
+    pub error_kind: Option<String>,
+    /// Backend session ID
+    #[serde(default)]
+    pub session_id: Option<String>,
+}
+
+impl HistoryEntry {
+    /// Entry for a result, with the run's config when there is one
+    pub fn new(result: &TaskResult, config: Option<&Config>) -> Self {
+        let usage = result.usage.unwrap_or_default();
+        Self {
+            finished_at: Utc::now(),
+            task_id: result
+                .task_id
+                .clone()
+                .or_else(|| config.and_then(|c| c.task_id.clone()))
+                .filter(|id| !id.is_empty()),
+            status: result.status().to_string(),
+            backend: result.backend.clone().or_else(|| {
+                let requested = config?.backend.as_deref();
+                backend_chain(requested, &[])
+                    .into_iter()
+                    .next()
+                    .map(|choice| choice.name)
+            }),
+            model: result
+                .model
+                .clone()
+                .or_else(|| config.and_then(|c| c.model.clone())),
+            agent: config.and_then(|c| c.agent.clone()),
+            workdir: config.map(|c| c.work_dir.clone()),
+            duration_ms: result.duration.as_millis() as u64,
+            exit_code: result.process_exit_code(),
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            files_changed: result.files_changed,
+            error_kind: result
+                .error
+                .as_ref()
+                .filter(|_| !result.success)
+                .map(|error| error.kind.as_str().to_string()),
+            session_id: result.session_id.clone(),
+        }
+    }
+}
+
+/// Append-only history file
+pub struct HistoryStore {
+    path: PathBuf,
+}
+
+impl HistoryStore {
+    /// History in `~/.codeagent/history.jsonl`
+    pub fn open_default() -> Self {
+        Self::new(get_config_dir().join("history.jsonl"))
+    }
+
+    /// History in the given file
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Append an entry (one write per line, so concurrent tasks never interleave)
+    pub fn append(&self, entry: &HistoryEntry) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open history: {}", self.path.display()))?;
+        let line = serde_json::to_string(entry)? + "\n";
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    /// All entries, oldest first; unreadable lines are skipped
+    pub fn load(&self) -> Result<Vec<HistoryEntry>> {
+        let content = match std::fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read history: {}", self.path.display()));
+            }
+        };
+        Ok(content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(entry) => Some(entry),
+                Err(e) => {
+                    warn!(error = %e, "Skipping unreadable history line");
+                    None
+                }
+            })
+            .collect())
+    }
+}
+
+/// Record entries in the default history, logging (not failing) on error
+pub fn record(entries: &[HistoryEntry]) {
+    let store = HistoryStore::open_default();
+    for entry in entries {
+        if let Err(e) = store.append(entry) {
+            warn!(error = %e, "Failed to record run history");
+        }
+    }
+}
+
+/// Parse a `--since` / `--until` bound
+///
+/// Accepts `YYYY-MM-DD` (local midnight; `end_of_day` moves it to the next
+/// midnight), RFC 3339 timestamps and durations back from now (`90m`, `12h`,
+/// `7d`, `2w`).
+pub fn parse_time_bound(flag: &str, value: &str, end_of_day: bool) -> Result<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let date = if end_of_day {
+            date.succ_opt().unwrap_or(date)
+        } else {
+            date
+        };
+        if let Some(midnight) = date
+            .and_hms_opt(0, 0, 0)
+            .and_then(|t| Local.from_local_datetime(&t).earliest())
+        {
+            return Ok(midnight.with_timezone(&Utc));
+        }
+    }
+    if let Some(unit) = value.chars().last()
+        && let Ok(amount) = value[..value.len() - unit.len_utf8()].parse::<i64>()
+    {
+        let duration = match unit {
+            'm' => Some(chrono::Duration::minutes(amount)),
+            'h' => Some(chrono::Duration::hours(amount)),
+            'd' => Some(chrono::Duration::days(amount)),
+            'w' => Some(chrono::Duration::weeks(amount)),
+            _ => None,
+        };
+        if let Some(duration) = duration {
+            return Ok(Utc::now() - duration);
+        }
+    }
+    Err(ConfigError::InvalidParameter(
+        flag.to_string(),
+        format!(
+            "'{}' (expected YYYY-MM-DD, an RFC 3339 time or a duration such as 7d)",
+            value
+        ),
+    )
+    .into())
+}
+
+/// Which entries `history` shows
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    /// Only entries finished at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Only entries finished before this time
+    pub until: Option<DateTime<Utc>>,
+    /// Only entries of this backend
+    pub backend: Option<String>,
+    /// Only entries with this status
+    pub status: Option<String>,
+}
+
+impl HistoryFilter {
+    /// Whether an entry passes the filter
+    pub fn matches(&self, entry: &HistoryEntry) -> bool {
+        self.since.is_none_or(|since| entry.finished_at >= since)
+            && self.until.is_none_or(|until| entry.finished_at < until)
+            && self.backend.as_ref().is_none_or(|backend| {
+                entry
+                    .backend
+                    .as_ref()
+                    .is_some_and(|b| b.eq_ignore_ascii_case(backend))
+            })
+            && self
+                .status
+                .as_ref()
+                .is_none_or(|status| entry.status == *status)
+    }
+}
+
+/// Aggregates for one backend
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackendStats {
+    pub backend: String,
+    /// Runs that reached the backend (skipped tasks excluded)
+    pub runs: usize,
+    pub succeeded: usize,
+    /// Share of successful runs, 0.0 to 1.0
+    pub success_rate: f64,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+}
+
+/// Aggregates for one local calendar day
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DayStats {
+    pub date: NaiveDate,
+    pub runs: usize,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+/// `history --stats` report
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryStats {
+    pub total: usize,
+    pub backends: Vec<BackendStats>,
+    pub days: Vec<DayStats>,
+}
+
+/// Nearest-rank percentile of sorted values
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Aggregate entries per backend and per day
+pub fn compute_stats(entries: &[HistoryEntry]) -> HistoryStats {
+    let mut by_backend: BTreeMap<String, Vec<&HistoryEntry>> = BTreeMap::new();
+    let mut by_day: BTreeMap<NaiveDate, DayStats> = BTreeMap::new();
+
+    for entry in entries {
+        let date = entry.finished_at.with_timezone(&Local).date_naive();
+        let day = by_day.entry(date).or_insert(DayStats {
+            date,
+            runs: 0,
+            input_tokens: 0,
+            output_tokens: 0,
+        });
+        day.runs += 1;
+        day.input_tokens += entry.input_tokens;
+        day.output_tokens += entry.output_tokens;
+
+        if entry.status != "skipped" {
+            let backend = entry.backend.clone().unwrap_or_else(|| "-".to_string());
+            by_backend.entry(backend).or_default().push(entry);
+        }
+    }
+
+    let backends = by_backend
+        .into_iter()
+        .map(|(backend, runs)| {
+            let succeeded = runs.iter().filter(|e| e.status == "success").count();
+            let mut durations: Vec<u64> = runs.iter().map(|e| e.duration_ms).collect();
+            durations.sort_unstable();
+            BackendStats {
+                backend,
+                runs: runs.len(),
+                succeeded,
+                success_rate: succeeded as f64 / runs.len() as f64,
+                p50_ms: percentile(&durations, 50.0),
+                p95_ms: percentile(&durations, 95.0),
+            }
+        })
+        .collect();
+
+    HistoryStats {
+        total: entries.len(),
+        backends,
+        days: by_day.into_values().collect(),
+    }
+}
+
+/// Render entries as a table, newest first
+pub fn format_history(entries: &[HistoryEntry]) -> String {
+    if entries.is_empty() {
+        return "No runs recorded yet.\n".to_string();
+    }
+
+    let mut out = format!(
+        "{:<16} {:<8} {:<9} {:>8} {:>4} {:>9} {:<16} {}\n",
+        "FINISHED", "STATUS", "BACKEND", "DURATION", "EXIT", "TOKENS", "ERROR", "TASK / WORKDIR"
+    );
+    for entry in entries {
+        out.push_str(&format!(
+            "{:<16} {:<8} {:<9} {:>8} {:>4} {:>9} {:<16} {}\n",
+            entry
+                .finished_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M"),
+            entry.status,
+            entry.backend.as_deref().unwrap_or("-"),
+            format_duration(Duration::from_millis(entry.duration_ms)),
+            entry.exit_code,
+            entry.input_tokens + entry.output_tokens,
+            entry.error_kind.as_deref().unwrap_or("-"),
+            entry
+                .task_id
+                .clone()
+                .or_else(|| entry.workdir.as_ref().map(|w| w.display().to_string()))
+                .map(|task| truncate_chars(&task, 40))
+                .unwrap_or_else(|| "-".to_string()),
+        ));
+    }
+    out
+}
+
+/// Render `history --stats`
+pub fn format_stats(stats: &HistoryStats) -> String {
+    if stats.total == 0 {
+        return "No runs recorded yet.\n".to_string();
+    }
+
+    let mut out = format!(
+        "{:<12} {:>6} {:>8} {:>8} {:>8}\n",
+        "BACKEND", "RUNS", "SUCCESS", "P50", "P95"
+    );
+    for backend in &stats.backends {
+        out.push_str(&format!(
+            "{:<12} {:>6} {:>7.1}% {:>8} {:>8}\n",
+            backend.backend,
+            backend.runs,
+            backend.success_rate * 100.0,
+            format_duration(Duration::from_millis(backend.p50_ms)),
+            format_duration(Duration::from_millis(backend.p95_ms)),
+        ));
+    }
+
+    out.push_str(&format!(
+        "\n{:<12} {:>6} {:>14} {:>14}\n",
+        "DAY", "RUNS", "INPUT TOKENS", "OUTPUT TOKENS"
+    ));
+    for day in &stats.days {
+        out.push_str(&format!(
+            "{:<12} {:>6} {:>14} {:>14}\n",
+            day.date.format("%Y-%m-%d"),
+            day.runs,
+            day.input_tokens,
+            day.output_tokens
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::{ErrorDetail, ErrorKind};
+    use crate::parser::TokenUsage;
+
+    fn entry(backend: &str, status: &str, duration_ms: u64, days_ago: i64) -> HistoryEntry {
+        HistoryEntry {
+            finished_at: Utc::now() - chrono::Duration::days(days_ago),
+            task_id: None,
+            status: status.to_string(),
+            backend: Some(backend.to_string()),
+            model: None,
+            agent: None,
+            workdir: None,
+            duration_ms,
+            exit_code: if status == "success" { 0 } else { 4 },
+            input_tokens: 100,
+            output_tokens: 10,
+            files_changed: None,
+            error_kind: None,
+            session_id: None,
+        }
+    }
+
+    #[test]
+    fn test_entry_from_result() {
+        let config = Config {
+            backend: Some("Claude,codex".to_string()),
+            agent: Some("oracle".to_string()),
+            work_dir: PathBuf::from("/src/api"),
+            ..Default::default()
+        };
+        let result = TaskResult {
+            exit_code: 1,
+            duration: Duration::from_millis(1500),
+            usage: Some(TokenUsage {
+                input_tokens: 7,
+                output_tokens: 3,
+            }),
+            error: Some(ErrorDetail::new(ErrorKind::RateLimited, "429")),
+            ..Default::default()
+        };
+
+        let entry = HistoryEntry::new(&result, Some(&config));
+        assert_eq!(entry.status, "failed");
+        assert_eq!(entry.backend.as_deref(), Some("claude"));
+        assert_eq!(entry.agent.as_deref(), Some("oracle"));
+        assert_eq!(entry.duration_ms, 1500);
+        assert_eq!(entry.input_tokens, 7);
+        assert_eq!(entry.error_kind.as_deref(), Some("rate-limited"));
+
+        let skipped = HistoryEntry::new(&TaskResult::skipped("b", "dependency 'a' failed"), None);
+        assert_eq!(skipped.status, "skipped");
+        assert_eq!(skipped.task_id.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn test_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = HistoryStore::new(dir.path().join("history.jsonl"));
+        assert!(store.load().unwrap().is_empty());
+
+        store.append(&entry("codex", "success", 1000, 0)).unwrap();
+        store.append(&entry("claude", "failed", 2000, 0)).unwrap();
+        let entries = store.load().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].backend.as_deref(), Some("claude"));
+    }
+
+    #[test]
+    fn test_filter() {
+        let old = entry("codex", "success", 1000, 10);
+        let recent = entry("claude", "failed", 1000, 0);
+        let filter = HistoryFilter {
+            since: Some(parse_time_bound("--since", "7d", false).unwrap()),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&old));
+        assert!(filter.matches(&recent));
+
+        let filter = HistoryFilter {
+            backend: Some("CODEX".to_string()),
+            status: Some("success".to_string()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&old));
+        assert!(!filter.matches(&recent));
+    }
+
+    #[test]
+    fn test_parse_time_bound() {
+        let start = parse_time_bound("--since", "2026-03-01", false).unwrap();
+        let end = parse_time_bound("--until", "2026-03-01", true).unwrap();
+        assert_eq!(end - start, chrono::Duration::days(1));
+        assert_eq!(
+            parse_time_bound("--since", "2026-03-01T12:00:00Z", false).unwrap(),
+            Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap()
+        );
+        let err = parse_time_bound("--since", "last tuesday", false).unwrap_err();
+        assert!(err.downcast_ref::<ConfigError>().is_some());
+    }
+
+    #[test]
+    fn test_compute_stats() {
+        let mut entries: Vec<HistoryEntry> = (1..=20)
+            .map(|i| entry("codex", "success", i * 1000, 0))
+            .collect();
+        entries.push(entry("claude", "success", 1000, 1));
+        entries.push(entry("claude", "failed", 3000, 1));
+        entries.push(entry("claude", "skipped", 0, 1));
+
+        let stats = compute_stats(&entries);
+        assert_eq!(stats.total, 23);
+
+        let claude = &stats.backends[0];
+        assert_eq!(claude.backend, "claude");
+        assert_eq!(claude.runs, 2);
+        assert_eq!(claude.success_rate, 0.5);
+
+        let codex = &stats.backends[1];
+        assert_eq!(codex.p50_ms, 10_000);
+        assert_eq!(codex.p95_ms, 19_000);
+
+        assert_eq!(stats.days.len(), 2);
+        assert_eq!(stats.days[1].runs, 20);
+        assert_eq!(stats.days[1].input_tokens, 2000);
+    }
+
+    #[test]
+    fn test_percentile() {
+        assert_eq!(percentile(&[], 50.0), 0);
+        assert_eq!(percentile(&[5], 95.0), 5);
+        assert_eq!(percentile(&[1, 2, 3, 4], 50.0), 2);
+    }
+}
@@ -39,7 +39,7 @@ pub async fn run_init(force: bool) -> Result<()> {
 }
 
 /// Get the skill installation directory
-fn get_skill_dir() -> Result<PathBuf> {
+pub fn get_skill_dir() -> Result<PathBuf> {
     let home =
         dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not determine home directory"))?;
     Ok(home.join(".claude").join("skills").join("codeagent"))
@@ -4,70 +4,224 @@
 
 mod agent_config;
 mod backend;
+mod backend_config;
 mod cli;
 mod config;
+mod diagnostics;
+mod doctor;
 mod errors;
 mod executor;
+mod export;
 mod filter;
+mod handoff;
+mod history;
 mod init;
 mod logger;
+mod mock;
 mod parser;
+mod progress;
+mod sessions;
 mod signal;
+mod template;
 mod utils;
 
 use anyhow::Result;
 use clap::Parser;
+use std::path::PathBuf;
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tracing::info;
 
-use crate::backend::select_backend;
-use crate::cli::{Cli, Command};
+use crate::cli::{Cli, Command, SessionsCommand};
 use crate::config::Config;
-use crate::executor::TaskExecutor;
+use crate::diagnostics::{ErrorDetail, ErrorKind};
+use crate::errors::{ConfigError, exit_codes, get_exit_code};
 use crate::logger::setup_logging;
+use crate::sessions::SessionRegistry;
 
 /// Package version from Cargo.toml
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() {
     // Parse CLI arguments
-    let cli = Cli::parse();
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(err) if err.use_stderr() => {
+            let _ = err.print();
+            let rendered = err.render().to_string();
+            let message = rendered.lines().next().unwrap_or_default();
+            let message = message.strip_prefix("error: ").unwrap_or(message);
+            let detail = ErrorDetail::new(ErrorKind::InvalidArgument, message);
+            if let Ok(output) = utils::generate_error_output(&detail, exit_codes::INVALID_ARGUMENT)
+            {
+                println!("{}", output);
+            }
+            std::process::exit(exit_codes::INVALID_ARGUMENT);
+        }
+        // --help and --version
+        Err(err) => err.exit(),
+    };
+
+    let code = match run(&cli).await {
+        Ok(code) => code,
+        Err(err) => report_error(&cli, &err),
+    };
+    std::process::exit(code);
+}
+
+/// Print an error on stderr and as a JSON document on stdout; returns the exit code
+fn report_error(cli: &Cli, err: &anyhow::Error) -> i32 {
+    let code = get_exit_code(err);
+    let detail = ErrorDetail::from_error(err);
+
+    eprintln!("{}", utils::format_error(&detail));
+    if !cli.message_only {
+        // A stopped run still reports what the backend produced
+        let output = if err.is::<executor::PartialResult>() {
+            utils::generate_final_output(&executor::TaskResult {
+                exit_code: code,
+                ..executor::TaskResult::from_error("", err)
+            })
+        } else {
+            utils::generate_error_output(&detail, code)
+        };
+        match output {
+            Ok(output) => println!("{}", output),
+            Err(e) => eprintln!("Failed to render error output: {}", e),
+        }
+    }
+
+    code
+}
+
+/// Dispatch the command line; returns the process exit code
+async fn run(cli: &Cli) -> Result<i32> {
+    // The replay process stands in for a backend, so it must not log like the wrapper
+    if let Some(Command::MockReplay {
+        file,
+        delay_ms,
+        exit_code,
+        stderr,
+        session,
+        attempt,
+        prompt,
+    }) = &cli.command
+    {
+        return mock::run_replay(mock::ReplayOptions {
+            file: file.as_ref().map(Into::into),
+            delay_ms: *delay_ms,
+            exit_code: *exit_code,
+            stderr: stderr.clone(),
+            session: session.clone(),
+            attempt: *attempt,
+            prompt: prompt.clone(),
+        })
+        .await;
+    }
 
     // Setup logging
-    let _guard = setup_logging(&cli)?;
+    let _guard = setup_logging(cli)?;
 
     info!(version = VERSION, "codeagent-wrapper starting");
 
     // Handle subcommands
     match &cli.command {
+        Some(Command::Sessions { action }) => run_sessions(action),
+        Some(Command::Doctor { json }) => doctor::run_doctor(*json).await,
+        Some(Command::History {
+            since,
+            until,
+            backend,
+            status,
+            limit,
+            stats,
+            json,
+        }) => {
+            let filter = history::HistoryFilter {
+                since: since
+                    .as_deref()
+                    .map(|s| history::parse_time_bound("--since", s, false))
+                    .transpose()?,
+                until: until
+                    .as_deref()
+                    .map(|s| history::parse_time_bound("--until", s, true))
+                    .transpose()?,
+                backend: backend.clone(),
+                status: status.clone(),
+            };
+            run_history(&filter, *limit, *stats, *json)
+        }
+        Some(Command::Export {
+            reference,
+            format,
+            output,
+        }) => export::run_export(
+            reference,
+            *format,
+            output.as_deref().map(std::path::Path::new),
+        ),
+        Some(Command::MockReplay { .. }) => unreachable!("handled before logging is set up"),
         Some(Command::Init { force }) => {
             init::run_init(*force).await?;
-            return Ok(());
+            Ok(exit_codes::SUCCESS)
         }
         Some(Command::Resume {
             session_id,
             task,
             workdir,
         }) => {
+            let record = SessionRegistry::open_default().resolve(session_id)?;
             // Handle "-" as stdin marker for resume mode
             let actual_task = if task == "-" {
                 read_stdin_task().await?
             } else {
                 task.clone()
             };
-            let config = Config::from_resume(&cli, session_id, &actual_task, workdir.as_deref())?;
-            run_task(config).await?;
+            let session_id = record.as_ref().map_or(session_id, |r| &r.id);
+            let mut config =
+                Config::from_resume(cli, session_id, &actual_task, workdir.as_deref())?;
+            if let Some(ref record) = record {
+                config.apply_session_record(record, workdir.is_some())?;
+            }
+            run_task(config).await
+        }
+        Some(Command::Handoff {
+            session,
+            to,
+            task,
+            workdir,
+            budget,
+        }) => {
+            let registry = SessionRegistry::open_default();
+            let record = registry
+                .resolve(session)?
+                .ok_or_else(|| ConfigError::UnknownSession(session.clone()))?;
+            let actual_task = if task == "-" {
+                read_stdin_task().await?
+            } else {
+                task.clone()
+            };
+            let turns = registry.transcript(&record.id)?;
+
+            let mut config = Config::from_cli(cli, &actual_task)?;
+            config.backend = Some(to.clone());
+            config.work_dir = workdir
+                .as_ref()
+                .map(PathBuf::from)
+                .unwrap_or_else(|| record.workdir.clone());
+            config.handoff_from = Some(record.id.clone());
+            config.handoff_context = Some(handoff::build_context(&record, &turns, *budget));
+            run_task(config).await
         }
         None => {
             // Check for special modes
             if cli.cleanup {
                 logger::cleanup_old_logs().await?;
-                return Ok(());
+                return Ok(exit_codes::SUCCESS);
             }
 
             if cli.parallel {
-                run_parallel(&cli).await?;
+                run_parallel(cli).await
             } else if let Some(ref task) = cli.task {
                 // Handle "-" as stdin marker
                 let actual_task = if task == "-" {
@@ -75,39 +229,42 @@ async fn main() -> Result<()> {
                 } else {
                     task.clone()
                 };
-                let config = Config::from_cli(&cli, &actual_task)?;
-                run_task(config).await?;
+                let config = Config::from_cli(cli, &actual_task)?;
+                run_task(config).await
             } else {
                 // Print help if no task provided
                 use clap::CommandFactory;
                 Cli::command().print_help()?;
-                std::process::exit(1);
+                Ok(exit_codes::INVALID_ARGUMENT)
             }
         }
     }
-
-    Ok(())
 }
 
 /// Run a single task
-async fn run_task(config: Config) -> Result<()> {
-    let backend = select_backend(config.backend.as_deref())?;
-    let executor = TaskExecutor::new(backend, &config)?;
-    let result = executor.run().await?;
+async fn run_task(mut config: Config) -> Result<i32> {
+    config.resolve_agent().await?;
+    let result = executor::execute(config.clone()).await?;
 
     // Generate and print final output
-    let output = utils::generate_final_output(&result)?;
-    println!("{}", output);
+    if config.message_only {
+        println!("{}", result.message.as_deref().unwrap_or_default());
+    } else {
+        let output = utils::generate_final_output(&result)?;
+        println!("{}", output);
+    }
 
-    if !result.success {
-        std::process::exit(1);
+    if !config.quiet
+        && let Some(ref error) = result.error
+    {
+        eprintln!("{}", utils::format_error(error));
     }
 
-    Ok(())
+    Ok(result.process_exit_code())
 }
 
 /// Run tasks in parallel mode
-async fn run_parallel(cli: &Cli) -> Result<()> {
+async fn run_parallel(cli: &Cli) -> Result<i32> {
     use crate::config::parse_parallel_config;
     use crate::executor::run_parallel_tasks;
 
@@ -115,15 +272,94 @@ async fn run_parallel(cli: &Cli) -> Result<()> {
     let results = run_parallel_tasks(cli, parallel_config).await?;
 
     // Generate and print final output
-    let output = utils::generate_parallel_output(&results)?;
-    println!("{}", output);
+    if cli.message_only {
+        print!("{}", utils::generate_parallel_messages(&results));
+    } else {
+        let output = utils::generate_parallel_output(&results)?;
+        println!("{}", output);
+    }
+
+    // Skipped tasks only fail because of another task, so report the first real failure
+    Ok(results
+        .iter()
+        .find(|r| r.status() == "failed")
+        .map(|r| r.process_exit_code())
+        .unwrap_or(exit_codes::SUCCESS))
+}
+
+/// Handle `sessions list|show|rm`
+fn run_sessions(action: &SessionsCommand) -> Result<i32> {
+    let registry = SessionRegistry::open_default();
+    let find = |reference: &str| {
+        registry
+            .resolve(reference)?
+            .ok_or_else(|| anyhow::Error::from(ConfigError::UnknownSession(reference.to_string())))
+    };
 
-    let all_success = results.iter().all(|r| r.success);
-    if !all_success {
-        std::process::exit(1);
+    match action {
+        SessionsCommand::List { limit, json } => {
+            let mut records = registry.list()?;
+            records.truncate(*limit);
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&records)?);
+            } else {
+                print!("{}", sessions::format_session_list(&records));
+            }
+        }
+        SessionsCommand::Show { session, json } => {
+            let record = find(session)?;
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&record)?);
+            } else {
+                print!("{}", sessions::format_session(&record));
+            }
+        }
+        SessionsCommand::Rm { sessions } => {
+            // Resolve everything first so a typo removes nothing
+            let records = sessions
+                .iter()
+                .map(|reference| find(reference))
+                .collect::<Result<Vec<_>>>()?;
+            for record in records {
+                registry.remove(&record.id)?;
+                println!("Removed session {}", record.id);
+            }
+        }
+    }
+
+    Ok(exit_codes::SUCCESS)
+}
+
+/// Handle `history`
+fn run_history(
+    filter: &history::HistoryFilter,
+    limit: usize,
+    stats: bool,
+    json: bool,
+) -> Result<i32> {
+    let entries: Vec<_> = history::HistoryStore::open_default()
+        .load()?
+        .into_iter()
+        .filter(|entry| filter.matches(entry))
+        .collect();
+
+    if stats {
+        let stats = history::compute_stats(&entries);
+        if json {
+            println!("{}", serde_json::to_string_pretty(&stats)?);
+        } else {
+            print!("{}", history::format_stats(&stats));
+        }
+    } else {
+        let recent: Vec<_> = entries.into_iter().rev().take(limit).collect();
+        if json {
+            println!("{}", serde_json::to_string_pretty(&recent)?);
+        } else {
+            print!("{}", history::format_history(&recent));
+        }
     }
 
-    Ok(())
+    Ok(exit_codes::SUCCESS)
 }
 
 /// Read task content from stdin
@@ -139,7 +375,7 @@ async fn read_stdin_task() -> Result<String> {
 
     let task = content.join("\n");
     if task.trim().is_empty() {
-        anyhow::bail!("No task provided via stdin");
+        return Err(ConfigError::MissingParameter("task (stdin was empty)".to_string()).into());
     }
 
     Ok(task)
@@ -0,0 +1,433 @@
+//! Mock backend support: replaying and recording event streams
+//!
+//! The `mock` backend runs this binary's hidden `mock-replay` command as the
+//! backend process, so replays go through the same spawn, stderr, timeout and
+//! process-group handling as a real CLI. Replay files are JSONL: every line is
+//! printed to stdout as a backend event, except control lines such as
+//! `{"mock": "sleep", "ms": 500}`, `{"mock": "stderr", "line": "..."}` and
+//! `{"mock": "exit", "code": 1}`. `--record` writes the same format, starting
+//! every attempt of a task with `{"mock": "attempt", "number": 2, "backend": "codex"}`;
+//! a replay plays one attempt, the last unless `--mock-attempt` picks another.
+
+use anyhow::Result;
+use serde_json::{Value, json};
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+use tracing::warn;
+
+use crate::cli::Cli;
+use crate::errors::ConfigError;
+
+/// Session ID reported when no replay file is given
+pub const MOCK_SESSION_ID: &str = "mock-session";
+
+/// One step of a replay file
+#[derive(Debug, Clone, PartialEq)]
+enum ReplayStep {
+    /// Print a line to stdout
+    Event(String),
+    /// Pause for the given number of milliseconds
+    Sleep(u64),
+    /// Print a line to stderr
+    Stderr(String),
+    /// Exit with the given code once replay ends
+    Exit(i32),
+    /// Start of a recorded attempt
+    Attempt(u32),
+}
+
+/// Parse one line of a replay file
+fn parse_step(line: &str) -> ReplayStep {
+    let Ok(value) = serde_json::from_str::<Value>(line) else {
+        return ReplayStep::Event(line.to_string());
+    };
+    let Some(directive) = value.get("mock").and_then(|m| m.as_str()) else {
+        return ReplayStep::Event(line.to_string());
+    };
+
+    match directive {
+        "sleep" => ReplayStep::Sleep(value.get("ms").and_then(|v| v.as_u64()).unwrap_or(0)),
+        "stderr" => ReplayStep::Stderr(
+            value
+                .get("line")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string(),
+        ),
+        "exit" => ReplayStep::Exit(value.get("code").and_then(|v| v.as_i64()).unwrap_or(0) as i32),
+        "attempt" => {
+            ReplayStep::Attempt(value.get("number").and_then(|v| v.as_u64()).unwrap_or(0) as u32)
+        }
+        _ => ReplayStep::Event(line.to_string()),
+    }
+}
+
+/// Events for a run without a replay file: echo the prompt as the answer
+fn echo_steps(prompt: &str, session: Option<&str>) -> Vec<ReplayStep> {
+    let session = session.unwrap_or(MOCK_SESSION_ID);
+    [
+        json!({"type": "system", "subtype": "init", "session_id": session}),
+        json!({"type": "assistant", "message": {"content": [{"type": "text", "text": prompt}]}}),
+        json!({"type": "result", "subtype": "success", "result": prompt, "session_id": session,
+            "usage": {"input_tokens": 0, "output_tokens": 0}}),
+    ]
+    .iter()
+    .map(|event| ReplayStep::Event(event.to_string()))
+    .collect()
+}
+
+/// How the mock backend replays for a run (`--mock-*`, or `CODEAGENT_MOCK_*`)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MockOptions {
+    /// Replay file; without one the prompt is echoed back
+    pub file: Option<PathBuf>,
+    /// Pause before every event
+    pub delay_ms: Option<u64>,
+    /// Exit code, overriding any `exit` line in the file
+    pub exit_code: Option<i32>,
+    /// Line printed to stderr before replay starts
+    pub stderr: Option<String>,
+    /// Recorded attempt to replay (1-based); the last one when unset
+    pub attempt: Option<u32>,
+}
+
+impl MockOptions {
+    /// Options from the command line
+    pub fn from_cli(cli: &Cli) -> Self {
+        Self {
+            file: cli.mock_file.as_deref().and_then(resolve_file),
+            delay_ms: cli.mock_delay_ms,
+            exit_code: cli.mock_exit_code,
+            stderr: cli.mock_stderr.clone(),
+            attempt: cli.mock_attempt,
+        }
+    }
+
+    /// Use a task's own replay file instead of the command line's
+    pub fn with_file(self, file: Option<&str>) -> Self {
+        match file.and_then(resolve_file) {
+            Some(file) => Self {
+                file: Some(file),
+                ..self
+            },
+            None => self,
+        }
+    }
+
+    /// Arguments for `mock-replay`
+    pub fn replay_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(ref file) = self.file {
+            args.extend(["--file".to_string(), file.display().to_string()]);
+        }
+        if let Some(delay_ms) = self.delay_ms {
+            args.extend(["--delay-ms".to_string(), delay_ms.to_string()]);
+        }
+        if let Some(exit_code) = self.exit_code {
+            args.extend(["--exit-code".to_string(), exit_code.to_string()]);
+        }
+        if let Some(ref stderr) = self.stderr {
+            args.extend(["--stderr".to_string(), stderr.clone()]);
+        }
+        if let Some(attempt) = self.attempt {
+            args.extend(["--attempt".to_string(), attempt.to_string()]);
+        }
+        args
+    }
+}
+
+/// Resolve a replay file against the wrapper's directory, since the backend
+/// runs in the task's workdir
+fn resolve_file(file: &str) -> Option<PathBuf> {
+    (!file.is_empty()).then(|| std::env::current_dir().unwrap_or_default().join(file))
+}
+
+/// Options of the hidden `mock-replay` command
+#[derive(Debug, Clone, Default)]
+pub struct ReplayOptions {
+    /// Replay file (echo the prompt when absent)
+    pub file: Option<PathBuf>,
+    /// Pause before every event
+    pub delay_ms: u64,
+    /// Exit code, overriding any `exit` line in the file
+    pub exit_code: Option<i32>,
+    /// Lines printed to stderr before replay starts
+    pub stderr: Vec<String>,
+    /// Session ID for the echo replay
+    pub session: Option<String>,
+    /// Recorded attempt to replay (1-based); the last one when unset
+    pub attempt: Option<u32>,
+    /// Prompt, or `-` to read it from stdin
+    pub prompt: String,
+}
+
+/// Replay an event stream as a backend process would; returns the exit code
+pub async fn run_replay(options: ReplayOptions) -> Result<i32> {
+    let prompt = if options.prompt == "-" {
+        let mut input = String::new();
+        std::io::stdin().read_to_string(&mut input)?;
+        input
+    } else {
+        options.prompt.clone()
+    };
+
+    let steps = match options.file {
+        Some(ref file) => {
+            let steps = std::fs::read_to_string(file)
+                .map_err(|e| ConfigError::FileNotFound(format!("{}: {}", file.display(), e)))?
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(parse_step)
+                .collect();
+            select_attempt(steps, options.attempt)
+        }
+        None => echo_steps(&prompt, options.session.as_deref()),
+    };
+
+    for line in &options.stderr {
+        eprintln!("{}", line);
+    }
+
+    let mut exit_code = 0;
+    let mut stdout = std::io::stdout();
+    for step in steps {
+        match step {
+            ReplayStep::Event(line) => {
+                if options.delay_ms > 0 {
+                    tokio::time::sleep(Duration::from_millis(options.delay_ms)).await;
+                }
+                writeln!(stdout, "{}", line)?;
+                stdout.flush()?;
+            }
+            ReplayStep::Sleep(ms) => tokio::time::sleep(Duration::from_millis(ms)).await,
+            ReplayStep::Stderr(line) => eprintln!("{}", line),
+            ReplayStep::Exit(code) => exit_code = code,
+            ReplayStep::Attempt(_) => {}
+        }
+    }
+
+    Ok(options.exit_code.unwrap_or(exit_code))
+}
+
+/// Steps of one recorded attempt (1-based, the last one when `attempt` is
+/// unset or past the end); a file without attempt lines is a single attempt
+fn select_attempt(steps: Vec<ReplayStep>, attempt: Option<u32>) -> Vec<ReplayStep> {
+    let mut attempts: Vec<Vec<ReplayStep>> = vec![Vec::new()];
+    for step in steps {
+        if matches!(step, ReplayStep::Attempt(_)) {
+            attempts.push(Vec::new());
+        }
+        attempts.last_mut().unwrap().push(step);
+    }
+    // Lines before the first attempt line only count when there are any
+    if attempts[0].is_empty() && attempts.len() > 1 {
+        attempts.remove(0);
+    }
+
+    let idx = attempt
+        .map(|n| (n.max(1) as usize - 1).min(attempts.len() - 1))
+        .unwrap_or(attempts.len() - 1);
+    attempts.swap_remove(idx)
+}
+
+/// Writes a task's raw events, stderr and exit code as a replay file, one
+/// section per attempt
+#[derive(Debug)]
+pub struct Recorder {
+    path: PathBuf,
+    writer: Mutex<BufWriter<std::fs::File>>,
+    attempts: AtomicU32,
+}
+
+impl Recorder {
+    /// Create (or truncate) the replay file
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = std::fs::File::create(path).map_err(|e| {
+            ConfigError::InvalidFilePath(format!("--record {}: {}", path.display(), e))
+        })?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer: Mutex::new(BufWriter::new(file)),
+            attempts: AtomicU32::new(0),
+        })
+    }
+
+    /// Start recording the next attempt, run by `backend`
+    pub fn start_attempt(&self, backend: &str) {
+        let number = self.attempts.fetch_add(1, Ordering::SeqCst) + 1;
+        self.write_line(
+            &json!({"mock": "attempt", "number": number, "backend": backend}).to_string(),
+        );
+    }
+
+    /// Record one raw backend event
+    pub fn event(&self, value: &Value) {
+        self.write_line(&value.to_string());
+    }
+
+    /// Record one stderr line
+    pub fn stderr(&self, line: &str) {
+        let line = line.trim_end_matches(['\n', '\r']);
+        self.write_line(&json!({"mock": "stderr", "line": line}).to_string());
+    }
+
+    /// Record the exit code and flush the file
+    pub fn finish(&self, exit_code: i32) {
+        self.write_line(&json!({"mock": "exit", "code": exit_code}).to_string());
+        if let Ok(mut writer) = self.writer.lock()
+            && let Err(e) = writer.flush()
+        {
+            warn!(path = %self.path.display(), error = %e, "Failed to write recording");
+        }
+    }
+
+    fn write_line(&self, line: &str) {
+        if let Ok(mut writer) = self.writer.lock()
+            && let Err(e) = writeln!(writer, "{}", line)
+        {
+            warn!(path = %self.path.display(), error = %e, "Failed to write recording");
+        }
+    }
+}
+
+/// Per-task recording path in parallel mode: `run.jsonl` -> `run.<task-id>.jsonl`
+///
+/// Characters outside `[A-Za-z0-9._-]` become `_`, so an ID such as
+/// `../x` or `a/b` cannot point outside the recording's directory.
+pub fn task_record_path(path: &Path, task_id: &str) -> PathBuf {
+    let task_id: String = task_id
+        .chars()
+        .map(|c| match c {
+            'A'..='Z' | 'a'..='z' | '0'..='9' | '.' | '_' | '-' => c,
+            _ => '_',
+        })
+        .collect();
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, task_id, ext.to_string_lossy()),
+        None => format!("{}.{}", stem, task_id),
+    };
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_step() {
+        assert_eq!(
+            parse_step(r#"{"type":"result"}"#),
+            ReplayStep::Event(r#"{"type":"result"}"#.to_string())
+        );
+        assert_eq!(
+            parse_step(r#"{"mock":"sleep","ms":250}"#),
+            ReplayStep::Sleep(250)
+        );
+        assert_eq!(
+            parse_step(r#"{"mock":"stderr","line":"Error: 429"}"#),
+            ReplayStep::Stderr("Error: 429".to_string())
+        );
+        assert_eq!(
+            parse_step(r#"{"mock":"exit","code":3}"#),
+            ReplayStep::Exit(3)
+        );
+        assert_eq!(
+            parse_step("not json"),
+            ReplayStep::Event("not json".to_string())
+        );
+    }
+
+    #[test]
+    fn test_recorder_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.jsonl");
+
+        let recorder = Recorder::create(&path).unwrap();
+        recorder.event(&json!({"type": "thread.started", "thread_id": "t1"}));
+        recorder.stderr("warning: slow\n");
+        recorder.finish(1);
+
+        let steps: Vec<ReplayStep> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(parse_step)
+            .collect();
+        assert!(matches!(&steps[0], ReplayStep::Event(line) if line.contains("t1")));
+        assert_eq!(steps[1], ReplayStep::Stderr("warning: slow".to_string()));
+        assert_eq!(steps[2], ReplayStep::Exit(1));
+    }
+
+    #[test]
+    fn test_recorder_keeps_every_attempt() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.jsonl");
+
+        let recorder = Recorder::create(&path).unwrap();
+        recorder.start_attempt("claude");
+        recorder.stderr("Error: 429 Too Many Requests");
+        recorder.finish(1);
+        recorder.start_attempt("codex");
+        recorder.event(&json!({"type": "thread.started", "thread_id": "t2"}));
+        recorder.finish(0);
+
+        let steps: Vec<ReplayStep> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(parse_step)
+            .collect();
+        assert_eq!(steps.len(), 6);
+
+        let first = select_attempt(steps.clone(), Some(1));
+        assert_eq!(first[0], ReplayStep::Attempt(1));
+        assert_eq!(first[2], ReplayStep::Exit(1));
+        let last = select_attempt(steps.clone(), None);
+        assert_eq!(last[0], ReplayStep::Attempt(2));
+        assert_eq!(last[2], ReplayStep::Exit(0));
+        assert_eq!(select_attempt(steps, Some(9)), last);
+
+        // A file without attempt lines is a single attempt
+        let plain = vec![ReplayStep::Sleep(1), ReplayStep::Exit(2)];
+        assert_eq!(select_attempt(plain.clone(), Some(2)), plain);
+    }
+
+    #[test]
+    fn test_mock_options_replay_args() {
+        let options = MockOptions {
+            file: Some(PathBuf::from("/tmp/run.jsonl")),
+            exit_code: Some(-1),
+            ..Default::default()
+        };
+        assert_eq!(
+            options.replay_args(),
+            vec!["--file", "/tmp/run.jsonl", "--exit-code", "-1"]
+        );
+
+        let options = options.with_file(Some("/tmp/task.jsonl"));
+        assert_eq!(options.file, Some(PathBuf::from("/tmp/task.jsonl")));
+        assert_eq!(options.clone().with_file(None), options);
+        assert!(MockOptions::default().replay_args().is_empty());
+    }
+
+    #[test]
+    fn test_task_record_path() {
+        assert_eq!(
+            task_record_path(Path::new("/tmp/run.jsonl"), "build"),
+            PathBuf::from("/tmp/run.build.jsonl")
+        );
+        assert_eq!(
+            task_record_path(Path::new("run"), "a"),
+            PathBuf::from("run.a")
+        );
+        assert_eq!(
+            task_record_path(Path::new("/tmp/run.jsonl"), "../../etc/x"),
+            PathBuf::from("/tmp/run..._.._etc_x.jsonl")
+        );
+    }
+}
@@ -1,9 +1,9 @@
 //! JSON stream parser for backend output
 //!
-//! Backend type detection and progress parsing are reserved for future output processing.
-
-#![allow(dead_code)] // Reserved API: backend type detection for enhanced output processing
+//! Raw backend JSON is translated into the normalized [`AgentEvent`] model by
+//! each backend's translator (see `backend.rs`).
 
+use serde::{Deserialize, Serialize};
 use tokio::io::{AsyncBufRead, AsyncBufReadExt};
 use tracing::trace;
 
@@ -73,6 +73,7 @@ pub enum ParseError {
     #[error("Message too large: {0} bytes (max: {1})")]
     MessageTooLarge(usize, usize),
 
+    #[allow(dead_code)] // Invalid lines are skipped rather than reported
     #[error("Invalid JSON: {0}")]
     InvalidJson(String),
 
@@ -92,21 +93,35 @@ pub enum BackendType {
 
 /// Detect backend type from JSON event structure
 pub fn detect_backend_type(value: &serde_json::Value) -> BackendType {
-    // Claude format: has "type" field
-    if value.get("type").is_some() {
-        return BackendType::Claude;
-    }
+    let event_type = value.get("type").and_then(|t| t.as_str());
 
-    // Codex format: has "event" field
-    if value.get("event").is_some() {
+    // Codex format: thread_id, typed item, or legacy "event" field
+    if value.get("thread_id").is_some()
+        || value.pointer("/item/type").is_some()
+        || value.get("event").is_some()
+    {
         return BackendType::Codex;
     }
 
-    // Gemini format: has "candidates" field
-    if value.get("candidates").is_some() {
+    // Opencode format: camelCase sessionID
+    if value.get("sessionID").is_some() {
+        return BackendType::Opencode;
+    }
+
+    // Gemini format: candidates, role/delta messages, or init with session_id
+    if value.get("candidates").is_some()
+        || value.get("role").is_some()
+        || value.get("delta").is_some()
+        || (event_type == Some("init") && value.get("session_id").is_some())
+    {
         return BackendType::Gemini;
     }
 
+    // Claude format: has "type" field
+    if event_type.is_some() {
+        return BackendType::Claude;
+    }
+
     // Opencode format: has "message" object
     if value.get("message").is_some_and(|m| m.is_object()) {
         return BackendType::Opencode;
@@ -115,24 +130,114 @@ pub fn detect_backend_type(value: &serde_json::Value) -> BackendType {
     BackendType::Unknown
 }
 
-/// Check if event indicates progress
-pub fn is_progress_event(value: &serde_json::Value, backend_type: BackendType) -> bool {
-    match backend_type {
-        BackendType::Claude => value
-            .get("type")
-            .and_then(|t| t.as_str())
-            .is_some_and(|t| t == "assistant" || t == "content_block_delta" || t == "tool_use"),
-        BackendType::Codex => value
-            .get("event")
-            .and_then(|e| e.as_str())
-            .is_some_and(|e| e == "message" || e == "tool_call"),
-        BackendType::Gemini => value.get("candidates").is_some(),
-        BackendType::Opencode => value
-            .get("type")
-            .and_then(|t| t.as_str())
-            .is_some_and(|t| t == "message" || t == "tool_use"),
-        BackendType::Unknown => false,
+/// Normalized event produced by a backend's translator
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentEvent {
+    /// Backend reported its session/thread ID
+    SessionStarted { session_id: String },
+    /// Assistant text; `delta` chunks are appended to the current message
+    AssistantText { text: String, delta: bool },
+    /// Reasoning/thinking text
+    Thinking { text: String },
+    /// Tool invocation
+    ToolCall {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        name: String,
+        input: serde_json::Value,
+    },
+    /// Tool output
+    ToolResult {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        output: String,
+        is_error: bool,
+    },
+    /// Token usage report
+    Usage {
+        input_tokens: u64,
+        output_tokens: u64,
+    },
+    /// Error reported by the backend
+    Error { message: String },
+    /// The backend finished its turn
+    Completed {
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        result: Option<String>,
+    },
+}
+
+/// Aggregated token usage for a run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+impl TokenUsage {
+    /// Add a usage report
+    pub fn add(&mut self, input_tokens: u64, output_tokens: u64) {
+        self.input_tokens += input_tokens;
+        self.output_tokens += output_tokens;
+    }
+}
+
+/// Accumulates the final assistant message across a stream of events
+#[derive(Debug, Default)]
+pub struct MessageAccumulator {
+    message: String,
+    /// Set when a tool call interrupts streamed chunks, so the next chunk starts fresh
+    interrupted: bool,
+}
+
+impl MessageAccumulator {
+    /// Feed one event
+    pub fn push(&mut self, event: &AgentEvent) {
+        match event {
+            AgentEvent::AssistantText { text, delta: false } => {
+                self.message = text.clone();
+                self.interrupted = false;
+            }
+            AgentEvent::AssistantText { text, delta: true } => {
+                if self.interrupted {
+                    self.message.clear();
+                    self.interrupted = false;
+                }
+                self.message.push_str(text);
+            }
+            AgentEvent::Completed {
+                result: Some(result),
+                ..
+            } if !result.is_empty() => {
+                self.message = result.clone();
+                self.interrupted = false;
+            }
+            AgentEvent::ToolCall { .. } | AgentEvent::ToolResult { .. } => {
+                self.interrupted = true;
+            }
+            _ => {}
+        }
+    }
+
+    /// The final message, if any text was seen
+    pub fn finish(self) -> Option<String> {
+        let message = self.message.trim();
+        (!message.is_empty()).then(|| message.to_string())
+    }
+}
+
+/// Extract the final assistant message from normalized events
+pub fn extract_final_message(events: &[AgentEvent]) -> Option<String> {
+    let mut accumulator = MessageAccumulator::default();
+    for event in events {
+        accumulator.push(event);
     }
+    accumulator.finish()
 }
 
 #[cfg(test)]
@@ -174,11 +279,80 @@ not json
     }
 
     #[test]
-    fn test_is_progress_event() {
-        let claude = serde_json::json!({"type": "assistant"});
-        assert!(is_progress_event(&claude, BackendType::Claude));
+    fn test_detect_backend_type_node_heuristics() {
+        let codex = serde_json::json!({"type": "thread.started", "thread_id": "t"});
+        assert_eq!(detect_backend_type(&codex), BackendType::Codex);
 
-        let codex = serde_json::json!({"event": "message"});
-        assert!(is_progress_event(&codex, BackendType::Codex));
+        let opencode = serde_json::json!({"type": "text", "sessionID": "s", "part": {}});
+        assert_eq!(detect_backend_type(&opencode), BackendType::Opencode);
+
+        let gemini = serde_json::json!({"type": "init", "session_id": "s"});
+        assert_eq!(detect_backend_type(&gemini), BackendType::Gemini);
+
+        let claude = serde_json::json!({"type": "assistant", "message": {"content": []}});
+        assert_eq!(detect_backend_type(&claude), BackendType::Claude);
+    }
+
+    #[test]
+    fn test_message_accumulator() {
+        let events = vec![
+            AgentEvent::AssistantText {
+                text: "Reading".to_string(),
+                delta: true,
+            },
+            AgentEvent::ToolCall {
+                id: None,
+                name: "read_file".to_string(),
+                input: serde_json::Value::Null,
+            },
+            AgentEvent::AssistantText {
+                text: "Hello ".to_string(),
+                delta: true,
+            },
+            AgentEvent::AssistantText {
+                text: "world".to_string(),
+                delta: true,
+            },
+            AgentEvent::Usage {
+                input_tokens: 1,
+                output_tokens: 2,
+            },
+            AgentEvent::Completed {
+                success: true,
+                result: None,
+            },
+        ];
+        assert_eq!(
+            extract_final_message(&events),
+            Some("Hello world".to_string())
+        );
+    }
+
+    #[test]
+    fn test_message_accumulator_prefers_completed_result() {
+        let events = vec![
+            AgentEvent::AssistantText {
+                text: "Draft".to_string(),
+                delta: false,
+            },
+            AgentEvent::Completed {
+                success: true,
+                result: Some("Final".to_string()),
+            },
+        ];
+        assert_eq!(extract_final_message(&events), Some("Final".to_string()));
+    }
+
+    #[test]
+    fn test_agent_event_serialization() {
+        let event = AgentEvent::ToolCall {
+            id: None,
+            name: "Bash".to_string(),
+            input: serde_json::json!({"command": "ls"}),
+        };
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(value["type"], "tool_call");
+        assert_eq!(value["name"], "Bash");
+        assert!(value.get("id").is_none());
     }
 }
@@ -0,0 +1,388 @@
+//! Live progress reporting on stderr
+//!
+//! On a terminal the progress line is redrawn in place (and refreshed every
+//! second so the elapsed time keeps moving); otherwise, in parallel mode and
+//! with `--backend-output`, one line is printed per stage or tool change.
+
+use std::io::{IsTerminal, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+use crate::config::Config;
+use crate::parser::AgentEvent;
+use crate::utils::{format_duration, format_progress_message};
+
+/// Current stage of a running task
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Starting,
+    Thinking,
+    ToolCall,
+    Editing,
+    Testing,
+    Responding,
+    Error,
+}
+
+impl Stage {
+    /// Human-readable stage label
+    pub fn label(self) -> &'static str {
+        match self {
+            Stage::Starting => "starting",
+            Stage::Thinking => "thinking",
+            Stage::ToolCall => "tool call",
+            Stage::Editing => "editing",
+            Stage::Testing => "testing",
+            Stage::Responding => "responding",
+            Stage::Error => "error",
+        }
+    }
+}
+
+/// Tool names that modify files
+pub const EDIT_TOOLS: &[&str] = &[
+    "edit",
+    "multiedit",
+    "write",
+    "write_file",
+    "replace",
+    "apply_patch",
+    "file_change",
+    "notebookedit",
+];
+
+/// Programs that run tests
+const TEST_RUNNERS: &[&str] = &["pytest", "jest", "vitest", "mocha", "rspec", "phpunit"];
+
+/// Tools with a `test` subcommand (`cargo test`, `npm test`, `make test`)
+const TEST_SUBCOMMAND_TOOLS: &[&str] = &[
+    "cargo", "go", "npm", "pnpm", "yarn", "bun", "deno", "make", "dotnet", "mix", "mvn", "gradle",
+    "gradlew", "swift", "dart", "flutter",
+];
+
+/// Package managers that run scripts with `run` (`npm run test:unit`)
+const SCRIPT_RUNNERS: &[&str] = &["npm", "pnpm", "yarn", "bun"];
+
+/// Whether a shell command runs tests
+///
+/// Compares whole words: a test runner as any word (`npx jest`,
+/// `.venv/bin/pytest`), or `test` as the subcommand right after a known tool
+/// (`cargo test`, `npm test`, `npm run test:unit`). Other arguments named
+/// `test` (`cat test`, `git add test`) and the `test` builtin do not count.
+fn is_test_command(command: &str) -> bool {
+    command.split([';', '&', '|', '\n']).any(|segment| {
+        let words: Vec<&str> = segment
+            .split(|c: char| c.is_whitespace() || "\"'[](),".contains(c))
+            .filter(|word| !word.is_empty())
+            .map(|word| word.rsplit('/').next().unwrap_or(word))
+            .collect();
+        words.iter().enumerate().any(|(index, word)| {
+            if TEST_RUNNERS.contains(word) {
+                return true;
+            }
+            if word.split(':').next() != Some("test") {
+                return false;
+            }
+            index > 0
+                && (TEST_SUBCOMMAND_TOOLS.contains(&words[index - 1])
+                    || (index > 1
+                        && words[index - 1] == "run"
+                        && SCRIPT_RUNNERS.contains(&words[index - 2])))
+        })
+    })
+}
+
+/// Classify a tool call into a stage
+pub fn classify_tool(name: &str, input: &serde_json::Value) -> Stage {
+    let lower = name.to_lowercase();
+    if EDIT_TOOLS.contains(&lower.as_str()) {
+        return Stage::Editing;
+    }
+
+    let command = input
+        .get("command")
+        .map(|c| match c {
+            serde_json::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+        .unwrap_or_default()
+        .to_lowercase();
+    if is_test_command(&command) {
+        return Stage::Testing;
+    }
+
+    Stage::ToolCall
+}
+
+/// Snapshot of a task's progress
+#[derive(Debug, Clone)]
+pub struct ProgressState {
+    /// Task label (task ID in parallel mode)
+    pub label: Option<String>,
+    /// When the task started
+    pub started: Instant,
+    /// Current stage
+    pub stage: Stage,
+    /// Last tool name
+    pub tool: Option<String>,
+    /// Last assistant or thinking text
+    pub message: Option<String>,
+}
+
+impl ProgressState {
+    /// Create the initial state
+    pub fn new(label: Option<String>) -> Self {
+        Self {
+            label,
+            started: Instant::now(),
+            stage: Stage::Starting,
+            tool: None,
+            message: None,
+        }
+    }
+
+    /// Elapsed time since start
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Apply an event; returns true if the stage or tool changed
+    pub fn update(&mut self, event: &AgentEvent) -> bool {
+        let before = (self.stage, self.tool.clone());
+
+        match event {
+            AgentEvent::Thinking { text } => {
+                self.stage = Stage::Thinking;
+                self.message = Some(text.clone());
+            }
+            AgentEvent::AssistantText { text, delta } => {
+                self.stage = Stage::Responding;
+                match (&mut self.message, delta) {
+                    (Some(message), true) if before.0 == Stage::Responding => {
+                        message.push_str(text)
+                    }
+                    _ => self.message = Some(text.clone()),
+                }
+            }
+            AgentEvent::ToolCall { name, input, .. } => {
+                self.stage = classify_tool(name, input);
+                self.tool = Some(name.clone());
+            }
+            AgentEvent::Error { message } => {
+                self.stage = Stage::Error;
+                self.message = Some(message.clone());
+            }
+            _ => {}
+        }
+
+        before != (self.stage, self.tool.clone())
+    }
+}
+
+/// Minimum interval between in-place redraws
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Renders progress for a single task to stderr
+pub struct ProgressReporter {
+    enabled: bool,
+    inline: bool,
+    state: Arc<Mutex<ProgressState>>,
+    last_draw: Option<Instant>,
+    ticker: Option<JoinHandle<()>>,
+}
+
+impl ProgressReporter {
+    /// Create a reporter for the given config (disabled in quiet mode)
+    pub fn new(config: &Config) -> Self {
+        let enabled = !config.quiet;
+        // Forwarded backend stderr would garble an in-place line, so fall back to line mode
+        let inline = enabled
+            && config.task_id.is_none()
+            && !config.backend_output
+            && std::io::stderr().is_terminal();
+        let state = Arc::new(Mutex::new(ProgressState::new(config.task_id.clone())));
+
+        let ticker = inline.then(|| {
+            let state = Arc::clone(&state);
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(1));
+                loop {
+                    interval.tick().await;
+                    if let Ok(state) = state.lock() {
+                        draw_inline(&state);
+                    }
+                }
+            })
+        });
+
+        let reporter = Self {
+            enabled,
+            inline,
+            state,
+            last_draw: None,
+            ticker,
+        };
+        if enabled && !inline {
+            reporter.print_line();
+        }
+        reporter
+    }
+
+    /// Record an event and redraw if needed
+    pub fn on_event(&mut self, event: &AgentEvent) {
+        if !self.enabled {
+            return;
+        }
+
+        let changed = match self.state.lock() {
+            Ok(mut state) => state.update(event),
+            Err(_) => return,
+        };
+
+        if self.inline {
+            let due = self
+                .last_draw
+                .is_none_or(|t| t.elapsed() >= REDRAW_INTERVAL);
+            if changed || due {
+                if let Ok(state) = self.state.lock() {
+                    draw_inline(&state);
+                }
+                self.last_draw = Some(Instant::now());
+            }
+        } else if changed {
+            self.print_line();
+        }
+    }
+
+    /// Print the final status line
+    pub fn finish(mut self, success: bool, exit_code: i32) {
+        if let Some(ticker) = self.ticker.take() {
+            ticker.abort();
+        }
+        if !self.enabled {
+            return;
+        }
+
+        let Ok(state) = self.state.lock() else {
+            return;
+        };
+        let use_ascii = std::env::var("CODEAGENT_ASCII_MODE").is_ok();
+        let (symbol, outcome) = match (success, use_ascii) {
+            (true, false) => ("✓", "completed".to_string()),
+            (true, true) => ("[+]", "completed".to_string()),
+            (false, false) => ("✗", format!("failed (exit {})", exit_code)),
+            (false, true) => ("[x]", format!("failed (exit {})", exit_code)),
+        };
+        let prefix = state
+            .label
+            .as_ref()
+            .map(|l| format!("[{}] ", l))
+            .unwrap_or_default();
+        let line = format!(
+            "{}{} Task {} ({})",
+            prefix,
+            symbol,
+            outcome,
+            format_duration(state.elapsed())
+        );
+
+        let mut stderr = std::io::stderr().lock();
+        if self.inline {
+            let _ = write!(stderr, "\r\x1b[2K");
+        }
+        let _ = writeln!(stderr, "{}", line);
+    }
+
+    fn print_line(&self) {
+        if let Ok(state) = self.state.lock()
+            && let Some(line) = format_progress_message(&state, false)
+        {
+            eprintln!("{}", line);
+        }
+    }
+}
+
+impl Drop for ProgressReporter {
+    fn drop(&mut self) {
+        if let Some(ticker) = self.ticker.take() {
+            ticker.abort();
+        }
+    }
+}
+
+/// Redraw the progress line in place
+fn draw_inline(state: &ProgressState) {
+    if let Some(line) = format_progress_message(state, false) {
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[2K{}", line);
+        let _ = stderr.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_tool() {
+        let none = serde_json::Value::Null;
+        assert_eq!(classify_tool("Edit", &none), Stage::Editing);
+        assert_eq!(classify_tool("apply_patch", &none), Stage::Editing);
+        assert_eq!(
+            classify_tool("Bash", &serde_json::json!({"command": "cargo test --all"})),
+            Stage::Testing
+        );
+        assert_eq!(
+            classify_tool(
+                "shell",
+                &serde_json::json!({"command": ["bash", "-lc", "npx jest"]})
+            ),
+            Stage::Testing
+        );
+        assert_eq!(classify_tool("Read", &none), Stage::ToolCall);
+    }
+
+    #[test]
+    fn test_is_test_command() {
+        assert!(is_test_command("cd api && npm run test:unit"));
+        assert!(is_test_command("go test ./..."));
+        assert!(is_test_command(".venv/bin/pytest -x"));
+        assert!(!is_test_command("cat latest.log"));
+        assert!(!is_test_command("mkdir test-fixtures"));
+        assert!(!is_test_command("test -f package.json && cat package.json"));
+        assert!(!is_test_command("rg contest src"));
+        assert!(is_test_command("cargo test --workspace"));
+        assert!(is_test_command("./gradlew test"));
+        assert!(!is_test_command("cat test"));
+        assert!(!is_test_command("git add test"));
+        assert!(!is_test_command("rm -rf test"));
+    }
+
+    #[test]
+    fn test_progress_state_update() {
+        let mut state = ProgressState::new(None);
+        assert!(state.update(&AgentEvent::Thinking {
+            text: "hmm".to_string()
+        }));
+        assert_eq!(state.stage, Stage::Thinking);
+
+        assert!(state.update(&AgentEvent::ToolCall {
+            id: None,
+            name: "Write".to_string(),
+            input: serde_json::Value::Null,
+        }));
+        assert_eq!(state.stage, Stage::Editing);
+        assert_eq!(state.tool.as_deref(), Some("Write"));
+
+        assert!(state.update(&AgentEvent::AssistantText {
+            text: "Hello ".to_string(),
+            delta: true,
+        }));
+        assert!(!state.update(&AgentEvent::AssistantText {
+            text: "world".to_string(),
+            delta: true,
+        }));
+        assert_eq!(state.message.as_deref(), Some("Hello world"));
+    }
+}
@@ -0,0 +1,544 @@
+//! Local session registry
+//!
+//! Every run that reports a session ID is recorded as
+//! `~/.codeagent/sessions/<session-id>.json`, so sessions can be listed and
+//! resumed as `last`, `last@<workdir>` or by a `--name` alias instead of a
+//! copied UUID. The prompts and normalized events of each run are appended to
+//! `<session-id>.jsonl` next to it, so a session can be handed off to another
+//! backend.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::agent_config::get_config_dir;
+use crate::config::Config;
+use crate::errors::ConfigError;
+use crate::executor::TaskResult;
+use crate::parser::{AgentEvent, TokenUsage};
+use crate::utils::{expand_home, is_valid_session_id, truncate_chars};
+
+/// Longest first prompt line kept in a record
+const PROMPT_CHARS: usize = 120;
+
+/// A recorded session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionRecord {
+    /// Backend session ID
+    pub id: String,
+    /// Alias set with `--name`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Backend that created the session
+    pub backend: String,
+    /// Model the session was created with
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Working directory
+    pub workdir: PathBuf,
+    /// First line of the first prompt
+    pub prompt: String,
+    /// When the session was first recorded
+    pub created_at: DateTime<Utc>,
+    /// When the session last ran
+    pub updated_at: DateTime<Utc>,
+    /// Outcome of the last run: `success` or `failed`
+    pub status: String,
+    /// Number of runs in this session
+    pub runs: u32,
+    /// Session this one was handed off from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handoff_from: Option<String>,
+}
+
+/// One run of a session, as stored in its transcript
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscriptTurn {
+    /// Task sent to the backend (without preset prefix or handoff context)
+    pub prompt: String,
+    /// Backend that ran the turn
+    pub backend: String,
+    /// Model requested for the turn
+    #[serde(default)]
+    pub model: Option<String>,
+    /// When the turn started
+    pub started_at: DateTime<Utc>,
+    /// How long the turn took
+    pub duration_ms: u64,
+    /// Whether the turn succeeded
+    pub success: bool,
+    /// Token usage reported by the backend
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+    /// Normalized events of the turn
+    pub events: Vec<AgentEvent>,
+}
+
+/// Session records stored as one JSON file per session
+pub struct SessionRegistry {
+    dir: PathBuf,
+}
+
+impl SessionRegistry {
+    /// Registry in `~/.codeagent/sessions`
+    pub fn open_default() -> Self {
+        Self::new(get_config_dir().join("sessions"))
+    }
+
+    /// Registry in the given directory
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    fn transcript_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.jsonl", id))
+    }
+
+    /// Recorded turns of a session, oldest first
+    pub fn transcript(&self, id: &str) -> Result<Vec<TranscriptTurn>> {
+        if !is_valid_session_id(id) {
+            return Ok(Vec::new());
+        }
+        let path = self.transcript_path(id);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read transcript: {}", path.display()));
+            }
+        };
+        Ok(content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(turn) => Some(turn),
+                Err(e) => {
+                    warn!(path = %path.display(), error = %e, "Skipping unreadable transcript line");
+                    None
+                }
+            })
+            .collect())
+    }
+
+    fn append_turn(&self, id: &str, turn: &TranscriptTurn) -> Result<()> {
+        let path = self.transcript_path(id);
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open transcript: {}", path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(turn)?)?;
+        Ok(())
+    }
+
+    /// All sessions, most recently used first; unreadable files are skipped
+    pub fn list(&self) -> Result<Vec<SessionRecord>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!("Failed to read sessions directory: {}", self.dir.display())
+                });
+            }
+        };
+
+        let mut records: Vec<SessionRecord> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| match read_record(&path) {
+                Ok(record) => Some(record),
+                Err(e) => {
+                    warn!(path = %path.display(), error = %e, "Skipping unreadable session record");
+                    None
+                }
+            })
+            .collect();
+        records.sort_by_key(|record| std::cmp::Reverse(record.updated_at));
+        Ok(records)
+    }
+
+    /// Look up a session by ID
+    pub fn get(&self, id: &str) -> Result<Option<SessionRecord>> {
+        if !is_valid_session_id(id) {
+            return Ok(None);
+        }
+        let path = self.path(id);
+        if !path.exists() {
+            return Ok(None);
+        }
+        read_record(&path).map(Some)
+    }
+
+    /// Write a record (atomically, so concurrent parallel tasks never see half a file)
+    pub fn save(&self, record: &SessionRecord) -> Result<()> {
+        std::fs::create_dir_all(&self.dir).with_context(|| {
+            format!(
+                "Failed to create sessions directory: {}",
+                self.dir.display()
+            )
+        })?;
+        let path = self.path(&record.id);
+        let tmp = path.with_extension(format!("json.{}.tmp", std::process::id()));
+        std::fs::write(&tmp, serde_json::to_string_pretty(record)?)?;
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("Failed to write session record: {}", path.display()))
+    }
+
+    /// Delete a session record; returns false if it did not exist
+    pub fn remove(&self, id: &str) -> Result<bool> {
+        if !is_valid_session_id(id) {
+            return Ok(false);
+        }
+        if let Err(e) = std::fs::remove_file(self.transcript_path(id))
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            return Err(e.into());
+        }
+        match std::fs::remove_file(self.path(id)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Resolve `last`, `last@<workdir>`, an alias or a session ID
+    ///
+    /// `last` forms fail when nothing matches; other unknown references resolve
+    /// to `None` so raw IDs from before the registry still work.
+    pub fn resolve(&self, reference: &str) -> Result<Option<SessionRecord>> {
+        if reference == "last" {
+            return self
+                .list()?
+                .into_iter()
+                .next()
+                .map(Some)
+                .ok_or_else(|| ConfigError::UnknownSession(reference.to_string()).into());
+        }
+        if let Some(workdir) = reference.strip_prefix("last@") {
+            let workdir = normalize_workdir(Path::new(&expand_home(workdir)));
+            return self
+                .list()?
+                .into_iter()
+                .find(|record| record.workdir == workdir)
+                .map(Some)
+                .ok_or_else(|| ConfigError::UnknownSession(reference.to_string()).into());
+        }
+
+        if let Some(record) = self.get(reference)? {
+            return Ok(Some(record));
+        }
+        Ok(self
+            .list()?
+            .into_iter()
+            .find(|record| record.name.as_deref() == Some(reference)))
+    }
+
+    /// Record a finished run; runs without a session ID are ignored
+    pub fn record_run(&self, config: &Config, result: &TaskResult) -> Result<()> {
+        let Some(ref id) = result.session_id else {
+            return Ok(());
+        };
+        if !is_valid_session_id(id) {
+            warn!(session_id = %id, "Not recording session with unexpected ID");
+            return Ok(());
+        }
+
+        let now = Utc::now();
+        let status = if result.success { "success" } else { "failed" };
+        // A resumed run may report a new ID; carry the original record over
+        let previous = match self.get(id)? {
+            Some(record) => Some(record),
+            None => match config.session_id {
+                Some(ref resumed) => self.get(resumed)?,
+                None => None,
+            },
+        };
+
+        // A resumed run that changes the ID moves the record and its transcript
+        let replaced = previous
+            .as_ref()
+            .map(|previous| previous.id.clone())
+            .filter(|previous_id| previous_id != id);
+        if let Some(ref previous_id) = replaced {
+            let old = self.transcript_path(previous_id);
+            if old.exists() {
+                std::fs::rename(&old, self.transcript_path(id))?;
+            }
+        }
+
+        let record = match previous {
+            Some(previous) => SessionRecord {
+                id: id.clone(),
+                name: config.session_name.clone().or(previous.name),
+                updated_at: now,
+                status: status.to_string(),
+                runs: previous.runs + 1,
+                ..previous
+            },
+            None => SessionRecord {
+                id: id.clone(),
+                name: config.session_name.clone(),
+                backend: result
+                    .backend
+                    .clone()
+                    .or_else(|| config.backend.clone())
+                    .unwrap_or_default(),
+                model: result.model.clone(),
+                workdir: normalize_workdir(&config.work_dir),
+                prompt: truncate_chars(
+                    config
+                        .task
+                        .lines()
+                        .find(|l| !l.trim().is_empty())
+                        .unwrap_or(""),
+                    PROMPT_CHARS,
+                ),
+                created_at: now,
+                updated_at: now,
+                status: status.to_string(),
+                runs: 1,
+                handoff_from: config.handoff_from.clone(),
+            },
+        };
+
+        // An alias points at exactly one session
+        if let Some(ref name) = record.name {
+            for mut other in self.list()? {
+                if other.id != record.id && other.name.as_deref() == Some(name) {
+                    other.name = None;
+                    self.save(&other)?;
+                }
+            }
+        }
+
+        self.save(&record)?;
+        if let Some(ref previous_id) = replaced
+            && let Err(e) = std::fs::remove_file(self.path(previous_id))
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            return Err(e.into());
+        }
+        self.append_turn(
+            id,
+            &TranscriptTurn {
+                prompt: config.task.clone(),
+                backend: record.backend.clone(),
+                model: result.model.clone(),
+                started_at: now - chrono::Duration::from_std(result.duration).unwrap_or_default(),
+                duration_ms: result.duration.as_millis() as u64,
+                success: result.success,
+                usage: result.usage,
+                events: result.events.clone(),
+            },
+        )
+    }
+}
+
+fn read_record(path: &Path) -> Result<SessionRecord> {
+    let content = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// Canonical form of a workdir, so `last@.` and absolute paths match
+fn normalize_workdir(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Render sessions as a table for `sessions list`
+pub fn format_session_list(records: &[SessionRecord]) -> String {
+    if records.is_empty() {
+        return "No sessions recorded yet.\n".to_string();
+    }
+
+    let mut out = format!(
+        "{:<38} {:<12} {:<9} {:<8} {:<16} {}\n",
+        "ID", "NAME", "BACKEND", "STATUS", "UPDATED", "PROMPT"
+    );
+    for record in records {
+        out.push_str(&format!(
+            "{:<38} {:<12} {:<9} {:<8} {:<16} {}\n",
+            record.id,
+            record.name.as_deref().unwrap_or("-"),
+            record.backend,
+            record.status,
+            record
+                .updated_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M"),
+            truncate_chars(&record.prompt, 60)
+        ));
+    }
+    out
+}
+
+/// Render one session for `sessions show`
+pub fn format_session(record: &SessionRecord) -> String {
+    let local = |time: &DateTime<Utc>| {
+        time.with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string()
+    };
+    [
+        ("Session", record.id.clone()),
+        (
+            "Name",
+            record.name.clone().unwrap_or_else(|| "-".to_string()),
+        ),
+        ("Backend", record.backend.clone()),
+        (
+            "Model",
+            record
+                .model
+                .clone()
+                .unwrap_or_else(|| "(default)".to_string()),
+        ),
+        ("Workdir", record.workdir.display().to_string()),
+        ("Prompt", record.prompt.clone()),
+        ("Created", local(&record.created_at)),
+        ("Updated", local(&record.updated_at)),
+        ("Status", record.status.clone()),
+        ("Runs", record.runs.to_string()),
+    ]
+    .into_iter()
+    .chain(record.handoff_from.clone().map(|from| ("From", from)))
+    .map(|(key, value)| format!("{:<8} {}\n", format!("{}:", key), value))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(session_id: &str, success: bool) -> TaskResult {
+        TaskResult {
+            success,
+            session_id: Some(session_id.to_string()),
+            backend: Some("codex".to_string()),
+            model: Some("gpt-5".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_record_and_resolve() {
+        let home = tempfile::tempdir().unwrap();
+        let registry = SessionRegistry::new(home.path().join("sessions"));
+        let workdir = tempfile::tempdir().unwrap();
+
+        let config = Config {
+            task: "\nFix the login bug\nDetails follow".to_string(),
+            work_dir: workdir.path().to_path_buf(),
+            session_name: Some("login".to_string()),
+            ..Default::default()
+        };
+        registry.record_run(&config, &run("s1", false)).unwrap();
+
+        let record = registry.get("s1").unwrap().unwrap();
+        assert_eq!(record.backend, "codex");
+        assert_eq!(record.model.as_deref(), Some("gpt-5"));
+        assert_eq!(record.prompt, "Fix the login bug");
+        assert_eq!(record.status, "failed");
+
+        let other = Config {
+            task: "Other".to_string(),
+            work_dir: home.path().to_path_buf(),
+            ..Default::default()
+        };
+        registry.record_run(&other, &run("s2", true)).unwrap();
+
+        assert_eq!(registry.resolve("last").unwrap().unwrap().id, "s2");
+        let at = format!("last@{}", workdir.path().display());
+        assert_eq!(registry.resolve(&at).unwrap().unwrap().id, "s1");
+        assert_eq!(registry.resolve("login").unwrap().unwrap().id, "s1");
+        assert_eq!(registry.resolve("unknown-id").unwrap(), None);
+        assert!(registry.resolve("last@/nonexistent").is_err());
+
+        // Resuming updates the record; moving the alias clears it elsewhere
+        let resumed = Config {
+            session_id: Some("s2".to_string()),
+            session_name: Some("login".to_string()),
+            ..other
+        };
+        registry.record_run(&resumed, &run("s2", true)).unwrap();
+        let record = registry.get("s2").unwrap().unwrap();
+        assert_eq!(record.runs, 2);
+        assert_eq!(record.prompt, "Other");
+        assert_eq!(registry.get("s1").unwrap().unwrap().name, None);
+        assert_eq!(registry.resolve("login").unwrap().unwrap().id, "s2");
+
+        // Every run is appended to the transcript, which follows a changed ID
+        let turns = registry.transcript("s2").unwrap();
+        assert_eq!(turns.len(), 2);
+        assert_eq!(turns[0].prompt, "Other");
+        assert_eq!(turns[0].backend, "codex");
+        let continued = Config {
+            session_id: Some("s2".to_string()),
+            ..Default::default()
+        };
+        registry.record_run(&continued, &run("s3", true)).unwrap();
+        assert_eq!(registry.transcript("s3").unwrap().len(), 3);
+        assert!(registry.transcript("s2").unwrap().is_empty());
+
+        assert!(registry.remove("s1").unwrap());
+        assert!(registry.transcript("s1").unwrap().is_empty());
+        assert!(!registry.remove("s1").unwrap());
+        assert_eq!(registry.list().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_changed_id_replaces_record() {
+        let home = tempfile::tempdir().unwrap();
+        let registry = SessionRegistry::new(home.path().join("sessions"));
+
+        let config = Config {
+            task: "Fix the login bug".to_string(),
+            session_name: Some("login".to_string()),
+            ..Default::default()
+        };
+        registry.record_run(&config, &run("old-id", true)).unwrap();
+
+        let resumed = Config {
+            session_id: Some("old-id".to_string()),
+            session_name: None,
+            ..config
+        };
+        registry.record_run(&resumed, &run("new-id", true)).unwrap();
+
+        assert_eq!(registry.get("old-id").unwrap(), None);
+        let records = registry.list().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].id, "new-id");
+        assert_eq!(records[0].runs, 2);
+        assert_eq!(registry.resolve("login").unwrap().unwrap().id, "new-id");
+        assert!(!home.path().join("sessions/old-id.json").exists());
+    }
+
+    #[test]
+    fn test_empty_registry() {
+        let home = tempfile::tempdir().unwrap();
+        let registry = SessionRegistry::new(home.path().join("sessions"));
+        assert!(registry.list().unwrap().is_empty());
+        let err = registry.resolve("last").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ConfigError>(),
+            Some(ConfigError::UnknownSession(_))
+        ));
+
+        // Runs without a session ID leave no record
+        registry
+            .record_run(&Config::default(), &TaskResult::default())
+            .unwrap();
+        assert!(registry.list().unwrap().is_empty());
+    }
+}
@@ -1,88 +1,255 @@
 //! Signal handling for graceful shutdown
+//!
+//! Each backend runs in its own process group so that shells, MCP servers and
+//! test runners it spawns are stopped together with it. The first SIGINT,
+//! SIGTERM or SIGHUP asks running tasks to shut down gracefully; a second one
+//! force-kills every group and exits immediately with `128 + N`.
 
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use tokio::sync::watch;
 use tracing::{info, warn};
 
+use crate::errors::exit_codes;
+
+/// Signal number reported for Ctrl-C (same on every platform)
+pub const SIGINT: i32 = 2;
+
 /// Global flag for signal received
 static SIGNAL_RECEIVED: AtomicBool = AtomicBool::new(false);
 
+/// Broadcasts the number of the first termination signal to running tasks
+static SHUTDOWN: LazyLock<watch::Sender<Option<i32>>> = LazyLock::new(|| watch::channel(None).0);
+
+/// Process groups of running backends
+static ACTIVE_GROUPS: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
 /// Check if a termination signal has been received
 pub fn is_signal_received() -> bool {
     SIGNAL_RECEIVED.load(Ordering::SeqCst)
 }
 
-/// Signal handler guard - kills child process on drop if signal received
+/// Wait until a termination signal is received; returns its number
+pub async fn shutdown_requested() -> i32 {
+    let mut rx = SHUTDOWN.subscribe();
+    rx.wait_for(Option::is_some)
+        .await
+        .ok()
+        .and_then(|signal| *signal)
+        .unwrap_or(SIGINT)
+}
+
+/// Cancels a group of running tasks, such as parallel siblings under
+/// `--fail-fast`; each one stops its backend gracefully, like on Ctrl-C
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<watch::Sender<bool>>);
+
+impl CancelToken {
+    /// Create a token that has not been cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask every task holding this token to stop
+    pub fn cancel(&self) {
+        self.0.send_replace(true);
+    }
+
+    /// Whether `cancel` has been called
+    pub fn is_cancelled(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Wait until `cancel` is called
+    pub async fn cancelled(&self) {
+        let mut rx = self.0.subscribe();
+        let _ = rx.wait_for(|cancelled| *cancelled).await;
+    }
+}
+
+/// Signal handler guard - registers the child's process group for a forced
+/// kill on a second termination signal until dropped
+///
+/// Dropping it signals nothing: once the child is reaped its PID may be reused,
+/// so stragglers are killed by `wait_for_exit` before reaping instead.
 pub struct SignalGuard {
     child_pid: u32,
 }
 
 impl Drop for SignalGuard {
     fn drop(&mut self) {
-        if is_signal_received() && self.child_pid > 0 {
-            warn!(pid = self.child_pid, "Killing child process due to signal");
-            #[cfg(unix)]
-            {
-                // Send SIGTERM first
-                unsafe {
-                    libc::kill(self.child_pid as i32, libc::SIGTERM);
-                }
-            }
-            #[cfg(windows)]
-            {
-                // On Windows, we rely on the process being killed when the handle is dropped
-                let _ = self.child_pid; // suppress unused warning
-            }
+        if self.child_pid == 0 {
+            return;
+        }
+        if let Ok(mut groups) = ACTIVE_GROUPS.lock() {
+            groups.retain(|&pid| pid != self.child_pid);
         }
     }
 }
 
-/// Setup signal handler and return a guard
+/// Setup signal handler and return a guard for the child's process group
 pub fn setup_signal_handler(child_pid: u32) -> SignalGuard {
     // Setup handler only once
     static HANDLER_INSTALLED: AtomicBool = AtomicBool::new(false);
 
     if !HANDLER_INSTALLED.swap(true, Ordering::SeqCst) {
-        ctrlc::set_handler(move || {
-            info!("Received termination signal");
-            SIGNAL_RECEIVED.store(true, Ordering::SeqCst);
-        })
-        .expect("Error setting signal handler");
+        install_handlers();
+    }
+
+    if child_pid > 0
+        && let Ok(mut groups) = ACTIVE_GROUPS.lock()
+    {
+        groups.push(child_pid);
     }
 
     SignalGuard { child_pid }
 }
 
-/// Wait for graceful shutdown with timeout
-#[allow(dead_code)]
+/// Listen for SIGINT, SIGTERM and SIGHUP on the runtime
+#[cfg(unix)]
+fn install_handlers() {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let kinds = [
+        (SignalKind::interrupt(), libc::SIGINT),
+        (SignalKind::terminate(), libc::SIGTERM),
+        (SignalKind::hangup(), libc::SIGHUP),
+    ];
+    for (kind, number) in kinds {
+        let mut stream = signal(kind).expect("Error setting signal handler");
+        tokio::spawn(async move {
+            while stream.recv().await.is_some() {
+                on_signal(number);
+            }
+        });
+    }
+}
+
+/// Listen for Ctrl-C on the runtime
+#[cfg(not(unix))]
+fn install_handlers() {
+    tokio::spawn(async {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            on_signal(SIGINT);
+        }
+    });
+}
+
+/// Shut down gracefully on the first signal, force-kill on the second
+fn on_signal(signal: i32) {
+    if SIGNAL_RECEIVED.swap(true, Ordering::SeqCst) {
+        warn!(signal, "Received second termination signal, force killing");
+        force_kill_all();
+        std::process::exit(exit_codes::SIGNAL_TERMINATED + signal);
+    }
+    info!(
+        signal,
+        "Received termination signal, shutting down (send it again to force)"
+    );
+    SHUTDOWN.send_replace(Some(signal));
+}
+
+/// Wait for a backend to exit and reap it
+///
+/// Stragglers left in its process group (background shells, MCP servers) are
+/// SIGKILLed first, while the unreaped leader still holds the group ID, so they
+/// cannot keep stderr open and no reused PID is ever signalled.
+pub async fn wait_for_exit(
+    child: &mut tokio::process::Child,
+) -> std::io::Result<std::process::ExitStatus> {
+    #[cfg(unix)]
+    if let Some(pid) = child.id()
+        && matches!(
+            tokio::task::spawn_blocking(move || wait_exited(pid)).await,
+            Ok(true)
+        )
+    {
+        kill_group(pid, GroupSignal::Kill);
+    }
+    child.wait().await
+}
+
+/// Block until `pid` has exited, without reaping it; false if it cannot be waited for
+#[cfg(unix)]
+fn wait_exited(pid: u32) -> bool {
+    loop {
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let rc = unsafe {
+            libc::waitid(
+                libc::P_PID,
+                pid as libc::id_t,
+                &mut info,
+                libc::WEXITED | libc::WNOWAIT,
+            )
+        };
+        if rc == 0 {
+            return true;
+        }
+        if std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+            return false;
+        }
+    }
+}
+
+/// Stop a backend: SIGTERM its process group, wait up to `timeout_secs`, then
+/// SIGKILL the group
 pub async fn wait_for_graceful_shutdown(
     child: &mut tokio::process::Child,
     timeout_secs: u64,
 ) -> std::io::Result<std::process::ExitStatus> {
     use tokio::time::{Duration, timeout};
 
-    // First, try graceful shutdown (SIGTERM on Unix)
-    #[cfg(unix)]
-    if let Some(pid) = child.id() {
-        unsafe {
-            libc::kill(pid as i32, libc::SIGTERM);
-        }
+    let pid = child.id().unwrap_or(0);
+    if pid == 0 || !kill_group(pid, GroupSignal::Term) {
+        // Not a separate group (or not unix): fall back to killing the child
+        child.kill().await?;
+        return child.wait().await;
     }
 
-    #[cfg(not(unix))]
-    let _ = child; // suppress unused warning on non-unix
-
     // Wait for process to exit with timeout
-    match timeout(Duration::from_secs(timeout_secs), child.wait()).await {
+    match timeout(Duration::from_secs(timeout_secs), wait_for_exit(child)).await {
         Ok(result) => result,
         Err(_) => {
-            // Timeout - force kill
-            warn!("Graceful shutdown timed out, force killing");
+            warn!(pgid = pid, "Graceful shutdown timed out, force killing");
+            kill_group(pid, GroupSignal::Kill);
             child.kill().await?;
             child.wait().await
         }
     }
 }
 
+/// Signals sent to a backend's process group
+#[derive(Debug, Clone, Copy)]
+enum GroupSignal {
+    Term,
+    Kill,
+}
+
+/// Send a signal to the process group led by `pgid`; returns true if delivered
+#[cfg(unix)]
+fn kill_group(pgid: u32, signal: GroupSignal) -> bool {
+    let sig = match signal {
+        GroupSignal::Term => libc::SIGTERM,
+        GroupSignal::Kill => libc::SIGKILL,
+    };
+    // Negative PID addresses the whole process group
+    unsafe { libc::kill(-(pgid as i32), sig) == 0 }
+}
+
+#[cfg(not(unix))]
+fn kill_group(_pgid: u32, _signal: GroupSignal) -> bool {
+    false
+}
+
+/// SIGKILL every registered process group
+fn force_kill_all() {
+    if let Ok(groups) = ACTIVE_GROUPS.lock() {
+        for &pgid in groups.iter() {
+            kill_group(pgid, GroupSignal::Kill);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -94,4 +261,96 @@ mod tests {
         assert!(is_signal_received());
         SIGNAL_RECEIVED.store(false, Ordering::SeqCst);
     }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_graceful_shutdown_kills_process_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("grandchild.pid");
+        // The shell ignores SIGTERM and starts a grandchild in the same group
+        let script = format!(
+            "trap '' TERM; sleep 30 & echo $! > {}; wait",
+            marker.display()
+        );
+        let mut child = tokio::process::Command::new("sh")
+            .args(["-c", &script])
+            .process_group(0)
+            .spawn()
+            .unwrap();
+
+        while !marker.exists() {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let grandchild: i32 = loop {
+            if let Ok(pid) = std::fs::read_to_string(&marker).unwrap().trim().parse() {
+                break pid;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        };
+
+        let status = wait_for_graceful_shutdown(&mut child, 1).await.unwrap();
+        assert!(!status.success());
+
+        // The grandchild inherited the ignored SIGTERM, so only the group SIGKILL stops it
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert!(
+            !is_running(grandchild),
+            "grandchild {} survived",
+            grandchild
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_wait_for_exit_kills_leftovers() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("grandchild.pid");
+        // The shell exits normally, leaving a background grandchild in its group
+        let script = format!("sleep 30 & echo $! > {}", marker.display());
+        let mut child = tokio::process::Command::new("sh")
+            .args(["-c", &script])
+            .process_group(0)
+            .spawn()
+            .unwrap();
+
+        let status = wait_for_exit(&mut child).await.unwrap();
+        assert!(status.success());
+
+        let grandchild: i32 = std::fs::read_to_string(&marker)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert!(
+            !is_running(grandchild),
+            "grandchild {} survived",
+            grandchild
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancel_token() {
+        let token = CancelToken::new();
+        let waiter = tokio::spawn({
+            let token = token.clone();
+            async move { token.cancelled().await }
+        });
+        assert!(!token.is_cancelled());
+        token.cancel();
+        assert!(token.is_cancelled());
+        tokio::time::timeout(std::time::Duration::from_secs(1), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    /// Whether a process exists and is not a zombie awaiting reaping
+    #[cfg(unix)]
+    fn is_running(pid: i32) -> bool {
+        match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            Ok(stat) => !stat.contains(") Z "),
+            Err(_) => unsafe { libc::kill(pid, 0) == 0 },
+        }
+    }
 }
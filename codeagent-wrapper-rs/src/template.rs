@@ -0,0 +1,154 @@
+//! Dependency placeholders for parallel task prompts
+//!
+//! Downstream tasks can reference upstream results with `{{task_id.field}}`,
+//! resolved from the completed `TaskResult` right before the task is spawned.
+
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use crate::executor::TaskResult;
+
+/// Fields that can be referenced in a placeholder
+pub const PLACEHOLDER_FIELDS: &[&str] = &[
+    "message",
+    "session_id",
+    "files_changed",
+    "exit_code",
+    "status",
+];
+
+/// Matches `{{task_id.field}}`; the task ID may itself contain dots
+static PLACEHOLDER_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\{\s*([^{}\s]+)\.(\w+)\s*\}\}").unwrap());
+
+/// A `{{task_id.field}}` reference found in task content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placeholder {
+    pub task_id: String,
+    pub field: String,
+}
+
+/// List all placeholders in the given text
+pub fn find_placeholders(text: &str) -> Vec<Placeholder> {
+    PLACEHOLDER_RE
+        .captures_iter(text)
+        .map(|caps| Placeholder {
+            task_id: caps[1].to_string(),
+            field: caps[2].to_string(),
+        })
+        .collect()
+}
+
+/// Replace placeholders with values from completed dependency results
+///
+/// Placeholders that do not name a completed task are left untouched, so
+/// template syntax such as `{{user.name}}` in a prompt survives. References to
+/// tasks outside `dependencies` or to unknown fields are rejected by the
+/// parallel config validation before anything runs.
+pub fn render(text: &str, results: &HashMap<String, TaskResult>) -> String {
+    PLACEHOLDER_RE
+        .replace_all(text, |caps: &regex::Captures| {
+            results
+                .get(&caps[1])
+                .and_then(|result| field_value(result, &caps[2]))
+                .unwrap_or_else(|| caps[0].to_string())
+        })
+        .into_owned()
+}
+
+/// Build a context section containing the output of each dependency
+pub fn dependency_context(
+    dependencies: &[String],
+    results: &HashMap<String, TaskResult>,
+) -> String {
+    let mut section = String::from("## Context from dependencies\n");
+
+    for dep in dependencies {
+        let Some(result) = results.get(dep) else {
+            continue;
+        };
+        section.push_str(&format!("\n### {}\n", dep));
+        if let Some(ref session_id) = result.session_id {
+            section.push_str(&format!("Session: {}\n", session_id));
+        }
+        section.push_str(result.message.as_deref().unwrap_or("(no output)"));
+        section.push('\n');
+    }
+
+    section
+}
+
+/// Resolve a single placeholder field
+fn field_value(result: &TaskResult, field: &str) -> Option<String> {
+    let value = match field {
+        "message" => result.message.clone().unwrap_or_default(),
+        "session_id" => result.session_id.clone().unwrap_or_default(),
+        "files_changed" => result
+            .files_changed
+            .map(|n| n.to_string())
+            .unwrap_or_default(),
+        "exit_code" => result.exit_code.to_string(),
+        "status" => result.status().to_string(),
+        _ => return None,
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results() -> HashMap<String, TaskResult> {
+        let analyze = TaskResult {
+            success: true,
+            session_id: Some("sess-1".to_string()),
+            files_changed: Some(3),
+            message: Some("Use a token bucket.".to_string()),
+            ..Default::default()
+        };
+        HashMap::from([("analyze.v2".to_string(), analyze)])
+    }
+
+    #[test]
+    fn test_find_placeholders() {
+        let found = find_placeholders("See {{analyze.v2.message}} and {{ lint.status }}");
+        assert_eq!(
+            found,
+            vec![
+                Placeholder {
+                    task_id: "analyze.v2".to_string(),
+                    field: "message".to_string()
+                },
+                Placeholder {
+                    task_id: "lint".to_string(),
+                    field: "status".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_placeholders() {
+        let text = "Plan: {{analyze.v2.message}} (session {{analyze.v2.session_id}}, \
+                    {{analyze.v2.files_changed}} files, {{analyze.v2.status}})";
+        assert_eq!(
+            render(text, &results()),
+            "Plan: Use a token bucket. (session sess-1, 3 files, success)"
+        );
+    }
+
+    #[test]
+    fn test_render_leaves_unknown_placeholders() {
+        let text = "{{other.message}} {{analyze.v2.nope}}";
+        assert_eq!(render(text, &results()), text);
+    }
+
+    #[test]
+    fn test_dependency_context() {
+        let section = dependency_context(&["analyze.v2".to_string()], &results());
+        assert!(section.contains("### analyze.v2"));
+        assert!(section.contains("Session: sess-1"));
+        assert!(section.contains("Use a token bucket."));
+    }
+}
@@ -8,23 +8,98 @@ use anyhow::Result;
 use serde_json::{Value, json};
 use std::env;
 
+use crate::diagnostics::ErrorDetail;
 use crate::executor::TaskResult;
+use crate::progress::{ProgressState, Stage};
 
 /// Generate final output JSON for a single task
 pub fn generate_final_output(result: &TaskResult) -> Result<String> {
-    let output = json!({
+    let mut output = json!({
         "success": result.success,
         "exitCode": result.exit_code,
         "duration": result.duration.as_millis(),
         "sessionId": result.session_id,
+        "message": result.message,
+        "usage": result.usage,
         "filesChanged": result.files_changed,
         "coverage": result.coverage,
         "events": result.events,
     });
+    if let Some(ref error) = result.error {
+        output["error"] = json!(error);
+    }
+    if !result.attempts.is_empty() {
+        output["attempts"] = attempts_json(result);
+    }
+    if let Some(ref backend) = result.backend {
+        output["backend"] = json!(backend);
+    }
+    if !result.skipped_backends.is_empty() {
+        output["skippedBackends"] = json!(result.skipped_backends);
+    }
+    if !result.raw_events.is_empty() {
+        output["rawEvents"] = json!(result.raw_events);
+    }
 
     Ok(serde_json::to_string_pretty(&output)?)
 }
 
+/// Serialize the attempts of a retried task
+fn attempts_json(result: &TaskResult) -> Value {
+    result
+        .attempts
+        .iter()
+        .map(|a| {
+            json!({
+                "attempt": a.number,
+                "duration": a.duration.as_millis(),
+                "exitCode": a.exit_code,
+                "error": a.error,
+            })
+        })
+        .collect()
+}
+
+/// Generate the JSON error document for a run that failed before producing a result
+///
+/// Uses the same envelope as `generate_final_output`, with `exitCode` set to
+/// the process exit code.
+pub fn generate_error_output(error: &ErrorDetail, exit_code: i32) -> Result<String> {
+    generate_final_output(&TaskResult {
+        exit_code,
+        error: Some(error.clone()),
+        ..Default::default()
+    })
+}
+
+/// Format an error for display on stderr (mirrors Node's `formatError`)
+pub fn format_error(error: &ErrorDetail) -> String {
+    let use_ascii = env::var("CODEAGENT_ASCII_MODE").is_ok();
+    let (error_sym, info_sym, suggestion_sym) = if use_ascii {
+        ("[ERROR]", "[INFO]", "[SUGGESTION]")
+    } else {
+        ("❌", "📋", "💡")
+    };
+
+    let mut lines = vec![format!("{} Error: {}", error_sym, error.message)];
+    for cause in &error.causes {
+        lines.push(format!("   Caused by: {}", cause));
+    }
+    if !error.lines.is_empty() {
+        lines.push(String::new());
+        lines.extend(error.lines.iter().map(|l| format!("   | {}", l)));
+    }
+    lines.push(String::new());
+    lines.push(format!("{} Error Code: {}", info_sym, error.kind.as_str()));
+    if let Some(ref hint) = error.hint {
+        lines.push(String::new());
+        lines.push(format!("{} Suggestion:", suggestion_sym));
+        lines.push(format!("   {}", hint));
+    }
+
+    lines.join("\n")
+}
+
 /// Generate final output JSON for parallel execution
 pub fn generate_parallel_output(results: &[TaskResult]) -> Result<String> {
     let task_results: Vec<Value> = results
@@ -33,10 +108,19 @@ pub fn generate_parallel_output(results: &[TaskResult]) -> Result<String> {
         .map(|(i, r)| {
             json!({
                 "taskIndex": i,
+                "taskId": r.task_id,
+                "status": r.status(),
                 "success": r.success,
                 "exitCode": r.exit_code,
                 "duration": r.duration.as_millis(),
                 "sessionId": r.session_id,
+                "message": r.message,
+                "usage": r.usage,
+                "error": r.error,
+                "skipReason": r.skip_reason,
+                "attempts": (!r.attempts.is_empty()).then(|| attempts_json(r)),
+                "backend": r.backend,
+                "skippedBackends": (!r.skipped_backends.is_empty()).then_some(&r.skipped_backends),
             })
         })
         .collect();
@@ -48,7 +132,8 @@ pub fn generate_parallel_output(results: &[TaskResult]) -> Result<String> {
         "success": all_success,
         "totalTasks": results.len(),
         "successfulTasks": results.iter().filter(|r| r.success).count(),
-        "failedTasks": results.iter().filter(|r| !r.success).count(),
+        "failedTasks": results.iter().filter(|r| r.status() == "failed").count(),
+        "skippedTasks": results.iter().filter(|r| r.status() == "skipped").count(),
         "totalDuration": total_duration,
         "tasks": task_results,
     });
@@ -56,49 +141,104 @@ pub fn generate_parallel_output(results: &[TaskResult]) -> Result<String> {
     Ok(serde_json::to_string_pretty(&output)?)
 }
 
+/// Generate message-only output for parallel execution
+pub fn generate_parallel_messages(results: &[TaskResult]) -> String {
+    results
+        .iter()
+        .map(|r| {
+            let id = r.task_id.as_deref().unwrap_or("task");
+            let body = r
+                .message
+                .as_deref()
+                .or(r.skip_reason.as_deref())
+                .or(r.error.as_ref().map(|e| e.message.as_str()))
+                .unwrap_or("");
+            format!("## {} ({})\n\n{}\n", id, r.status(), body)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 /// Format progress message for display
-pub fn format_progress_message(event: &Value, quiet: bool) -> Option<String> {
+pub fn format_progress_message(state: &ProgressState, quiet: bool) -> Option<String> {
     if quiet {
         return None;
     }
 
     let use_ascii = env::var("CODEAGENT_ASCII_MODE").is_ok();
 
-    let event_type = event.get("type").and_then(|t| t.as_str()).unwrap_or("");
-
     let symbol = if use_ascii {
-        match event_type {
-            "assistant" => "[>]",
-            "tool_use" => "[*]",
-            "error" => "[!]",
-            "done" => "[+]",
-            _ => "[-]",
+        match state.stage {
+            Stage::Starting => "[.]",
+            Stage::Thinking => "[~]",
+            Stage::ToolCall => "[*]",
+            Stage::Editing => "[e]",
+            Stage::Testing => "[t]",
+            Stage::Responding => "[>]",
+            Stage::Error => "[!]",
         }
     } else {
-        match event_type {
-            "assistant" => "💬",
-            "tool_use" => "🔧",
-            "error" => "❌",
-            "done" => "✅",
-            _ => "📝",
+        match state.stage {
+            Stage::Starting => "⏳",
+            Stage::Thinking => "🔍",
+            Stage::ToolCall => "⚡",
+            Stage::Editing => "✏️",
+            Stage::Testing => "🧪",
+            Stage::Responding => "💬",
+            Stage::Error => "❌",
         }
     };
 
-    // Extract a short description
-    let desc = event
-        .get("content")
-        .and_then(|c| c.as_str())
-        .or_else(|| event.get("tool").and_then(|t| t.as_str()))
-        .unwrap_or("...");
+    let mut line = String::new();
+    if let Some(ref label) = state.label {
+        line.push_str(&format!("[{}] ", label));
+    }
+    line.push_str(&format!(
+        "{} {} {}",
+        symbol,
+        format_duration(state.elapsed()),
+        state.stage.label()
+    ));
+    if let Some(ref tool) = state.tool {
+        line.push_str(&format!(" · {}", truncate_chars(tool, 30)));
+    }
 
-    // Truncate long descriptions
-    let desc = if desc.len() > 60 {
-        format!("{}...", &desc[..57])
-    } else {
-        desc.to_string()
-    };
+    // Show the first line of the latest message, truncated on a char boundary
+    if let Some(desc) = state
+        .message
+        .as_deref()
+        .and_then(|m| m.lines().find(|l| !l.trim().is_empty()))
+    {
+        line.push_str(&format!(" · {}", truncate_chars(desc.trim(), 60)));
+    }
 
-    Some(format!("{} {}", symbol, desc))
+    Some(line)
+}
+
+/// Truncate to at most `max_chars` characters, appending "..." when cut
+///
+/// Counts `char`s rather than bytes so multi-byte text (e.g. Chinese) never
+/// gets split in the middle of a character.
+pub fn truncate_chars(s: &str, max_chars: usize) -> String {
+    if s.chars().count() <= max_chars {
+        return s.to_string();
+    }
+    let keep = max_chars.saturating_sub(3);
+    let mut truncated: String = s.chars().take(keep).collect();
+    truncated.push_str("...");
+    truncated
+}
+
+/// Format a duration as `12.3s`, `4m05s` or `1h02m`
+pub fn format_duration(duration: std::time::Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else if secs < 3600 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{:02}m", secs / 3600, (secs % 3600) / 60)
+    }
 }
 
 /// Expand ~ to home directory
@@ -131,16 +271,87 @@ mod tests {
             exit_code: 0,
             duration: Duration::from_millis(1234),
             session_id: Some("abc123".to_string()),
+            message: Some("Done.".to_string()),
             ..Default::default()
         };
 
         let output = generate_final_output(&result).unwrap();
         let parsed: Value = serde_json::from_str(&output).unwrap();
 
+        assert_eq!(parsed["message"], "Done.");
         assert_eq!(parsed["success"], true);
         assert_eq!(parsed["exitCode"], 0);
         assert_eq!(parsed["duration"], 1234);
         assert_eq!(parsed["sessionId"], "abc123");
+        assert!(parsed.get("error").is_none());
+    }
+
+    #[test]
+    fn test_generate_error_output() {
+        let err = anyhow::Error::new(crate::errors::ConfigError::InvalidSessionId(
+            "bad id".to_string(),
+        ));
+        let detail = ErrorDetail::from_error(&err);
+
+        let output = generate_error_output(&detail, 2).unwrap();
+        let parsed: Value = serde_json::from_str(&output).unwrap();
+
+        assert_eq!(parsed["success"], false);
+        assert_eq!(parsed["exitCode"], 2);
+        assert_eq!(parsed["error"]["kind"], "invalid-argument");
+        assert_eq!(parsed["error"]["message"], "Invalid session ID: bad id");
+        assert!(parsed["events"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_format_error() {
+        let err = anyhow::anyhow!("connect ECONNREFUSED").context("Request failed");
+        let text = format_error(&ErrorDetail::from_error(&err));
+
+        assert!(text.contains("Error: Request failed"));
+        assert!(text.contains("Caused by: connect ECONNREFUSED"));
+        assert!(text.contains("Error Code: network"));
+        assert!(text.contains("Suggestion:"));
+    }
+
+    #[test]
+    fn test_generate_parallel_output_statuses() {
+        let results = vec![
+            TaskResult {
+                success: true,
+                task_id: Some("a".to_string()),
+                ..Default::default()
+            },
+            TaskResult::failed("b", "spawn failed"),
+            TaskResult::skipped("c", "dependency 'b' failed"),
+        ];
+
+        let output = generate_parallel_output(&results).unwrap();
+        let parsed: Value = serde_json::from_str(&output).unwrap();
+
+        assert_eq!(parsed["successfulTasks"], 1);
+        assert_eq!(parsed["failedTasks"], 1);
+        assert_eq!(parsed["skippedTasks"], 1);
+        assert_eq!(parsed["tasks"][1]["error"]["kind"], "unknown");
+        assert_eq!(parsed["tasks"][1]["error"]["message"], "spawn failed");
+        assert_eq!(parsed["tasks"][2]["status"], "skipped");
+        assert_eq!(parsed["tasks"][2]["skipReason"], "dependency 'b' failed");
+    }
+
+    #[test]
+    fn test_generate_parallel_messages() {
+        let results = vec![
+            TaskResult {
+                success: true,
+                task_id: Some("a".to_string()),
+                message: Some("Answer".to_string()),
+                ..Default::default()
+            },
+            TaskResult::skipped("b", "dependency 'a' failed"),
+        ];
+        let output = generate_parallel_messages(&results);
+        assert!(output.contains("## a (success)\n\nAnswer"));
+        assert!(output.contains("## b (skipped)\n\ndependency 'a' failed"));
     }
 
     #[test]
@@ -163,11 +374,34 @@ mod tests {
 
     #[test]
     fn test_format_progress_message() {
-        let event = json!({"type": "assistant", "content": "Hello, I'm working on your task"});
-        let msg = format_progress_message(&event, false);
-        assert!(msg.is_some());
+        let mut state = ProgressState::new(Some("analyze".to_string()));
+        state.stage = Stage::ToolCall;
+        state.tool = Some("Read".to_string());
+        state.message = Some("\nHello, I'm working on your task\nsecond line".to_string());
 
-        let quiet_msg = format_progress_message(&event, true);
+        let msg = format_progress_message(&state, false).unwrap();
+        assert!(msg.starts_with("[analyze] "));
+        assert!(msg.contains("tool call · Read · Hello, I'm working on your task"));
+        assert!(!msg.contains("second line"));
+
+        let quiet_msg = format_progress_message(&state, true);
         assert!(quiet_msg.is_none());
     }
+
+    #[test]
+    fn test_truncate_chars_utf8_safe() {
+        let chinese = "请帮我分析这个代码库的整体架构并给出重构建议，重点关注模块之间的依赖关系以及错误处理方式是否一致，同时列出需要优先处理的问题";
+        let truncated = truncate_chars(chinese, 60);
+        assert_eq!(truncated.chars().count(), 60);
+        assert!(truncated.ends_with("..."));
+
+        assert_eq!(truncate_chars("short", 60), "short");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_millis(15200)), "15.2s");
+        assert_eq!(format_duration(Duration::from_secs(245)), "4m05s");
+        assert_eq!(format_duration(Duration::from_secs(3720)), "1h02m");
+    }
 }
@@ -5,6 +5,14 @@
 use assert_cmd::Command;
 use predicates::prelude::*;
 
+/// Run codeagent in an isolated home directory, so tests never write to the
+/// developer's `~/.codeagent` (history, sessions, logs)
+fn codeagent(home: &std::path::Path) -> Command {
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    cmd.env("HOME", home);
+    cmd
+}
+
 #[test]
 fn test_help_output() {
     let mut cmd = Command::cargo_bin("codeagent").unwrap();
@@ -27,21 +35,1010 @@ fn test_version_output() {
 
 #[test]
 fn test_unknown_backend_error() {
-    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    let home = tempfile::tempdir().unwrap();
+    let mut cmd = codeagent(home.path());
     cmd.args(["--backend", "unknown", "test task"])
         .assert()
-        .failure()
-        .stderr(predicate::str::contains("Backend not found"));
+        .code(3)
+        .stderr(predicate::str::contains("Backend not found"))
+        .stdout(predicate::str::contains("\"kind\": \"backend-not-found\""));
+
+    // History stores the code the process exited with
+    let history = std::fs::read_to_string(home.path().join(".codeagent/history.jsonl")).unwrap();
+    let entry: serde_json::Value = serde_json::from_str(history.lines().last().unwrap()).unwrap();
+    assert_eq!(entry["exitCode"], 3);
+    assert_eq!(entry["errorKind"], "backend-not-found");
+}
+
+#[test]
+fn test_invalid_flag_emits_json_error() {
+    let home = tempfile::tempdir().unwrap();
+    let mut cmd = codeagent(home.path());
+    let output = cmd.arg("--no-such-flag").output().unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["success"], false);
+    assert_eq!(doc["exitCode"], 2);
+    assert_eq!(doc["error"]["kind"], "invalid-argument");
+}
+
+#[test]
+fn test_parallel_parse_error_emits_json_error() {
+    let home = tempfile::tempdir().unwrap();
+    let mut cmd = codeagent(home.path());
+    let output = cmd
+        .arg("--parallel")
+        .write_stdin("{not json}\n")
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["error"]["kind"], "invalid-argument");
+    assert!(doc["error"]["message"].as_str().unwrap().contains("line 1"));
 }
 
 #[test]
 fn test_init_command() {
-    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    let home = tempfile::tempdir().unwrap();
+    let mut cmd = codeagent(home.path());
     cmd.arg("init").assert().success();
 }
 
 #[test]
 fn test_cleanup_command() {
-    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    let home = tempfile::tempdir().unwrap();
+    let mut cmd = codeagent(home.path());
     cmd.arg("--cleanup").assert().success();
 }
+
+#[test]
+fn test_parallel_rejects_unknown_dependency() {
+    let home = tempfile::tempdir().unwrap();
+    let mut cmd = codeagent(home.path());
+    cmd.arg("--parallel")
+        .write_stdin("---TASK---\nid: a\ndependencies: typo\n---CONTENT---\nDo it\n")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("depends on unknown task 'typo'"));
+}
+
+#[test]
+fn test_parallel_task_exit_codes() {
+    let home = tempfile::tempdir().unwrap();
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    let output = cmd
+        .env("HOME", home.path())
+        .args(["--quiet", "--parallel"])
+        .write_stdin("{\"id\": \"a\", \"task\": \"x\", \"backend\": \"nope\"}\n")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["tasks"][0]["exitCode"], 3);
+
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    let output = cmd
+        .env("HOME", home.path())
+        .args(["--quiet", "--parallel"])
+        .write_stdin("{\"id\": \"a\", \"task\": \"x\", \"agent\": \"nope\"}\n")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["tasks"][0]["error"]["kind"], "invalid-argument");
+}
+
+#[test]
+fn test_parallel_reports_cycle_path() {
+    let home = tempfile::tempdir().unwrap();
+    let mut cmd = codeagent(home.path());
+    cmd.arg("--parallel")
+        .write_stdin(
+            "---TASK---\nid: a\ndependencies: b\n---CONTENT---\nx\n\
+             ---TASK---\nid: b\ndependencies: a\n---CONTENT---\ny\n",
+        )
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("a -> b -> a"));
+}
+
+#[test]
+fn test_resume_invalid_session_exit_code() {
+    let home = tempfile::tempdir().unwrap();
+    let mut cmd = codeagent(home.path());
+    cmd.args(["resume", "bad session!", "continue"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("Invalid session ID"));
+}
+
+/// Run codeagent with the mock backend in an isolated home directory
+fn mock_cmd(home: &std::path::Path) -> Command {
+    let mut cmd = codeagent(home);
+    cmd.env_remove("CODEAGENT_MOCK_FILE")
+        .env_remove("CODEAGENT_MOCK_EXIT_CODE")
+        .env_remove("CODEAGENT_MOCK_DELAY_MS")
+        .env_remove("CODEAGENT_MOCK_STDERR")
+        .env_remove("CODEAGENT_MOCK_ATTEMPT")
+        .args(["--quiet", "--backend", "mock"]);
+    cmd
+}
+
+#[test]
+fn test_mock_backend_echoes_prompt() {
+    let home = tempfile::tempdir().unwrap();
+    // Quotes and newlines send the prompt over stdin
+    let output = mock_cmd(home.path())
+        .arg("say \"hi\"\nplease")
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["message"], "say \"hi\"\nplease");
+    assert_eq!(doc["sessionId"], "mock-session");
+    assert_eq!(doc["backend"], "mock");
+}
+
+#[test]
+fn test_mock_replay_failure() {
+    let home = tempfile::tempdir().unwrap();
+    let replay = home.path().join("fail.jsonl");
+    std::fs::write(
+        &replay,
+        "{\"type\":\"thread.started\",\"thread_id\":\"t1\"}\n\
+         {\"mock\":\"stderr\",\"line\":\"Error: 429 Too Many Requests\"}\n\
+         {\"mock\":\"exit\",\"code\":1}\n",
+    )
+    .unwrap();
+
+    let output = mock_cmd(home.path())
+        .args(["--mock-file", replay.to_str().unwrap(), "task"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(4));
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["sessionId"], "t1");
+    assert_eq!(doc["error"]["kind"], "rate-limited");
+
+    // --mock-exit-code overrides the recorded exit code, the environment is a fallback
+    let output = mock_cmd(home.path())
+        .args([
+            "--mock-exit-code",
+            "0",
+            "--mock-file",
+            replay.to_str().unwrap(),
+            "task",
+        ])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = mock_cmd(home.path())
+        .env("CODEAGENT_MOCK_FILE", replay.to_str().unwrap())
+        .env("CODEAGENT_MOCK_EXIT_CODE", "0")
+        .arg("task")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["sessionId"], "t1");
+}
+
+#[test]
+fn test_mock_replay_timeout() {
+    let home = tempfile::tempdir().unwrap();
+    let replay = home.path().join("slow.jsonl");
+    std::fs::write(
+        &replay,
+        "{\"type\":\"system\",\"subtype\":\"init\",\"session_id\":\"sess-timeout-1\"}\n\
+         {\"mock\":\"sleep\",\"ms\":10000}\n",
+    )
+    .unwrap();
+
+    let output = mock_cmd(home.path())
+        .args([
+            "--timeout",
+            "1",
+            "--mock-file",
+            replay.to_str().unwrap(),
+            "task",
+        ])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(5));
+
+    // What the backend produced before the timeout is kept
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["success"], false);
+    assert_eq!(doc["sessionId"], "sess-timeout-1");
+    assert_eq!(doc["error"]["kind"], "timeout");
+    assert!(doc["duration"].as_u64().unwrap() >= 1000);
+    assert!(
+        home.path()
+            .join(".codeagent/sessions/sess-timeout-1.json")
+            .exists()
+    );
+
+    // The history gets the real duration, not zero
+    let history = std::fs::read_to_string(home.path().join(".codeagent/history.jsonl")).unwrap();
+    let entry: serde_json::Value = serde_json::from_str(history.trim()).unwrap();
+    assert_eq!(entry["errorKind"], "timeout");
+    assert!(entry["durationMs"].as_u64().unwrap() >= 1000);
+}
+
+#[test]
+fn test_mock_parallel_task_mock_file() {
+    let home = tempfile::tempdir().unwrap();
+    let replay = home.path().join("fail.jsonl");
+    std::fs::write(&replay, "{\"mock\":\"exit\",\"code\":3}\n").unwrap();
+
+    let output = mock_cmd(home.path())
+        .args(["--parallel", "--keep-going"])
+        .write_stdin(format!(
+            "{{\"id\": \"echo\", \"task\": \"hi\"}}\n\
+             {{\"id\": \"replay\", \"task\": \"hi\", \"mockFile\": \"{}\"}}\n",
+            replay.display()
+        ))
+        .output()
+        .unwrap();
+
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["tasks"][0]["status"], "success");
+    assert_eq!(doc["tasks"][1]["exitCode"], 3);
+}
+
+#[test]
+fn test_mock_parallel_dependency_outputs() {
+    let home = tempfile::tempdir().unwrap();
+    let output = mock_cmd(home.path())
+        .arg("--parallel")
+        .write_stdin(
+            "{\"id\": \"plan\", \"task\": \"step one\"}\n\
+             {\"id\": \"build\", \"task\": \"after {{plan.message}}\", \"dependencies\": [\"plan\"]}\n",
+        )
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["tasks"][1]["message"], "after step one");
+}
+
+#[test]
+fn test_record_then_replay() {
+    let home = tempfile::tempdir().unwrap();
+    let recording = home.path().join("run.jsonl");
+
+    mock_cmd(home.path())
+        .args(["--record", recording.to_str().unwrap(), "recorded answer"])
+        .assert()
+        .success();
+    let recorded = std::fs::read_to_string(&recording).unwrap();
+    assert!(recorded.ends_with("{\"code\":0,\"mock\":\"exit\"}\n"));
+
+    let output = mock_cmd(home.path())
+        .args(["--mock-file", recording.to_str().unwrap(), "ignored"])
+        .output()
+        .unwrap();
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["message"], "recorded answer");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_record_keeps_every_attempt() {
+    // Fails with a rate limit on the first call, succeeds on the second
+    let bin = fake_claude(
+        "if [ -f \"$HOME/called\" ]; then echo '{\"type\":\"result\",\"result\":\"done\"}'; \
+         else touch \"$HOME/called\"; echo 'Error: 429 Too Many Requests' >&2; exit 1; fi",
+    );
+    let recording = bin.path().join("run.jsonl");
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    cmd.env("PATH", format!("{}:/usr/bin:/bin", bin.path().display()))
+        .env("HOME", bin.path())
+        .args([
+            "--quiet",
+            "--backend",
+            "claude",
+            "--retries",
+            "1",
+            "--retry-backoff",
+            "0",
+            "--record",
+            recording.to_str().unwrap(),
+            "task",
+        ])
+        .assert()
+        .success();
+
+    let recorded = std::fs::read_to_string(&recording).unwrap();
+    assert_eq!(recorded.matches("\"mock\":\"attempt\"").count(), 2);
+
+    // The first, failed attempt can still be replayed
+    let output = mock_cmd(bin.path())
+        .args([
+            "--mock-file",
+            recording.to_str().unwrap(),
+            "--mock-attempt",
+            "1",
+            "ignored",
+        ])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(4));
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["error"]["kind"], "rate-limited");
+
+    let output = mock_cmd(bin.path())
+        .args(["--mock-file", recording.to_str().unwrap(), "ignored"])
+        .output()
+        .unwrap();
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["message"], "done");
+}
+
+#[test]
+fn test_session_registry_and_resume_last() {
+    let home = tempfile::tempdir().unwrap();
+    mock_cmd(home.path())
+        .args(["--name", "demo", "first task"])
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    let output = cmd
+        .env("HOME", home.path())
+        .args(["sessions", "list", "--json"])
+        .output()
+        .unwrap();
+    let sessions: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(sessions[0]["id"], "mock-session");
+    assert_eq!(sessions[0]["name"], "demo");
+    assert_eq!(sessions[0]["backend"], "mock");
+    assert_eq!(sessions[0]["prompt"], "first task");
+
+    let output = mock_cmd(home.path())
+        .args(["resume", "last", "next step"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    let output = cmd
+        .env("HOME", home.path())
+        .args(["sessions", "show", "demo", "--json"])
+        .output()
+        .unwrap();
+    let session: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(session["runs"], 2);
+
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    cmd.env("HOME", home.path())
+        .args(["sessions", "rm", "demo"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Removed session mock-session"));
+
+    mock_cmd(home.path())
+        .args(["resume", "last", "again"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "No recorded session matches 'last'",
+        ));
+}
+
+#[test]
+fn test_resume_uses_session_backend() {
+    let home = tempfile::tempdir().unwrap();
+    mock_cmd(home.path())
+        .args(["--name", "demo", "first task"])
+        .assert()
+        .success();
+
+    // No --backend: the session's backend (mock) is used
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    cmd.env("HOME", home.path())
+        .env_remove("CODEAGENT_BACKEND")
+        .env_remove("CODEAGENT_MOCK_FILE")
+        .args(["--quiet", "resume", "demo", "next step"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("next step"));
+
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    cmd.env("HOME", home.path())
+        .args(["--quiet", "--backend", "claude", "resume", "demo", "again"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "Session 'mock-session' was created with mock and cannot be resumed with claude",
+        ));
+}
+
+#[test]
+fn test_handoff_prepends_transcript_summary() {
+    let home = tempfile::tempdir().unwrap();
+    mock_cmd(home.path())
+        .args(["--name", "demo", "Fix the login bug"])
+        .assert()
+        .success();
+
+    // The mock backend echoes its prompt, so the reply shows the handed-off context
+    let output = mock_cmd(home.path())
+        .args([
+            "--message-only",
+            "handoff",
+            "demo",
+            "--to",
+            "mock",
+            "Add tests",
+        ])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let message = String::from_utf8_lossy(&output.stdout);
+    assert!(message.starts_with("# Context handed off from a mock session"));
+    assert!(message.contains("## Turn 1 (mock)\nUser: Fix the login bug"));
+    assert!(message.contains("Assistant: Fix the login bug"));
+    assert!(message.trim_end().ends_with("# Next task\n\nAdd tests"));
+
+    mock_cmd(home.path())
+        .args(["handoff", "nope", "--to", "mock", "Add tests"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "No recorded session matches 'nope'",
+        ));
+}
+
+#[test]
+fn test_export_session_and_run_output() {
+    let home = tempfile::tempdir().unwrap();
+    let output = mock_cmd(home.path())
+        .args(["--name", "demo", "Fix the login bug"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let run_file = home.path().join("run.json");
+    std::fs::write(&run_file, &output.stdout).unwrap();
+
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    cmd.env("HOME", home.path())
+        .args(["export", "demo"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("# Session mock-session (demo)"))
+        .stdout(predicate::str::contains("> Fix the login bug"))
+        .stdout(predicate::str::contains(
+            "**Assistant**\n\nFix the login bug",
+        ));
+
+    let json_file = home.path().join("export.json");
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    cmd.env("HOME", home.path())
+        .args(["export", "last", "--format", "json", "-o"])
+        .arg(&json_file)
+        .assert()
+        .success();
+    let exported: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&json_file).unwrap()).unwrap();
+    assert_eq!(exported["session"]["backend"], "mock");
+    assert_eq!(exported["turns"][0]["prompt"], "Fix the login bug");
+
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    cmd.env("HOME", home.path())
+        .args(["export", "--format", "html"])
+        .arg(&run_file)
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("<!DOCTYPE html>"))
+        .stdout(predicate::str::contains("<title>run.json</title>"));
+}
+
+#[test]
+fn test_history_filters_and_stats() {
+    let home = tempfile::tempdir().unwrap();
+    mock_cmd(home.path()).arg("first").assert().success();
+    mock_cmd(home.path())
+        .env("CODEAGENT_MOCK_EXIT_CODE", "1")
+        .arg("second")
+        .assert()
+        .failure();
+
+    let history = |args: &[&str]| -> serde_json::Value {
+        let mut cmd = Command::cargo_bin("codeagent").unwrap();
+        let output = cmd
+            .env("HOME", home.path())
+            .arg("history")
+            .args(args)
+            .arg("--json")
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(0));
+        serde_json::from_slice(&output.stdout).unwrap()
+    };
+
+    let runs = history(&[]);
+    assert_eq!(runs.as_array().unwrap().len(), 2);
+    assert_eq!(runs[0]["status"], "failed");
+    assert_eq!(runs[0]["backend"], "mock");
+    assert_eq!(runs[1]["status"], "success");
+
+    assert_eq!(
+        history(&["--status", "success"]).as_array().unwrap().len(),
+        1
+    );
+    assert_eq!(
+        history(&["--backend", "codex"]).as_array().unwrap().len(),
+        0
+    );
+    assert_eq!(
+        history(&["--since", "2000-01-01", "--until", "2000-12-31"]),
+        serde_json::json!([])
+    );
+
+    let stats = history(&["--stats"]);
+    assert_eq!(stats["total"], 2);
+    assert_eq!(stats["backends"][0]["backend"], "mock");
+    assert_eq!(stats["backends"][0]["successRate"], 0.5);
+    assert_eq!(stats["days"].as_array().unwrap().len(), 1);
+
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    cmd.env("HOME", home.path())
+        .args(["history", "--since", "someday"])
+        .assert()
+        .code(2);
+}
+
+/// Install fake backend executables running the given scripts and return their directory
+#[cfg(unix)]
+fn fake_backends(backends: &[(&str, &str)]) -> tempfile::TempDir {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    for (name, script) in backends {
+        let path = dir.path().join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    dir
+}
+
+/// Install a fake `claude` executable running `script` and return its directory
+#[cfg(unix)]
+fn fake_claude(script: &str) -> tempfile::TempDir {
+    fake_backends(&[("claude", script)])
+}
+
+#[cfg(unix)]
+#[test]
+fn test_backend_failure_exit_code() {
+    let bin = fake_claude("echo 'Error: Invalid API key' >&2; exit 7");
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    cmd.env("PATH", format!("{}:/usr/bin:/bin", bin.path().display()))
+        .env("HOME", bin.path())
+        .args(["--quiet", "--backend", "claude", "task"])
+        .assert()
+        .code(4)
+        .stdout(predicate::str::contains("\"kind\": \"auth-missing\""));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_backend_timeout_exit_code() {
+    let bin = fake_claude("sleep 10 & wait");
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    cmd.env("PATH", format!("{}:/usr/bin:/bin", bin.path().display()))
+        .env("HOME", bin.path())
+        .args(["--quiet", "--timeout", "1", "--backend", "claude", "task"])
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("timeout after 1 seconds"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_backend_idle_timeout_exit_code() {
+    let bin = fake_claude("echo 'warming up' >&2; sleep 10 & wait");
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    cmd.env("PATH", format!("{}:/usr/bin:/bin", bin.path().display()))
+        .env("HOME", bin.path())
+        .args([
+            "--quiet",
+            "--idle-timeout",
+            "1",
+            "--backend",
+            "claude",
+            "task",
+        ])
+        .assert()
+        .code(6)
+        .stdout(predicate::str::contains("\"kind\": \"idle-timeout\""));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_parallel_task_idle_timeout() {
+    let bin = fake_claude(
+        "case \"$*\" in *slow*) sleep 10 & wait ;; *) echo '{\"type\":\"result\",\"result\":\"ok\"}' ;; esac",
+    );
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    let output = cmd
+        .env("PATH", format!("{}:/usr/bin:/bin", bin.path().display()))
+        .env("HOME", bin.path())
+        .args([
+            "--quiet",
+            "--parallel",
+            "--keep-going",
+            "--backend",
+            "claude",
+        ])
+        .write_stdin(
+            "{\"id\": \"fast\", \"task\": \"fast\"}\n\
+             {\"id\": \"slow\", \"task\": \"slow\", \"idleTimeout\": 1}\n",
+        )
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(6));
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["tasks"][0]["status"], "success");
+    assert_eq!(doc["tasks"][1]["error"]["kind"], "idle-timeout");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_retries_record_attempts() {
+    // Fails with a rate limit on the first call, succeeds on the second
+    let bin = fake_claude(
+        "if [ -f \"$HOME/called\" ]; then echo '{\"type\":\"result\",\"result\":\"done\"}'; \
+         else touch \"$HOME/called\"; echo 'Error: 429 Too Many Requests' >&2; exit 1; fi",
+    );
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    let output = cmd
+        .env("PATH", format!("{}:/usr/bin:/bin", bin.path().display()))
+        .env("HOME", bin.path())
+        .args([
+            "--quiet",
+            "--backend",
+            "claude",
+            "--retries",
+            "2",
+            "--retry-backoff",
+            "0",
+            "--retry-on",
+            "rate-limited",
+            "task",
+        ])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["message"], "done");
+    let attempts = doc["attempts"].as_array().unwrap();
+    assert_eq!(attempts.len(), 2);
+    assert_eq!(attempts[0]["error"]["kind"], "rate-limited");
+    assert!(attempts[1]["error"].is_null());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_retry_on_skips_other_failures() {
+    let bin = fake_claude("echo 'Error: Invalid API key' >&2; exit 1");
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    let output = cmd
+        .env("PATH", format!("{}:/usr/bin:/bin", bin.path().display()))
+        .env("HOME", bin.path())
+        .args([
+            "--quiet",
+            "--backend",
+            "claude",
+            "--retries",
+            "3",
+            "--retry-backoff",
+            "0",
+            "--retry-on",
+            "rate-limited,network",
+            "task",
+        ])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(4));
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["attempts"].as_array().unwrap().len(), 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_backend_fallback_chain() {
+    // gemini is not installed, codex has no credentials, claude answers
+    let bin = fake_backends(&[
+        ("codex", "echo 'Error: 401 Unauthorized' >&2; exit 1"),
+        (
+            "claude",
+            "echo '{\"type\":\"result\",\"result\":\"from claude\"}'",
+        ),
+    ]);
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    let output = cmd
+        .env("PATH", format!("{}:/usr/bin:/bin", bin.path().display()))
+        .env("HOME", bin.path())
+        .args(["--quiet", "--backend", "gemini,codex,claude", "task"])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["backend"], "claude");
+    assert_eq!(doc["message"], "from claude");
+    let skipped = doc["skippedBackends"].as_array().unwrap();
+    assert_eq!(skipped[0]["backend"], "gemini");
+    assert_eq!(skipped[0]["reason"], "not found on PATH");
+    assert_eq!(skipped[1]["backend"], "codex");
+    assert!(
+        skipped[1]["reason"]
+            .as_str()
+            .unwrap()
+            .starts_with("auth-missing")
+    );
+
+    // Both backends that ran are in the history; the missing one is not
+    let history = std::fs::read_to_string(bin.path().join(".codeagent/history.jsonl")).unwrap();
+    let entries: Vec<serde_json::Value> = history
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["backend"], "codex");
+    assert_eq!(entries[0]["status"], "failed");
+    assert_eq!(entries[0]["errorKind"], "auth-missing");
+    assert_eq!(entries[1]["backend"], "claude");
+    assert_eq!(entries[1]["status"], "success");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_backend_fallback_notes_dropped_model() {
+    let bin = fake_backends(&[(
+        "claude",
+        "echo '{\"type\":\"result\",\"result\":\"from claude\"}'",
+    )]);
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    let output = cmd
+        .env("PATH", format!("{}:/usr/bin:/bin", bin.path().display()))
+        .env("HOME", bin.path())
+        .args([
+            "--quiet",
+            "--backend",
+            "codex,claude",
+            "--model",
+            "gpt-5",
+            "task",
+        ])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        doc["skippedBackends"][0]["reason"],
+        "not found on PATH; claude runs its default model instead of 'gpt-5'"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_backend_fallback_chain_exhausted() {
+    let bin = fake_backends(&[]);
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    let output = cmd
+        .env("PATH", format!("{}:/usr/bin:/bin", bin.path().display()))
+        .env("HOME", bin.path())
+        .args(["--quiet", "--backend", "claude,codex", "task"])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(3));
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["error"]["kind"], "backend-not-found");
+    assert_eq!(doc["backend"], "codex");
+    assert_eq!(doc["skippedBackends"][0]["backend"], "claude");
+    assert_eq!(doc["skippedBackends"][0]["reason"], "not found on PATH");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_custom_backend_from_config() {
+    // Echoes its arguments and stdin back as a Codex agent message
+    let bin = fake_backends(&[(
+        "local-agent",
+        "input=$(cat); printf '{\"type\":\"item.completed\",\"item\":{\"type\":\"agent_message\",\"text\":\"%s|%s\"}}\\n' \"$*\" \"$input\"",
+    )]);
+    std::fs::create_dir(bin.path().join(".codeagent")).unwrap();
+    std::fs::write(
+        bin.path().join(".codeagent").join("backends.yaml"),
+        "local:\n  command: local-agent\n  args: [exec]\n  model: [--model, \"{model}\"]\n  stdin: true\n  dialect: codex\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    let output = cmd
+        .env("PATH", format!("{}:/usr/bin:/bin", bin.path().display()))
+        .env("HOME", bin.path())
+        .args(["--quiet", "--backend", "local", "--model", "m1", "hello"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["backend"], "local");
+    assert_eq!(doc["message"], "exec --model m1|hello");
+
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    let output = cmd
+        .env("PATH", format!("{}:/usr/bin:/bin", bin.path().display()))
+        .env("HOME", bin.path())
+        .args(["--quiet", "--parallel"])
+        .write_stdin("{\"id\": \"a\", \"task\": \"first\", \"backend\": \"local\"}\n")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["tasks"][0]["message"], "exec|first");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unsupported_option_warns_or_fails_strict() {
+    let bin = fake_claude("echo '{\"type\":\"result\",\"result\":\"ok\"}'");
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    cmd.env("PATH", format!("{}:/usr/bin:/bin", bin.path().display()))
+        .env("HOME", bin.path())
+        .args(["--backend", "claude", "--reasoning-effort", "high", "task"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "backend 'claude' does not support --reasoning-effort",
+        ));
+
+    // Backends of the chain that never run are not checked
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    cmd.env("PATH", format!("{}:/usr/bin:/bin", bin.path().display()))
+        .env("HOME", bin.path())
+        .args([
+            "--backend",
+            "gemini,claude",
+            "--reasoning-effort",
+            "high",
+            "task",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("backend 'gemini' does not support").not())
+        .stderr(predicate::str::contains(
+            "backend 'claude' does not support --reasoning-effort",
+        ));
+
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    cmd.env("PATH", format!("{}:/usr/bin:/bin", bin.path().display()))
+        .env("HOME", bin.path())
+        .args([
+            "--strict",
+            "--backend",
+            "claude",
+            "--reasoning-effort",
+            "high",
+            "task",
+        ])
+        .assert()
+        .code(2)
+        .stdout(predicate::str::contains("\"kind\": \"invalid-argument\""));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_doctor_json_report() {
+    let bin = fake_backends(&[
+        ("claude", "echo '2.0.14 (Claude Code)'"),
+        ("codex", "echo 'codex-cli 9.0.0'"),
+    ]);
+    std::fs::create_dir(bin.path().join(".claude")).unwrap();
+    std::fs::write(bin.path().join(".claude/.credentials.json"), "{}").unwrap();
+
+    let mut cmd = Command::cargo_bin("codeagent").unwrap();
+    let output = cmd
+        .env_clear()
+        .env("PATH", format!("{}:/usr/bin:/bin", bin.path().display()))
+        .env("HOME", bin.path())
+        .args(["doctor", "--json"])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let backend = |name: &str| {
+        doc["backends"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|b| b["name"] == name)
+            .unwrap()
+            .clone()
+    };
+
+    let claude = backend("claude");
+    assert_eq!(claude["available"], true);
+    assert_eq!(claude["version"], "2.0.14 (Claude Code)");
+    assert_eq!(claude["compatible"], true);
+    assert_eq!(claude["auth"]["configured"], true);
+    assert_eq!(claude["auth"]["sources"][0], "~/.claude/.credentials.json");
+
+    let codex = backend("codex");
+    assert_eq!(codex["compatible"], false);
+    assert_eq!(codex["auth"]["configured"], false);
+    assert_eq!(backend("gemini")["available"], false);
+    assert!(
+        doc["paths"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|p| p["name"] == "logs")
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_interrupt_stops_backend_group() {
+    use std::time::{Duration, Instant};
+
+    let bin = fake_claude("sleep 30 & wait");
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("codeagent"))
+        .env("PATH", format!("{}:/usr/bin:/bin", bin.path().display()))
+        .env("HOME", bin.path())
+        .args(["--quiet", "--backend", "claude", "task"])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+
+    std::thread::sleep(Duration::from_millis(500));
+    unsafe {
+        libc::kill(child.id() as i32, libc::SIGINT);
+    }
+
+    let started = Instant::now();
+    let status = child.wait().unwrap();
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(status.code(), Some(130));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_terminate_reports_sigterm() {
+    use std::time::Duration;
+
+    let bin = fake_claude("sleep 30 & wait");
+    let child = std::process::Command::new(assert_cmd::cargo::cargo_bin("codeagent"))
+        .env("PATH", format!("{}:/usr/bin:/bin", bin.path().display()))
+        .env("HOME", bin.path())
+        .args(["--quiet", "--backend", "claude", "task"])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+
+    std::thread::sleep(Duration::from_millis(500));
+    unsafe {
+        libc::kill(child.id() as i32, libc::SIGTERM);
+    }
+
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(143));
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["error"]["kind"], "signal");
+}